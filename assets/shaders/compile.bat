@@ -1,2 +1,16 @@
 glslc voxel.comp -o voxel.spv
+glslc resolve.comp -o resolve.spv
+glslc fog.comp -o fog.spv
+glslc bloom_extract.comp -o bloom_extract.spv
+glslc bloom_blur.comp -o bloom_blur.spv
+glslc bloom_composite.comp -o bloom_composite.spv
+glslc tonemap.comp -o tonemap.spv
+glslc grade.comp -o grade.spv
+glslc fxaa.comp -o fxaa.spv
+glslc ui.vert -o ui.vert.spv
+glslc ui.frag -o ui.frag.spv
+glslc text.vert -o text.vert.spv
+glslc text.frag -o text.frag.spv
+glslc debug_draw.vert -o debug_draw.vert.spv
+glslc debug_draw.frag -o debug_draw.frag.spv
 pause
@@ -0,0 +1,41 @@
+#version 450
+
+layout (location = 0) in vec3 in_position;
+layout (location = 1) in vec4 in_color;
+
+layout (location = 0) out vec3 out_position;
+layout (location = 1) out vec4 out_color;
+
+layout (push_constant) uniform DebugDrawParameters
+{
+    // xyz is the camera position, w the tangent of half the vertical field of view.
+    vec4 camera_position;
+    // xyz is the camera's right vector, w the aspect ratio.
+    vec4 camera_right;
+    vec4 camera_up;
+    vec4 camera_forward;
+    vec2 depth_scale;
+    uint output_transfer;
+    float paper_white;
+};
+
+// Lines closer to the camera than this are clipped.
+const float NEAR_DISTANCE = 0.01;
+
+void main()
+{
+    // The inverse of the ray directions `voxel.comp` casts, with +Y down like Vulkan's clip space.
+    vec3 relative = in_position - camera_position.xyz;
+    float tan_half_fov = camera_position.w;
+    float aspect = camera_right.w;
+
+    gl_Position = vec4(
+        dot(relative, camera_right.xyz) / (tan_half_fov * aspect),
+        -dot(relative, camera_up.xyz) / tan_half_fov,
+        NEAR_DISTANCE,
+        dot(relative, camera_forward.xyz)
+    );
+
+    out_position = in_position;
+    out_color = in_color;
+}
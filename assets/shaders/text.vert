@@ -0,0 +1,25 @@
+#version 450
+
+layout (location = 0) in vec2 in_position;
+layout (location = 1) in vec2 in_uv;
+layout (location = 2) in vec4 in_color;
+
+layout (location = 0) out vec2 out_uv;
+layout (location = 1) out vec4 out_color;
+
+layout (push_constant) uniform TextParameters
+{
+    // In pixels, the unit vertex positions are in.
+    vec2 screen_size;
+    uint output_transfer;
+    // Luminance of text white in nits, for PQ output.
+    float paper_white;
+};
+
+void main()
+{
+    gl_Position = vec4(2.0 * in_position / screen_size - 1.0, 0.0, 1.0);
+
+    out_uv = in_uv;
+    out_color = in_color;
+}
@@ -0,0 +1,70 @@
+#version 450
+
+layout (location = 0) in vec2 in_uv;
+layout (location = 1) in vec4 in_color;
+
+layout (location = 0) out vec4 out_color;
+
+layout (set = 0, binding = 0) uniform sampler2D ui_texture;
+
+const uint OUTPUT_TRANSFER_LINEAR = 0;
+const uint OUTPUT_TRANSFER_SRGB = 1;
+const uint OUTPUT_TRANSFER_PQ = 2;
+
+layout (push_constant) uniform UiParameters
+{
+    vec2 screen_size;
+    uint output_transfer;
+    float paper_white;
+};
+
+vec3 srgb_to_linear(vec3 color)
+{
+    vec3 low = color / 12.92;
+    vec3 high = pow((color + 0.055) / 1.055, vec3(2.4));
+
+    return mix(high, low, lessThanEqual(color, vec3(0.04045)));
+}
+
+// SMPTE ST 2084 inverse EOTF. Input is in nits.
+vec3 nits_to_pq(vec3 nits)
+{
+    const float m1 = 0.1593017578125;
+    const float m2 = 78.84375;
+    const float c1 = 0.8359375;
+    const float c2 = 18.8515625;
+    const float c3 = 18.6875;
+
+    vec3 y = pow(clamp(nits / 10000.0, 0.0, 1.0), vec3(m1));
+
+    return pow((c1 + c2 * y) / (1.0 + c3 * y), vec3(m2));
+}
+
+vec3 rec709_to_rec2020(vec3 color)
+{
+    const mat3 conversion = mat3(
+        0.6274, 0.0691, 0.0164,
+        0.3293, 0.9195, 0.0880,
+        0.0433, 0.0114, 0.8956
+    );
+
+    return conversion * color;
+}
+
+void main()
+{
+    // Vertex and texture colors are premultiplied and sRGB encoded, which is also the space the UI expects
+    // to be blended in. Only UNORM swapchains blend there exactly.
+    vec4 color = in_color * texture(ui_texture, in_uv);
+
+    if (output_transfer == OUTPUT_TRANSFER_LINEAR)
+    {
+        color.rgb = srgb_to_linear(color.rgb);
+    }
+    else if (output_transfer == OUTPUT_TRANSFER_PQ)
+    {
+        color.rgb = nits_to_pq(rec709_to_rec2020(srgb_to_linear(color.rgb)) * paper_white);
+    }
+
+    out_color = color;
+}
@@ -0,0 +1,83 @@
+mod controller;
+
+use std::f32::consts::{PI, TAU};
+use glam::{Vec2, Vec3};
+
+pub use controller::{CameraMode, CameraRig, ControllerContext};
+
+/// A perspective camera. Rotation is pitch, yaw and roll in radians; at zero rotation the camera looks
+/// down +Z with +Y up. `voxel.comp` derives its rays from the same convention.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub position: Vec3,
+    pub rotation: Vec3,
+    /// Vertical field of view in radians.
+    pub fov: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            position: Vec3::ZERO,
+            rotation: Vec3::ZERO,
+            fov: 70f32.to_radians(),
+        }
+    }
+}
+
+impl Camera {
+    pub fn forward(&self) -> Vec3 {
+        let (sin_pitch, cos_pitch) = self.rotation.x.sin_cos();
+        let (sin_yaw, cos_yaw) = self.rotation.y.sin_cos();
+
+        Vec3::new(sin_yaw * cos_pitch, sin_pitch, cos_yaw * cos_pitch)
+    }
+
+    pub fn right(&self) -> Vec3 {
+        let right = Vec3::Y.cross(self.forward()).normalize_or(Vec3::X);
+        let up = self.forward().cross(right);
+        let (sin_roll, cos_roll) = self.rotation.z.sin_cos();
+
+        right * cos_roll + up * sin_roll
+    }
+
+    pub fn up(&self) -> Vec3 {
+        self.forward().cross(self.right())
+    }
+
+    /// Direction of the ray through `ndc`, which runs from -1.0 to 1.0 across the screen with +Y down.
+    pub fn ray_direction(&self, ndc: Vec2, aspect: f32) -> Vec3 {
+        let tan_half_fov = (self.fov * 0.5).tan();
+
+        (self.right() * ndc.x * tan_half_fov * aspect - self.up() * ndc.y * tan_half_fov + self.forward()).normalize()
+    }
+
+    /// Where `point` appears on screen, in the same coordinates [`Camera::ray_direction`] takes. None if
+    /// the point is behind the camera.
+    pub fn project(&self, point: Vec3, aspect: f32) -> Option<Vec2> {
+        let relative = point - self.position;
+        let depth = relative.dot(self.forward());
+
+        if depth <= 0.0 {
+            return None;
+        }
+
+        let tan_half_fov = (self.fov * 0.5).tan();
+
+        Some(Vec2::new(
+            relative.dot(self.right()) / (depth * tan_half_fov * aspect),
+            -relative.dot(self.up()) / (depth * tan_half_fov),
+        ))
+    }
+
+    /// Blends from this camera to `other`. Angles turn the short way round.
+    pub fn lerp(&self, other: &Camera, t: f32) -> Camera {
+        let turn = (other.rotation - self.rotation).to_array().map(|angle| (angle + PI).rem_euclid(TAU) - PI);
+
+        Camera {
+            position: self.position.lerp(other.position, t),
+            rotation: self.rotation + Vec3::from_array(turn) * t,
+            fov: self.fov + (other.fov - self.fov) * t,
+        }
+    }
+}
@@ -0,0 +1,169 @@
+mod free_fly;
+mod orbit;
+mod walk;
+
+use glam::{Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+use crate::input::{Axis, Input};
+use super::Camera;
+use free_fly::FreeFlyController;
+use orbit::OrbitController;
+use walk::WalkController;
+
+/// Radians the camera turns per pixel of mouse motion.
+const LOOK_SENSITIVITY: f32 = 0.0025;
+/// How far the camera can look up or down, short of straight, where yaw stops meaning anything.
+const MAX_PITCH: f32 = 1.55;
+/// Seconds it takes to blend to the new controller's view after switching.
+const TRANSITION_TIME: f32 = 0.4;
+
+/// What controllers need to know about the world and the window.
+pub struct ControllerContext<'a> {
+    /// Top of the highest solid ground at or below a point, if there is any.
+    pub ground_height: &'a dyn Fn(Vec3) -> Option<f32>,
+    /// Cursor position in pixels, while it's over the window.
+    pub cursor: Option<Vec2>,
+    pub window_size: Vec2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CameraMode {
+    /// Flies through everything, for debugging.
+    FreeFly,
+    /// Walks on the ground in first person.
+    Walk,
+    /// Looks down on a point from a distance, for building.
+    Orbit,
+}
+
+impl CameraMode {
+    pub fn next(self) -> Self {
+        match self {
+            CameraMode::FreeFly => CameraMode::Walk,
+            CameraMode::Walk => CameraMode::Orbit,
+            CameraMode::Orbit => CameraMode::FreeFly,
+        }
+    }
+}
+
+struct Transition {
+    from: Camera,
+    elapsed: f32,
+}
+
+/// Moves the camera with one of the controllers. Movement runs in fixed steps and is interpolated between
+/// them for each frame, while mouse look and zoom follow every frame.
+pub struct CameraRig {
+    mode: CameraMode,
+    free_fly: FreeFlyController,
+    walk: WalkController,
+    orbit: OrbitController,
+    transition: Option<Transition>,
+    /// Where movement is between the last step and the next, from 0.0 to 1.0.
+    alpha: f32,
+}
+
+impl CameraRig {
+    pub fn new(mode: CameraMode, camera: &Camera) -> Self {
+        let mut rig = Self {
+            mode,
+            free_fly: FreeFlyController::default(),
+            walk: WalkController::default(),
+            orbit: OrbitController::default(),
+            transition: None,
+            alpha: 1.0,
+        };
+
+        rig.teleport(camera);
+
+        rig
+    }
+
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    /// Switches to `mode`, which takes over from the current view and blends to its own.
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        if mode == self.mode {
+            return;
+        }
+
+        let from = self.camera();
+
+        self.mode = mode;
+        self.teleport(&from);
+        self.transition = Some(Transition { from, elapsed: 0.0 });
+    }
+
+    /// Moves the current controller to `camera` without blending, for when the camera is edited directly.
+    pub fn teleport(&mut self, camera: &Camera) {
+        match self.mode {
+            CameraMode::FreeFly => self.free_fly.take_over(camera),
+            CameraMode::Walk => self.walk.take_over(camera),
+            CameraMode::Orbit => self.orbit.take_over(camera),
+        }
+
+        self.transition = None;
+    }
+
+    /// Whether the controller looks around with the mouse, so the cursor should be grabbed and hidden.
+    pub fn grabs_cursor(&self) -> bool {
+        self.mode != CameraMode::Orbit
+    }
+
+    /// Applies what changes every frame, like mouse look. `delta_time` is the frame time in seconds.
+    pub fn update(&mut self, input: &Input, delta_time: f32) {
+        match self.mode {
+            CameraMode::FreeFly => self.free_fly.look(input),
+            CameraMode::Walk => self.walk.look(input),
+            CameraMode::Orbit => self.orbit.look(input),
+        }
+
+        if let Some(transition) = &mut self.transition {
+            transition.elapsed += delta_time;
+
+            if transition.elapsed >= TRANSITION_TIME {
+                self.transition = None;
+            }
+        }
+    }
+
+    /// Moves by one fixed step of `delta_time` seconds.
+    pub fn step(&mut self, input: &Input, context: &ControllerContext, delta_time: f32) {
+        match self.mode {
+            CameraMode::FreeFly => self.free_fly.step(input, delta_time),
+            CameraMode::Walk => self.walk.step(input, context, delta_time),
+            CameraMode::Orbit => self.orbit.step(input, context, delta_time),
+        }
+    }
+
+    /// Sets how far the frame is between the last step and the next.
+    pub fn set_alpha(&mut self, alpha: f32) {
+        self.alpha = alpha.clamp(0.0, 1.0);
+    }
+
+    pub fn camera(&self) -> Camera {
+        let camera = match self.mode {
+            CameraMode::FreeFly => self.free_fly.camera(self.alpha),
+            CameraMode::Walk => self.walk.camera(self.alpha),
+            CameraMode::Orbit => self.orbit.camera(self.alpha),
+        };
+
+        match &self.transition {
+            Some(transition) => {
+                let t = (transition.elapsed / TRANSITION_TIME).clamp(0.0, 1.0);
+                transition.from.lerp(&camera, t * t * (3.0 - 2.0 * t))
+            }
+            None => camera,
+        }
+    }
+}
+
+/// Turns `rotation` by this frame's mouse look.
+fn look(rotation: &mut Vec3, input: &Input) {
+    rotation.y += input.axis(Axis::LookX) * LOOK_SENSITIVITY;
+    rotation.x = (rotation.x - input.axis(Axis::LookY) * LOOK_SENSITIVITY).clamp(-MAX_PITCH, MAX_PITCH);
+    rotation.z = 0.0;
+}
@@ -0,0 +1,44 @@
+use glam::Vec3;
+use crate::camera::Camera;
+use crate::input::{Action, Axis, Input};
+
+/// Meters per second.
+const SPEED: f32 = 10.0;
+const SPRINT_MULTIPLIER: f32 = 4.0;
+
+/// Flies where the camera looks, ignoring the world.
+#[derive(Default)]
+pub struct FreeFlyController {
+    camera: Camera,
+    previous_position: Vec3,
+}
+
+impl FreeFlyController {
+    pub fn take_over(&mut self, camera: &Camera) {
+        self.camera = *camera;
+        self.camera.rotation.z = 0.0;
+        self.previous_position = camera.position;
+    }
+
+    pub fn look(&mut self, input: &Input) {
+        super::look(&mut self.camera.rotation, input);
+    }
+
+    pub fn step(&mut self, input: &Input, delta_time: f32) {
+        let direction = self.camera.forward() * input.axis(Axis::MoveForward)
+            + self.camera.right() * input.axis(Axis::MoveRight)
+            + Vec3::Y * input.axis(Axis::MoveUp);
+
+        let speed = if input.is_held(Action::Sprint) { SPEED * SPRINT_MULTIPLIER } else { SPEED };
+
+        self.previous_position = self.camera.position;
+        self.camera.position += direction.clamp_length_max(1.0) * speed * delta_time;
+    }
+
+    pub fn camera(&self, alpha: f32) -> Camera {
+        Camera {
+            position: self.previous_position.lerp(self.camera.position, alpha),
+            ..self.camera
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use glam::{Vec2, Vec3};
+use crate::camera::Camera;
+use crate::input::{Action, Axis, Input};
+use super::{ControllerContext, LOOK_SENSITIVITY, MAX_PITCH};
+
+const MIN_DISTANCE: f32 = 4.0;
+const MAX_DISTANCE: f32 = 150.0;
+/// Shallowest angle the camera looks down at the focus. The steepest is straight down, for a top-down view.
+const MIN_PITCH: f32 = 0.2;
+/// Factor the distance changes by per line scrolled.
+const ZOOM_STEP: f32 = 1.15;
+/// How quickly the distance follows zooming. Higher is snappier.
+const ZOOM_SHARPNESS: f32 = 12.0;
+/// Panning speed per meter of distance, so it covers about the same part of the screen at any zoom.
+const PAN_SPEED: f32 = 1.2;
+/// Pixels from the edge of the window where the cursor pans the camera.
+const EDGE_PAN_MARGIN: f32 = 8.0;
+
+/// Circles a focus point on the ground, panned with the movement keys or by moving the cursor to the
+/// edge of the window, and zoomed with the wheel.
+pub struct OrbitController {
+    focus: Vec3,
+    previous_focus: Vec3,
+    yaw: f32,
+    /// Angle below the horizon the camera looks at the focus from.
+    pitch: f32,
+    distance: f32,
+    previous_distance: f32,
+    /// Distance being zoomed towards.
+    target_distance: f32,
+    fov: f32,
+}
+
+impl Default for OrbitController {
+    fn default() -> Self {
+        OrbitController {
+            focus: Vec3::ZERO,
+            previous_focus: Vec3::ZERO,
+            yaw: 0.0,
+            pitch: 0.8,
+            distance: 30.0,
+            previous_distance: 30.0,
+            target_distance: 30.0,
+            fov: Camera::default().fov,
+        }
+    }
+}
+
+impl OrbitController {
+    /// Focuses on the ground where `camera` looks, or straight ahead of it if it looks above the horizon.
+    pub fn take_over(&mut self, camera: &Camera) {
+        let forward = camera.forward();
+        let distance = if forward.y < -0.01 { camera.position.y / -forward.y } else { self.target_distance };
+
+        self.distance = distance.clamp(MIN_DISTANCE, MAX_DISTANCE);
+        self.previous_distance = self.distance;
+        self.target_distance = self.distance;
+        self.yaw = camera.rotation.y;
+        self.pitch = (-camera.rotation.x).clamp(MIN_PITCH, MAX_PITCH);
+        self.fov = camera.fov;
+
+        let horizontal = Vec3::new(forward.x, 0.0, forward.z).normalize_or_zero();
+        self.focus = camera.position + horizontal * self.distance * self.pitch.cos();
+        self.focus.y = 0.0;
+        self.previous_focus = self.focus;
+    }
+
+    pub fn look(&mut self, input: &Input) {
+        if input.is_held(Action::RotateCamera) {
+            self.yaw += input.axis(Axis::LookX) * LOOK_SENSITIVITY;
+            self.pitch = (self.pitch + input.axis(Axis::LookY) * LOOK_SENSITIVITY).clamp(MIN_PITCH, MAX_PITCH);
+        }
+
+        let zoom = ZOOM_STEP.powf(-input.axis(Axis::Zoom));
+        self.target_distance = (self.target_distance * zoom).clamp(MIN_DISTANCE, MAX_DISTANCE);
+    }
+
+    pub fn step(&mut self, input: &Input, context: &ControllerContext, delta_time: f32) {
+        self.previous_focus = self.focus;
+        self.previous_distance = self.distance;
+
+        let mut pan = Vec2::new(input.axis(Axis::MoveRight), input.axis(Axis::MoveForward));
+
+        if let Some(cursor) = context.cursor {
+            pan.x += (cursor.x >= context.window_size.x - EDGE_PAN_MARGIN) as u32 as f32 - (cursor.x < EDGE_PAN_MARGIN) as u32 as f32;
+            pan.y += (cursor.y < EDGE_PAN_MARGIN) as u32 as f32 - (cursor.y >= context.window_size.y - EDGE_PAN_MARGIN) as u32 as f32;
+        }
+
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let forward = Vec3::new(sin_yaw, 0.0, cos_yaw);
+        let right = Vec3::new(cos_yaw, 0.0, -sin_yaw);
+
+        let pan = pan.clamp_length_max(1.0);
+        self.focus += (right * pan.x + forward * pan.y) * PAN_SPEED * self.distance * delta_time;
+
+        self.distance += (self.target_distance - self.distance) * (1.0 - (-ZOOM_SHARPNESS * delta_time).exp());
+    }
+
+    pub fn camera(&self, alpha: f32) -> Camera {
+        let focus = self.previous_focus.lerp(self.focus, alpha);
+        let distance = self.previous_distance + (self.distance - self.previous_distance) * alpha;
+
+        let mut camera = Camera {
+            position: Vec3::ZERO,
+            rotation: Vec3::new(-self.pitch, self.yaw, 0.0),
+            fov: self.fov,
+        };
+        camera.position = focus - camera.forward() * distance;
+
+        camera
+    }
+}
@@ -0,0 +1,99 @@
+use glam::{Vec2, Vec3};
+use crate::camera::Camera;
+use crate::input::{Action, Axis, Input};
+use super::ControllerContext;
+
+/// Meters per second.
+const WALK_SPEED: f32 = 4.5;
+const SPRINT_SPEED: f32 = 7.5;
+/// Meters per second squared.
+const GRAVITY: f32 = 20.0;
+/// Upward speed at the start of a jump, in meters per second.
+const JUMP_SPEED: f32 = 7.0;
+/// Height of the eyes above the feet.
+const EYE_HEIGHT: f32 = 1.6;
+/// Highest ledge that is walked up rather than blocking the way.
+const STEP_HEIGHT: f32 = 0.6;
+/// How far below the ground the walker can fall before being put back where they started.
+const FALL_LIMIT: f32 = 64.0;
+
+/// Walks on the ground in first person. Jumping is bound to moving up.
+#[derive(Default)]
+pub struct WalkController {
+    rotation: Vec3,
+    fov: f32,
+    feet: Vec3,
+    previous_feet: Vec3,
+    vertical_speed: f32,
+    is_grounded: bool,
+    /// Where the walker is put back after falling out of the world.
+    spawn: Vec3,
+}
+
+impl WalkController {
+    pub fn take_over(&mut self, camera: &Camera) {
+        self.rotation = Vec3::new(camera.rotation.x, camera.rotation.y, 0.0);
+        self.fov = camera.fov;
+        self.feet = camera.position - Vec3::Y * EYE_HEIGHT;
+        self.previous_feet = self.feet;
+        self.vertical_speed = 0.0;
+        self.is_grounded = false;
+        self.spawn = self.feet;
+    }
+
+    pub fn look(&mut self, input: &Input) {
+        super::look(&mut self.rotation, input);
+    }
+
+    pub fn step(&mut self, input: &Input, context: &ControllerContext, delta_time: f32) {
+        self.previous_feet = self.feet;
+
+        // Walking follows the yaw only, so looking down doesn't slow it.
+        let (sin_yaw, cos_yaw) = self.rotation.y.sin_cos();
+        let forward = Vec2::new(sin_yaw, cos_yaw);
+        let right = Vec2::new(cos_yaw, -sin_yaw);
+
+        let direction = (forward * input.axis(Axis::MoveForward) + right * input.axis(Axis::MoveRight)).clamp_length_max(1.0);
+        let speed = if input.is_held(Action::Sprint) { SPRINT_SPEED } else { WALK_SPEED };
+        let movement = direction * speed * delta_time;
+
+        // Each axis is blocked on its own, so walking into a wall at an angle slides along it.
+        for movement in [Vec3::new(movement.x, 0.0, 0.0), Vec3::new(0.0, 0.0, movement.y)] {
+            let target = self.feet + movement;
+            let ground = (context.ground_height)(target + Vec3::Y * EYE_HEIGHT);
+
+            if ground.is_none_or(|ground| ground <= self.feet.y + STEP_HEIGHT) {
+                self.feet = target;
+            }
+        }
+
+        if self.is_grounded && input.axis(Axis::MoveUp) > 0.0 {
+            self.vertical_speed = JUMP_SPEED;
+        }
+
+        self.vertical_speed -= GRAVITY * delta_time;
+        self.feet.y += self.vertical_speed * delta_time;
+
+        let ground = (context.ground_height)(self.feet + Vec3::Y * STEP_HEIGHT);
+        self.is_grounded = ground.is_some_and(|ground| self.feet.y <= ground);
+
+        if let Some(ground) = ground.filter(|_| self.is_grounded) {
+            self.feet.y = ground;
+            self.vertical_speed = 0.0;
+        }
+
+        if self.feet.y < self.spawn.y - FALL_LIMIT {
+            self.feet = self.spawn;
+            self.previous_feet = self.spawn;
+            self.vertical_speed = 0.0;
+        }
+    }
+
+    pub fn camera(&self, alpha: f32) -> Camera {
+        Camera {
+            position: self.previous_feet.lerp(self.feet, alpha) + Vec3::Y * EYE_HEIGHT,
+            rotation: self.rotation,
+            fov: self.fov,
+        }
+    }
+}
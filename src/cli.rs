@@ -0,0 +1,106 @@
+use std::path::PathBuf;
+use clap::{Parser, Subcommand};
+use log::LevelFilter;
+use crate::settings::{Settings, WindowMode};
+
+/// A voxel factory game. Options given here override the settings file for this run, without changing it.
+#[derive(Parser)]
+#[command(name = "industra", version, about)]
+pub struct Cli {
+    /// MagicaVoxel model to play in, instead of the test scene.
+    #[arg(long, global = true, value_name = "PATH")]
+    pub world: Option<PathBuf>,
+    /// Window size in logical pixels, like 1920x1080.
+    #[arg(long, value_name = "WxH", value_parser = parse_resolution)]
+    pub resolution: Option<(u32, u32)>,
+    /// Borderless fullscreen.
+    #[arg(long)]
+    pub fullscreen: bool,
+    /// Monitor to go fullscreen on, by name. The names are logged at debug level on startup.
+    #[arg(long, value_name = "NAME")]
+    pub monitor: Option<String>,
+    /// Renders with the first GPU whose name contains NAME, ignoring case.
+    #[arg(long, value_name = "NAME")]
+    pub gpu: Option<String>,
+    /// Loads the Vulkan validation layer.
+    #[arg(long)]
+    pub validation: bool,
+    /// Runs the simulation without a window or renderer, until stopped. Tools always run without one.
+    #[arg(long)]
+    pub headless: bool,
+    /// Most detailed messages to log: off, error, warn, info, debug or trace.
+    #[arg(long, global = true, value_name = "LEVEL")]
+    pub log_level: Option<LevelFilter>,
+    /// Seed for anything random. Only the benchmark's rays use it so far.
+    #[arg(long, global = true, default_value_t = 0)]
+    pub seed: u64,
+    /// Runs a dedicated server for others to join. Not supported yet, there's no multiplayer to serve.
+    #[arg(long)]
+    pub server: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Tools that work on worlds and models without opening a window.
+#[derive(Subcommand)]
+pub enum Command {
+    /// Renders a rough picture of the world on the CPU, from above, with a transparent background. Only
+    /// sunlight and hard shadows, not the game's lighting.
+    RenderThumbnail {
+        /// PNG file to write.
+        output: PathBuf,
+        /// Width and height in pixels.
+        #[arg(long, default_value_t = 256, value_parser = clap::value_parser!(u32).range(16..=4096))]
+        size: u32,
+    },
+    /// Writes the palette of a MagicaVoxel model as a material file, to tune and add to materials.toml.
+    /// Worlds loaded with --world use tuned materials over the model's own.
+    ConvertVox {
+        /// MagicaVoxel model to read.
+        input: PathBuf,
+        /// Material file to write.
+        output: PathBuf,
+    },
+    /// Times voxel lookups, building the world's octree and casting rays through it.
+    Bench {
+        /// Number of random rays to cast.
+        #[arg(long, default_value_t = 1_000_000)]
+        rays: u32,
+    },
+}
+
+impl Cli {
+    /// Overrides `settings` with the options given. Values out of range are clamped, like the file's.
+    pub fn apply_to(&self, settings: &mut Settings) {
+        if let Some((width, height)) = self.resolution {
+            settings.window.width = width;
+            settings.window.height = height;
+        }
+
+        if self.fullscreen {
+            settings.window.mode = WindowMode::Borderless;
+        }
+
+        if let Some(monitor) = &self.monitor {
+            settings.window.monitor = monitor.clone();
+        }
+
+        if let Some(gpu) = &self.gpu {
+            settings.graphics.gpu = gpu.clone();
+        }
+
+        if self.validation {
+            settings.graphics.validation = Some(true);
+        }
+
+        settings.validate();
+    }
+}
+
+fn parse_resolution(value: &str) -> Result<(u32, u32), String> {
+    let (width, height) = value.split_once(['x', 'X']).ok_or("expected WIDTHxHEIGHT, like 1920x1080")?;
+    let parse = |value: &str| value.trim().parse::<u32>().map_err(|error| format!("{:?} isn't a size, {}", value, error));
+
+    Ok((parse(width)?, parse(height)?))
+}
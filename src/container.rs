@@ -66,12 +66,48 @@ impl<T> FreeList<T> {
 
         unsafe {
             ptr::write(self.data.as_ptr().add(insert_index), value);
-            ptr::write(self.free_indices.as_ptr().add(insert_index), true);
+            ptr::write(self.free_indices.as_ptr().add(insert_index), false);
         }
 
         insert_index
     }
 
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        if !self.is_occupied(index) {
+            return None;
+        }
+
+        unsafe {
+            ptr::write(self.free_indices.as_ptr().add(index), true);
+            Some(ptr::read(self.data.as_ptr().add(index)))
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if !self.is_occupied(index) {
+            return None;
+        }
+
+        unsafe { Some(&*self.data.as_ptr().add(index)) }
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if !self.is_occupied(index) {
+            return None;
+        }
+
+        unsafe { Some(&mut *self.data.as_ptr().add(index)) }
+    }
+
+    /// Iterates over the occupied slots along with their indices.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> {
+        (0..self.cap).filter_map(move |i| self.get(i).map(|value| (i, value)))
+    }
+
+    pub fn is_occupied(&self, index: usize) -> bool {
+        index < self.cap && unsafe { !*self.free_indices.as_ptr().add(index) }
+    }
+
     pub fn as_slice(&self) -> &[T] {
         unsafe { std::slice::from_raw_parts(self.data.as_ptr(), self.cap) }
     }
@@ -130,6 +166,8 @@ impl<T> FreeList<T> {
         for i in self.cap..new_cap {
             unsafe { ptr::write(self.free_indices.as_ptr().add(i), true) };
         }
+
+        self.cap = new_cap;
     }
 
     fn find_empty_index(&self) -> Option<usize> {
@@ -149,7 +187,7 @@ impl<T> Drop for FreeList<T> {
 
         for i in 0..self.cap {
             unsafe {
-                if *self.free_indices.as_ptr().add(i)  {
+                if !*self.free_indices.as_ptr().add(i)  {
                     ptr::drop_in_place(self.data.as_ptr().add(i));
                 }
             }
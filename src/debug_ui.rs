@@ -0,0 +1,444 @@
+use egui::{ComboBox, DragValue, Slider};
+use winit::{
+    event::WindowEvent,
+    window::{Fullscreen, Window},
+};
+use crate::camera::Camera;
+use crate::display;
+use crate::input::{Action, Input, BINDINGS_FILE};
+use crate::settings;
+use crate::renderer::{
+    DebugView,
+    FramePhase,
+    RenderScale,
+    Renderer,
+    RenderSettings,
+    Tonemapper,
+    UpscaleFilter,
+    VsyncMode,
+    DEFAULT_TARGET_FRAME_TIME,
+    MAX_FRAMES_IN_FLIGHT,
+    MAX_RENDER_SCALE,
+    MIN_RENDER_SCALE,
+};
+
+const MEBIBYTE: f64 = 1024.0 * 1024.0;
+/// Frame rate the limiter starts at when it's turned on.
+const DEFAULT_FRAME_RATE_LIMIT: u32 = 60;
+
+/// Runtime inspection and tweaking, drawn with egui over the frame.
+pub struct DebugUi {
+    context: egui::Context,
+    state: egui_winit::State,
+    is_visible: bool,
+    /// The action waiting for the next chord the player presses.
+    rebinding: Option<Action>,
+}
+
+impl DebugUi {
+    pub fn new(window: &Window) -> Self {
+        let context = egui::Context::default();
+        let state = egui_winit::State::new(
+            context.clone(),
+            egui::ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            None,
+        );
+
+        Self {
+            context,
+            state,
+            is_visible: true,
+            rebinding: None,
+        }
+    }
+
+    /// Passes a window event to the UI. Returns true if the UI consumed it, in which case the game shouldn't
+    /// react to it as well.
+    pub fn on_window_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        self.is_visible && self.state.on_window_event(window, event).consumed
+    }
+
+    pub fn toggle_visible(&mut self) {
+        self.is_visible = !self.is_visible;
+    }
+
+    /// Whether the pointer is over the UI, so the game shouldn't pick through it.
+    pub fn wants_pointer(&self) -> bool {
+        self.is_visible && self.context.wants_pointer_input()
+    }
+
+    /// Lays out the panels and hands the result to the renderer for the next frame. Edits to the camera
+    /// are written back to `camera`, and returns whether there were any; edits to the render settings and
+    /// bindings are applied directly.
+    pub fn update(&mut self, window: &Window, renderer: &mut Renderer, camera: &mut Camera, input: &mut Input) -> bool {
+        if let Some(action) = self.rebinding {
+            if let Some(chord) = input.pressed_chord() {
+                let bindings = input.bindings_mut();
+                bindings.unbind(action);
+                bindings.bind(action, chord);
+
+                self.rebinding = None;
+            }
+        }
+
+        let raw_input = self.state.take_egui_input(window);
+        let mut is_camera_edited = false;
+
+        let output = self.context.run(raw_input, |context| {
+            if !self.is_visible {
+                return;
+            }
+
+            egui::Window::new("Debug").default_width(280.0).show(context, |ui| {
+                egui::CollapsingHeader::new("Frame").default_open(true).show(ui, |ui| frame_panel(ui, renderer));
+                egui::CollapsingHeader::new("Camera").show(ui, |ui| is_camera_edited = camera_panel(ui, camera));
+                egui::CollapsingHeader::new("Renderer").show(ui, |ui| renderer_panel(ui, renderer));
+                egui::CollapsingHeader::new("World").show(ui, |ui| world_panel(ui, renderer));
+                egui::CollapsingHeader::new("Memory").show(ui, |ui| memory_panel(ui, renderer));
+                egui::CollapsingHeader::new("Display").show(ui, |ui| display_panel(ui, window));
+                egui::CollapsingHeader::new("Input").show(ui, |ui| input_panel(ui, input, &mut self.rebinding));
+            });
+        });
+
+        self.state.handle_platform_output(window, output.platform_output);
+
+        let primitives = self.context.tessellate(output.shapes, output.pixels_per_point);
+        renderer.set_ui(&output.textures_delta, primitives, output.pixels_per_point);
+
+        is_camera_edited
+    }
+}
+
+fn frame_panel(ui: &mut egui::Ui, renderer: &mut Renderer) {
+    let summary = renderer.frame_stats().summary();
+
+    egui::Grid::new("frame_stats").num_columns(2).show(ui, |ui| {
+        ui.label("Average");
+        ui.label(format!("{:.1} fps, {:.2}ms", summary.average_fps, summary.average_frame_time / 1000.0));
+        ui.end_row();
+
+        ui.label("1% low");
+        ui.label(format!("{:.1} fps", summary.one_percent_low_fps));
+        ui.end_row();
+
+        ui.label("p99");
+        ui.label(format!("{:.2}ms", summary.p99_frame_time / 1000.0));
+        ui.end_row();
+
+        ui.label("Hitches");
+        ui.label(summary.hitch_count.to_string());
+        ui.end_row();
+
+        for (phase, time) in FramePhase::ALL.iter().zip(summary.phase_times) {
+            ui.label(phase.name());
+            ui.label(format!("{:.2}ms", time / 1000.0));
+            ui.end_row();
+        }
+
+        ui.label("GPU");
+        ui.label(if summary.average_gpu_frame_time > 0.0 {
+            format!("{:.2}ms", summary.average_gpu_frame_time / 1000.0)
+        } else {
+            String::from("unknown")
+        });
+        ui.end_row();
+
+        ui.label("Render scale");
+        ui.label(format!("{:.2}", renderer.render_scale()));
+        ui.end_row();
+    });
+
+    // Starts the statistics over, for measuring a change without the frames from before it.
+    if ui.button("Reset").clicked() {
+        renderer.frame_stats_mut().clear();
+    }
+}
+
+/// Returns whether the camera was edited. Angles are shown in degrees and only converted back when
+/// edited, since the round trip through radians isn't exact.
+fn camera_panel(ui: &mut egui::Ui, camera: &mut Camera) -> bool {
+    let mut is_edited = false;
+
+    ui.horizontal(|ui| {
+        ui.label("Position");
+        is_edited |= ui.add(DragValue::new(&mut camera.position.x).speed(0.1).prefix("x ")).changed();
+        is_edited |= ui.add(DragValue::new(&mut camera.position.y).speed(0.1).prefix("y ")).changed();
+        is_edited |= ui.add(DragValue::new(&mut camera.position.z).speed(0.1).prefix("z ")).changed();
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Rotation");
+
+        for (axis, prefix) in ["pitch ", "yaw ", "roll "].into_iter().enumerate() {
+            let mut degrees = camera.rotation[axis].to_degrees();
+            let mut drag_value = DragValue::new(&mut degrees).speed(0.5).prefix(prefix);
+
+            if axis == 0 {
+                drag_value = drag_value.clamp_range(-89.0..=89.0);
+            }
+
+            if ui.add(drag_value).changed() {
+                camera.rotation[axis] = degrees.to_radians();
+                is_edited = true;
+            }
+        }
+    });
+
+    let mut fov = camera.fov.to_degrees();
+
+    if ui.add(Slider::new(&mut fov, 30.0..=120.0).text("Field of view")).changed() {
+        camera.fov = fov.to_radians();
+        is_edited = true;
+    }
+
+    is_edited
+}
+
+fn renderer_panel(ui: &mut egui::Ui, renderer: &mut Renderer) {
+    let mut settings = *renderer.settings();
+
+    settings_controls(ui, &mut settings);
+
+    if settings != *renderer.settings() {
+        renderer.apply_settings(settings);
+    }
+}
+
+fn settings_controls(ui: &mut egui::Ui, settings: &mut RenderSettings) {
+    ComboBox::from_label("Vsync")
+        .selected_text(format!("{:?}", settings.vsync))
+        .show_ui(ui, |ui| {
+            for mode in [VsyncMode::Off, VsyncMode::Mailbox, VsyncMode::On, VsyncMode::Adaptive] {
+                ui.selectable_value(&mut settings.vsync, mode, format!("{:?}", mode));
+            }
+        });
+
+    ui.add(Slider::new(&mut settings.frames_in_flight, 1..=MAX_FRAMES_IN_FLIGHT).text("Frames in flight"));
+
+    ui.horizontal(|ui| {
+        let mut is_limited = settings.frame_rate_limit.is_some();
+
+        if ui.checkbox(&mut is_limited, "Frame rate limit").changed() {
+            settings.frame_rate_limit = is_limited.then_some(DEFAULT_FRAME_RATE_LIMIT);
+        }
+
+        if let Some(limit) = &mut settings.frame_rate_limit {
+            ui.add(Slider::new(limit, 10..=500).suffix(" fps"));
+        }
+    });
+
+    let was_dynamic = matches!(settings.render_scale, RenderScale::Dynamic { .. });
+    let mut is_dynamic = was_dynamic;
+
+    ui.horizontal(|ui| {
+        ui.label("Render scale");
+        ui.radio_value(&mut is_dynamic, false, "Fixed");
+        ui.radio_value(&mut is_dynamic, true, "Dynamic");
+    });
+
+    // Switching keeps the largest scale, so the render targets aren't reallocated.
+    if is_dynamic != was_dynamic {
+        let scale = settings.render_scale.max_scale();
+
+        settings.render_scale = match is_dynamic {
+            true => RenderScale::Dynamic {
+                target_frame_time: DEFAULT_TARGET_FRAME_TIME,
+                min: MIN_RENDER_SCALE,
+                max: scale,
+            },
+            false => RenderScale::Fixed(scale),
+        };
+    }
+
+    match &mut settings.render_scale {
+        RenderScale::Fixed(scale) => {
+            ui.add(Slider::new(scale, MIN_RENDER_SCALE..=MAX_RENDER_SCALE).step_by(0.05).text("Scale"));
+        }
+        RenderScale::Dynamic { target_frame_time, min, max } => {
+            let mut milliseconds = *target_frame_time as f32 / 1000.0;
+
+            if ui.add(Slider::new(&mut milliseconds, 4.0..=50.0).text("Target frame time (ms)")).changed() {
+                *target_frame_time = (milliseconds * 1000.0) as u128;
+            }
+
+            ui.add(Slider::new(min, MIN_RENDER_SCALE..=MAX_RENDER_SCALE).step_by(0.05).text("Min scale"));
+            ui.add(Slider::new(max, MIN_RENDER_SCALE..=MAX_RENDER_SCALE).step_by(0.05).text("Max scale"));
+        }
+    }
+
+    ComboBox::from_label("Upscale filter")
+        .selected_text(format!("{:?}", settings.upscale_filter))
+        .show_ui(ui, |ui| {
+            for filter in [UpscaleFilter::Nearest, UpscaleFilter::Bilinear] {
+                ui.selectable_value(&mut settings.upscale_filter, filter, format!("{:?}", filter));
+            }
+        });
+
+    ComboBox::from_label("Tonemapper")
+        .selected_text(format!("{:?}", settings.tonemapper))
+        .show_ui(ui, |ui| {
+            for tonemapper in [Tonemapper::Reinhard, Tonemapper::Aces] {
+                ui.selectable_value(&mut settings.tonemapper, tonemapper, format!("{:?}", tonemapper));
+            }
+        });
+
+    ui.add(Slider::new(&mut settings.exposure, 0.1..=4.0).logarithmic(true).text("Exposure"));
+    ui.checkbox(&mut settings.hdr_output, "HDR output");
+    ui.add(Slider::new(&mut settings.hdr_paper_white, 80.0..=400.0).text("Paper white"));
+    ui.checkbox(&mut settings.ambient_occlusion, "Ambient occlusion");
+    ui.add(Slider::new(&mut settings.temporal_blend, 0.02..=1.0).text("Temporal blend"));
+    ui.checkbox(&mut settings.gbuffer, "G-buffer");
+
+    ComboBox::from_label("Debug view")
+        .selected_text(format!("{:?}", settings.debug_view))
+        .show_ui(ui, |ui| {
+            for view in DebugView::ALL {
+                ui.selectable_value(&mut settings.debug_view, view, format!("{:?}", view));
+            }
+        });
+
+    ui.separator();
+
+    let post = &mut settings.post;
+
+    ui.checkbox(&mut post.fog, "Fog");
+    ui.add_enabled(post.fog, Slider::new(&mut post.fog_density, 0.0..=0.1).text("Fog density"));
+    ui.checkbox(&mut post.bloom, "Bloom");
+    ui.add_enabled(post.bloom, Slider::new(&mut post.bloom_intensity, 0.0..=2.0).text("Bloom intensity"));
+    ui.checkbox(&mut post.color_grading, "Color grading");
+    ui.add_enabled(post.color_grading, Slider::new(&mut post.color_grading_strength, 0.0..=1.0).text("Grading strength"));
+    ui.checkbox(&mut post.fxaa, "FXAA");
+}
+
+/// The sun and the voxel instances, which can be moved or removed.
+fn world_panel(ui: &mut egui::Ui, renderer: &mut Renderer) {
+    let environment = renderer.environment();
+    let sun_direction = environment.sun_direction;
+
+    egui::Grid::new("environment").num_columns(2).show(ui, |ui| {
+        ui.label("Time of day");
+        ui.label(format!("{:.3}", environment.time_of_day));
+        ui.end_row();
+
+        ui.label("Sun direction");
+        ui.label(format!("{:.2} {:.2} {:.2}", sun_direction[0], sun_direction[1], sun_direction[2]));
+        ui.end_row();
+    });
+
+    let instances = renderer.voxel_instances().collect::<Vec<_>>();
+    let mut removed = None;
+
+    for (id, mut position) in instances {
+        ui.horizontal(|ui| {
+            ui.label(format!("Instance {}", id));
+
+            let mut is_moved = false;
+            is_moved |= ui.add(DragValue::new(&mut position.x).speed(0.1).prefix("x ")).changed();
+            is_moved |= ui.add(DragValue::new(&mut position.y).speed(0.1).prefix("y ")).changed();
+            is_moved |= ui.add(DragValue::new(&mut position.z).speed(0.1).prefix("z ")).changed();
+
+            if is_moved {
+                renderer.set_voxel_instance_position(id, position);
+            }
+
+            if ui.button("Remove").clicked() {
+                removed = Some(id);
+            }
+        });
+    }
+
+    if let Some(id) = removed {
+        renderer.remove_voxel_instance(id);
+    }
+}
+
+fn memory_panel(ui: &mut egui::Ui, renderer: &Renderer) {
+    let usage = renderer.memory_usage();
+
+    egui::Grid::new("memory_usage").num_columns(2).show(ui, |ui| {
+        ui.label("Allocations");
+        ui.label(usage.allocation_count.to_string());
+        ui.end_row();
+
+        ui.label("Device local");
+        ui.label(format!("{:.1} MiB", usage.device_local_bytes as f64 / MEBIBYTE));
+        ui.end_row();
+
+        ui.label("Host visible");
+        ui.label(format!("{:.1} MiB", usage.host_visible_bytes as f64 / MEBIBYTE));
+        ui.end_row();
+    });
+}
+
+/// The window's mode and size, and every monitor with its video modes, for picking window settings.
+fn display_panel(ui: &mut egui::Ui, window: &Window) {
+    egui::Grid::new("display").num_columns(2).show(ui, |ui| {
+        ui.label("Mode");
+        ui.label(match window.fullscreen() {
+            None => String::from("Windowed"),
+            Some(Fullscreen::Borderless(_)) => String::from("Borderless"),
+            Some(Fullscreen::Exclusive(video_mode)) => format!("Exclusive, {}", display::format_video_mode(&video_mode)),
+        });
+        ui.end_row();
+
+        let size = window.inner_size();
+        ui.label("Size");
+        ui.label(format!("{}x{}", size.width, size.height));
+        ui.end_row();
+
+        ui.label("Scale factor");
+        ui.label(format!("{:.2}", window.scale_factor()));
+        ui.end_row();
+
+        ui.label("Monitor");
+        ui.label(window.current_monitor().map(|monitor| display::monitor_name(&monitor)).unwrap_or_default());
+        ui.end_row();
+    });
+
+    for (i, monitor) in window.available_monitors().enumerate() {
+        let size = monitor.size();
+        let title = format!("{} ({}x{})", display::monitor_name(&monitor), size.width, size.height);
+
+        egui::CollapsingHeader::new(title).id_source(("monitor", i)).show(ui, |ui| {
+            for video_mode in monitor.video_modes() {
+                ui.label(display::format_video_mode(&video_mode));
+            }
+        });
+    }
+}
+
+fn input_panel(ui: &mut egui::Ui, input: &Input, rebinding: &mut Option<Action>) {
+    egui::Grid::new("bindings").num_columns(3).show(ui, |ui| {
+        for action in Action::ALL {
+            ui.label(format!("{:?}", action));
+
+            if *rebinding == Some(action) {
+                ui.label("Press a key...");
+            } else {
+                let chords = input.bindings().chords(action).iter().map(|chord| chord.to_string()).collect::<Vec<_>>();
+                ui.label(chords.join(", "));
+            }
+
+            let response = ui.button("Rebind");
+
+            // Give up focus, or egui keeps the key the player presses next to itself.
+            if response.clicked() {
+                *rebinding = Some(action);
+                response.surrender_focus();
+            }
+
+            ui.end_row();
+        }
+    });
+
+    if ui.button("Save").clicked() {
+        let path = settings::config_path(BINDINGS_FILE);
+
+        match input.bindings().save(&path) {
+            Ok(()) => log::info!("Saved bindings to {}.", path.display()),
+            Err(error) => log::error!("Failed to save bindings, {}.", error),
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use winit::{
+    dpi::PhysicalPosition,
+    monitor::{MonitorHandle, VideoMode},
+    window::{Fullscreen, Window},
+};
+use crate::settings::{WindowMode, WindowSettings};
+
+/// What [`Window::set_fullscreen`] takes for the window settings' mode, on the monitor they name.
+pub fn fullscreen(window: &Window, settings: &WindowSettings) -> Option<Fullscreen> {
+    match settings.mode {
+        WindowMode::Windowed => None,
+        WindowMode::Borderless => Some(Fullscreen::Borderless(find_monitor(window, &settings.monitor))),
+        WindowMode::Exclusive => {
+            let monitor = find_monitor(window, &settings.monitor)?;
+
+            match find_video_mode(&monitor, settings) {
+                Some(video_mode) => Some(Fullscreen::Exclusive(video_mode)),
+                // Some platforms, like Wayland, have no video modes to switch to.
+                None => {
+                    log::warn!("No video modes on {}, using borderless fullscreen.", monitor_name(&monitor));
+                    Some(Fullscreen::Borderless(Some(monitor)))
+                }
+            }
+        }
+    }
+}
+
+/// The monitor called `name`, or the one the window is on if `name` is empty or not connected.
+pub fn find_monitor(window: &Window, name: &str) -> Option<MonitorHandle> {
+    let current = || window.current_monitor().or_else(|| window.primary_monitor());
+
+    if name.is_empty() {
+        return current();
+    }
+
+    window
+        .available_monitors()
+        .find(|monitor| monitor.name().as_deref() == Some(name))
+        .or_else(|| {
+            log::warn!("Monitor {:?} isn't connected, using the current one.", name);
+            current()
+        })
+}
+
+/// The video mode of `monitor` closest to the one in `settings`: the nearest size, then the nearest
+/// refresh rate, then the most bits per pixel.
+pub fn find_video_mode(monitor: &MonitorHandle, settings: &WindowSettings) -> Option<VideoMode> {
+    let size = monitor.size();
+    let width = if settings.fullscreen_width > 0 { settings.fullscreen_width } else { size.width };
+    let height = if settings.fullscreen_height > 0 { settings.fullscreen_height } else { size.height };
+
+    monitor.video_modes().min_by_key(|video_mode| {
+        let size = video_mode.size();
+        let size_difference = size.width.abs_diff(width) as u64 + size.height.abs_diff(height) as u64;
+
+        // Without a refresh rate, the highest is the closest.
+        let refresh_rate = video_mode.refresh_rate_millihertz();
+        let refresh_difference = match settings.refresh_rate {
+            0 => u32::MAX - refresh_rate,
+            hertz => refresh_rate.abs_diff(hertz * 1000),
+        };
+
+        (size_difference, refresh_difference, u16::MAX - video_mode.bit_depth())
+    })
+}
+
+/// Whether a window at `position` would be on one of `monitors`, so it isn't restored somewhere that's
+/// since been disconnected.
+pub fn is_on_screen(position: PhysicalPosition<i32>, mut monitors: impl Iterator<Item = MonitorHandle>) -> bool {
+    monitors.any(|monitor| {
+        let origin = monitor.position();
+        let size = monitor.size();
+
+        (origin.x..origin.x + size.width as i32).contains(&position.x)
+            && (origin.y..origin.y + size.height as i32).contains(&position.y)
+    })
+}
+
+pub fn monitor_name(monitor: &MonitorHandle) -> String {
+    monitor.name().unwrap_or_else(|| String::from("Unknown monitor"))
+}
+
+/// Logs every monitor with its video modes, for picking the names and modes to put in the settings.
+pub fn log_monitors(window: &Window) {
+    for monitor in window.available_monitors() {
+        let size = monitor.size();
+
+        log::debug!(
+            "Monitor {:?}: {}x{} at {}x, {:.3} Hz.",
+            monitor_name(&monitor),
+            size.width,
+            size.height,
+            monitor.scale_factor(),
+            monitor.refresh_rate_millihertz().unwrap_or(0) as f32 / 1000.0,
+        );
+
+        for video_mode in monitor.video_modes() {
+            log::trace!("  {}", format_video_mode(&video_mode));
+        }
+    }
+}
+
+/// Like "1920x1080, 59.940 Hz, 32 bit".
+pub fn format_video_mode(video_mode: &VideoMode) -> String {
+    let size = video_mode.size();
+
+    format!(
+        "{}x{}, {:.3} Hz, {} bit",
+        size.width,
+        size.height,
+        video_mode.refresh_rate_millihertz() as f32 / 1000.0,
+        video_mode.bit_depth(),
+    )
+}
@@ -0,0 +1,379 @@
+mod bindings;
+mod gamepad;
+
+use std::collections::{HashMap, HashSet};
+use gilrs::{EventType, GamepadId};
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use winit::{
+    event::{DeviceEvent, ElementState, MouseScrollDelta, WindowEvent},
+    keyboard::{ModifiersState, PhysicalKey},
+};
+
+pub use bindings::{AxisBinding, Bindings, Button, Chord, GamepadAxis, MouseAxis};
+pub use gamepad::Gamepads;
+
+/// Name of the player's bindings file, next to their settings.
+pub const BINDINGS_FILE: &str = "bindings.toml";
+
+/// Pixels of a touchpad scroll that count as one line of a mouse wheel.
+const PIXELS_PER_LINE: f32 = 20.0;
+
+/// Something the player does by pressing a chord. Written in snake case in bindings files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum Action {
+    Place,
+    Remove,
+    RotateBuilding,
+    OpenInventory,
+    Sprint,
+    Pause,
+    /// Turns the orbit camera with the mouse while held.
+    RotateCamera,
+    CycleCameraMode,
+    ToggleDebugUi,
+    CycleDebugView,
+    ToggleFullscreen,
+}
+
+impl Action {
+    pub const ALL: [Action; 11] = [
+        Action::Place,
+        Action::Remove,
+        Action::RotateBuilding,
+        Action::OpenInventory,
+        Action::Sprint,
+        Action::Pause,
+        Action::RotateCamera,
+        Action::CycleCameraMode,
+        Action::ToggleDebugUi,
+        Action::CycleDebugView,
+        Action::ToggleFullscreen,
+    ];
+}
+
+/// A value the player controls continuously. Button pairs drive it between -1.0 and 1.0, mouse motion
+/// by pixels and the wheel by lines, each multiplied by their binding's scale. Look and zoom follow the
+/// mouse, which moves rather than holds a position, so gamepads drive them per second.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum Axis {
+    MoveForward,
+    MoveRight,
+    MoveUp,
+    LookX,
+    LookY,
+    Zoom,
+}
+
+impl Axis {
+    pub const ALL: [Axis; 6] = [Axis::MoveForward, Axis::MoveRight, Axis::MoveUp, Axis::LookX, Axis::LookY, Axis::Zoom];
+
+    /// Whether the axis is a change over the frame rather than a position.
+    fn is_relative(self) -> bool {
+        matches!(self, Axis::LookX | Axis::LookY | Axis::Zoom)
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct ActionState {
+    is_held: bool,
+    was_held: bool,
+}
+
+/// Turns window and device events into actions and axes through [`Bindings`]. Events are gathered as they
+/// arrive and resolved once per frame by [`Input::update`], so every query in a frame agrees.
+pub struct Input {
+    bindings: Bindings,
+    held_buttons: HashSet<Button>,
+    /// Buttons that went down since the last update, even if they were released again before it.
+    pressed_buttons: HashSet<Button>,
+    modifiers: ModifiersState,
+    mouse_motion: Vec2,
+    wheel: f32,
+    /// Latest value of each gamepad axis, per connected gamepad.
+    gamepad_axes: HashMap<GamepadId, [f32; GamepadAxis::ALL.len()]>,
+    /// Multiplies look axes, from the player's sensitivity setting.
+    look_scale: Vec2,
+    /// Buttons that went down in the frame.
+    frame_presses: Vec<Button>,
+    actions: [ActionState; Action::ALL.len()],
+    axes: [f32; Axis::ALL.len()],
+}
+
+impl Input {
+    pub fn new(bindings: Bindings) -> Self {
+        Self {
+            bindings,
+            held_buttons: HashSet::new(),
+            pressed_buttons: HashSet::new(),
+            modifiers: ModifiersState::empty(),
+            mouse_motion: Vec2::ZERO,
+            wheel: 0.0,
+            gamepad_axes: HashMap::new(),
+            look_scale: Vec2::ONE,
+            frame_presses: Vec::new(),
+            actions: [ActionState::default(); Action::ALL.len()],
+            axes: [0.0; Axis::ALL.len()],
+        }
+    }
+
+    pub fn bindings(&self) -> &Bindings {
+        &self.bindings
+    }
+
+    pub fn bindings_mut(&mut self) -> &mut Bindings {
+        &mut self.bindings
+    }
+
+    /// Scales both look axes by `sensitivity`, and flips vertical look if `invert_y` is set.
+    pub fn set_look_sensitivity(&mut self, sensitivity: f32, invert_y: bool) {
+        self.look_scale = Vec2::new(sensitivity, if invert_y { -sensitivity } else { sensitivity });
+    }
+
+    /// Records a window event. While `is_captured`, the UI has the event, so presses are dropped but
+    /// releases are still recorded, or buttons held when the pointer moved over the UI would stay down.
+    pub fn on_window_event(&mut self, event: &WindowEvent, is_captured: bool) {
+        match event {
+            WindowEvent::KeyboardInput { event, .. } if !event.repeat => {
+                if let PhysicalKey::Code(key) = event.physical_key {
+                    self.on_button(Button::Key(key), event.state, is_captured);
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. } => self.on_button(Button::Mouse(*button), *state, is_captured),
+            WindowEvent::MouseWheel { delta, .. } if !is_captured => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_LINE,
+                };
+
+                self.wheel += lines;
+
+                if lines > 0.0 {
+                    self.pressed_buttons.insert(Button::WheelUp);
+                } else if lines < 0.0 {
+                    self.pressed_buttons.insert(Button::WheelDown);
+                }
+            }
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            // Releases that happen while unfocused never arrive.
+            WindowEvent::Focused(false) => {
+                self.held_buttons.clear();
+                self.modifiers = ModifiersState::empty();
+            }
+            _ => {}
+        }
+    }
+
+    /// Records raw mouse motion, which unlike cursor movement keeps going at the edge of the screen.
+    pub fn on_device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta: (x, y) } = event {
+            self.mouse_motion += Vec2::new(*x as f32, *y as f32);
+        }
+    }
+
+    /// Records a gamepad event. Buttons of every gamepad count as one, and so do their axes, whichever is
+    /// pushed furthest.
+    pub fn on_gamepad_event(&mut self, id: GamepadId, event: &EventType) {
+        let (axis, value) = match *event {
+            EventType::ButtonPressed(button, _) => return self.on_button(Button::Gamepad(button), ElementState::Pressed, false),
+            EventType::ButtonReleased(button, _) => return self.on_button(Button::Gamepad(button), ElementState::Released, false),
+            EventType::ButtonChanged(gilrs::Button::LeftTrigger2, value, _) => (GamepadAxis::LeftTrigger, value),
+            EventType::ButtonChanged(gilrs::Button::RightTrigger2, value, _) => (GamepadAxis::RightTrigger, value),
+            EventType::AxisChanged(gilrs::Axis::LeftStickX, value, _) => (GamepadAxis::LeftStickX, value),
+            EventType::AxisChanged(gilrs::Axis::LeftStickY, value, _) => (GamepadAxis::LeftStickY, value),
+            EventType::AxisChanged(gilrs::Axis::RightStickX, value, _) => (GamepadAxis::RightStickX, value),
+            EventType::AxisChanged(gilrs::Axis::RightStickY, value, _) => (GamepadAxis::RightStickY, value),
+            // A gamepad that goes away can't release what it held.
+            EventType::Disconnected => {
+                self.gamepad_axes.remove(&id);
+                self.held_buttons.retain(|button| !matches!(button, Button::Gamepad(_)));
+                return;
+            }
+            _ => return,
+        };
+
+        self.gamepad_axes.entry(id).or_default()[axis as usize] = value;
+    }
+
+    fn on_button(&mut self, button: Button, state: ElementState, is_captured: bool) {
+        match state {
+            ElementState::Pressed if !is_captured => {
+                self.held_buttons.insert(button);
+                self.pressed_buttons.insert(button);
+            }
+            ElementState::Pressed => {}
+            ElementState::Released => {
+                self.held_buttons.remove(&button);
+            }
+        }
+    }
+
+    /// Resolves the events since the last update into this frame's actions and axes. Call once per frame,
+    /// before anything queries input, with the frame time in seconds.
+    pub fn update(&mut self, delta_time: f32) {
+        let is_button_down = |button| self.held_buttons.contains(&button) || self.pressed_buttons.contains(&button);
+
+        for (state, action) in self.actions.iter_mut().zip(Action::ALL) {
+            state.was_held = state.is_held;
+            state.is_held = self.bindings.chords(action).iter().any(|chord| chord.is_down(is_button_down, self.modifiers));
+        }
+
+        for (value, axis) in self.axes.iter_mut().zip(Axis::ALL) {
+            let mut buttons = 0.0;
+            let mut analog = 0.0;
+
+            for binding in self.bindings.axis_bindings(axis) {
+                match *binding {
+                    AxisBinding::Buttons { negative, positive } => {
+                        buttons += is_button_down(positive) as u32 as f32 - is_button_down(negative) as u32 as f32;
+                    }
+                    AxisBinding::Mouse { mouse, scale } => {
+                        analog += scale * match mouse {
+                            MouseAxis::X => self.mouse_motion.x,
+                            MouseAxis::Y => self.mouse_motion.y,
+                            MouseAxis::Wheel => self.wheel,
+                        };
+                    }
+                    AxisBinding::Gamepad { gamepad, dead_zone, curve, scale } => {
+                        let value = self.gamepad_axes.values()
+                            .map(|values| shape_gamepad_axis(values[gamepad as usize], dead_zone, curve))
+                            .fold(0.0f32, |furthest, value| if value.abs() > furthest.abs() { value } else { furthest });
+
+                        analog += scale * if axis.is_relative() { value * delta_time } else { value };
+                    }
+                }
+            }
+
+            // Holding two keys for the same direction shouldn't move twice as fast.
+            *value = f32::clamp(buttons, -1.0, 1.0) + analog;
+
+            match axis {
+                Axis::LookX => *value *= self.look_scale.x,
+                Axis::LookY => *value *= self.look_scale.y,
+                _ => {}
+            }
+        }
+
+        self.frame_presses = self.pressed_buttons.drain().collect();
+        self.mouse_motion = Vec2::ZERO;
+        self.wheel = 0.0;
+    }
+
+    /// Whether `action` was triggered this frame.
+    pub fn is_pressed(&self, action: Action) -> bool {
+        let state = self.actions[action as usize];
+        state.is_held && !state.was_held
+    }
+
+    /// Whether one of `action`'s chords is down this frame.
+    pub fn is_held(&self, action: Action) -> bool {
+        self.actions[action as usize].is_held
+    }
+
+    /// Whether `action` stopped being held this frame.
+    pub fn is_released(&self, action: Action) -> bool {
+        let state = self.actions[action as usize];
+        !state.is_held && state.was_held
+    }
+
+    pub fn axis(&self, axis: Axis) -> f32 {
+        self.axes[axis as usize]
+    }
+
+    /// The chord the player started pressing this frame, with the modifiers held along with it, for
+    /// rebinding. Modifier keys on their own don't count, since they're usually the start of a chord, and
+    /// neither do buttons that couldn't be written to the bindings file.
+    pub fn pressed_chord(&self) -> Option<Chord> {
+        self.frame_presses.iter().find(|button| button.is_bindable() && !button.is_modifier()).map(|&button| {
+            let mut chord = Chord::new(button);
+            chord.modifiers = self.modifiers;
+            chord
+        })
+    }
+}
+
+/// Rescales the part of `value` past `dead_zone` back to the full range, then bends it by `curve`.
+fn shape_gamepad_axis(value: f32, dead_zone: f32, curve: f32) -> f32 {
+    let magnitude = ((value.abs() - dead_zone) / (1.0 - dead_zone).max(f32::EPSILON)).clamp(0.0, 1.0);
+
+    magnitude.powf(curve).copysign(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::keyboard::KeyCode;
+    use super::*;
+
+    fn states(input: &Input, action: Action) -> (bool, bool, bool) {
+        (input.is_pressed(action), input.is_held(action), input.is_released(action))
+    }
+
+    #[test]
+    fn actions_are_pressed_held_then_released() {
+        let mut input = Input::new(Bindings::default());
+        let rotate = Button::Key(KeyCode::KeyR);
+
+        input.on_button(rotate, ElementState::Pressed, false);
+        input.update(0.016);
+        assert_eq!(states(&input, Action::RotateBuilding), (true, true, false));
+
+        input.update(0.016);
+        assert_eq!(states(&input, Action::RotateBuilding), (false, true, false));
+
+        input.on_button(rotate, ElementState::Released, false);
+        input.update(0.016);
+        assert_eq!(states(&input, Action::RotateBuilding), (false, false, true));
+
+        input.update(0.016);
+        assert_eq!(states(&input, Action::RotateBuilding), (false, false, false));
+    }
+
+    #[test]
+    fn taps_within_a_frame_are_pressed_then_released() {
+        let mut input = Input::new(Bindings::default());
+        let rotate = Button::Key(KeyCode::KeyR);
+
+        input.on_button(rotate, ElementState::Pressed, false);
+        input.on_button(rotate, ElementState::Released, false);
+        input.update(0.016);
+        assert_eq!(states(&input, Action::RotateBuilding), (true, true, false));
+
+        input.update(0.016);
+        assert_eq!(states(&input, Action::RotateBuilding), (false, false, true));
+    }
+
+    #[test]
+    fn captured_presses_are_dropped() {
+        let mut input = Input::new(Bindings::default());
+
+        input.on_button(Button::Key(KeyCode::KeyR), ElementState::Pressed, true);
+        input.update(0.016);
+        assert_eq!(states(&input, Action::RotateBuilding), (false, false, false));
+    }
+
+    #[test]
+    fn gamepad_axes_rescale_past_the_dead_zone() {
+        assert_eq!(shape_gamepad_axis(0.0, 0.2, 1.0), 0.0);
+        assert_eq!(shape_gamepad_axis(0.2, 0.2, 1.0), 0.0);
+        assert!((shape_gamepad_axis(0.6, 0.2, 1.0) - 0.5).abs() < 1e-6);
+        assert_eq!(shape_gamepad_axis(1.0, 0.2, 1.0), 1.0);
+    }
+
+    #[test]
+    fn gamepad_axes_bend_by_the_curve() {
+        assert!((shape_gamepad_axis(0.5, 0.0, 2.0) - 0.25).abs() < 1e-6);
+        assert!((shape_gamepad_axis(0.25, 0.0, 0.5) - 0.5).abs() < 1e-6);
+        assert_eq!(shape_gamepad_axis(1.0, 0.0, 2.0), 1.0);
+    }
+
+    #[test]
+    fn gamepad_axes_keep_their_sign() {
+        assert!((shape_gamepad_axis(-0.6, 0.2, 1.0) + 0.5).abs() < 1e-6);
+        assert!((shape_gamepad_axis(-0.5, 0.0, 2.0) + 0.25).abs() < 1e-6);
+        assert_eq!(shape_gamepad_axis(-1.0, 0.2, 2.0), -1.0);
+    }
+}
@@ -0,0 +1,575 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use gilrs::Button as PadButton;
+use winit::{
+    event::MouseButton,
+    keyboard::{KeyCode, ModifiersState},
+};
+use crate::settings;
+use super::{Action, Axis};
+
+/// Anything that can be held down: a key, a mouse or gamepad button, or a notch of the wheel, which is
+/// only ever down for the frame it scrolled in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Button {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    WheelUp,
+    WheelDown,
+    /// A button on any connected gamepad.
+    Gamepad(PadButton),
+}
+
+impl Button {
+    /// Whether the button has a name to write it to a bindings file as. Gamepad buttons gilrs couldn't
+    /// identify don't, and `KeyCode` may gain keys without names in a newer winit.
+    pub fn is_bindable(self) -> bool {
+        match self {
+            Button::Key(key) => KEY_NAMES.iter().any(|(code, _)| *code == key),
+            Button::Gamepad(button) => PAD_BUTTON_NAMES.iter().any(|(code, _)| *code == button),
+            _ => true,
+        }
+    }
+
+    pub fn is_modifier(self) -> bool {
+        !self.modifier().is_empty()
+    }
+
+    /// The modifier this button sets while held, if it's a modifier key.
+    fn modifier(self) -> ModifiersState {
+        match self {
+            Button::Key(KeyCode::ShiftLeft | KeyCode::ShiftRight) => ModifiersState::SHIFT,
+            Button::Key(KeyCode::ControlLeft | KeyCode::ControlRight) => ModifiersState::CONTROL,
+            Button::Key(KeyCode::AltLeft | KeyCode::AltRight) => ModifiersState::ALT,
+            Button::Key(KeyCode::SuperLeft | KeyCode::SuperRight) => ModifiersState::SUPER,
+            _ => ModifiersState::empty(),
+        }
+    }
+}
+
+impl fmt::Display for Button {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Button::Key(key) => match KEY_NAMES.iter().find(|(code, _)| code == key) {
+                Some((_, name)) => f.write_str(name),
+                None => write!(f, "{:?}", key),
+            },
+            Button::Mouse(MouseButton::Left) => f.write_str("MouseLeft"),
+            Button::Mouse(MouseButton::Right) => f.write_str("MouseRight"),
+            Button::Mouse(MouseButton::Middle) => f.write_str("MouseMiddle"),
+            Button::Mouse(MouseButton::Back) => f.write_str("MouseBack"),
+            Button::Mouse(MouseButton::Forward) => f.write_str("MouseForward"),
+            Button::Mouse(MouseButton::Other(index)) => write!(f, "Mouse{}", index),
+            Button::WheelUp => f.write_str("WheelUp"),
+            Button::WheelDown => f.write_str("WheelDown"),
+            Button::Gamepad(button) => match PAD_BUTTON_NAMES.iter().find(|(code, _)| code == button) {
+                Some((_, name)) => f.write_str(name),
+                None => write!(f, "Pad{:?}", button),
+            },
+        }
+    }
+}
+
+impl TryFrom<&str> for Button {
+    type Error = String;
+
+    /// Keys go by their winit `KeyCode` name, like `KeyW`, `Digit1` or `ShiftLeft`.
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        let button = match name {
+            "MouseLeft" => Button::Mouse(MouseButton::Left),
+            "MouseRight" => Button::Mouse(MouseButton::Right),
+            "MouseMiddle" => Button::Mouse(MouseButton::Middle),
+            "MouseBack" => Button::Mouse(MouseButton::Back),
+            "MouseForward" => Button::Mouse(MouseButton::Forward),
+            "WheelUp" => Button::WheelUp,
+            "WheelDown" => Button::WheelDown,
+            _ => {
+                if let Some(index) = name.strip_prefix("Mouse").and_then(|index| index.parse().ok()) {
+                    Button::Mouse(MouseButton::Other(index))
+                } else if let Some((key, _)) = KEY_NAMES.iter().find(|(_, key_name)| *key_name == name) {
+                    Button::Key(*key)
+                } else if let Some((button, _)) = PAD_BUTTON_NAMES.iter().find(|(_, pad_name)| *pad_name == name) {
+                    Button::Gamepad(*button)
+                } else {
+                    return Err(format!("unknown button {:?}", name));
+                }
+            }
+        };
+
+        Ok(button)
+    }
+}
+
+/// One or more buttons held together, with the modifiers that must be held along with them. Written as
+/// the modifiers and buttons joined by `+`, like `Ctrl+KeyS` or `KeyG+MouseLeft`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Chord {
+    pub modifiers: ModifiersState,
+    pub buttons: Vec<Button>,
+}
+
+impl Chord {
+    pub fn new(button: Button) -> Self {
+        Chord {
+            modifiers: ModifiersState::empty(),
+            buttons: vec![button],
+        }
+    }
+
+    /// Whether every button is down and exactly the chord's modifiers are held. Modifier keys that are
+    /// part of the chord don't count as extra modifiers, so `ShiftLeft` on its own can be bound.
+    pub fn is_down(&self, is_button_down: impl Fn(Button) -> bool, modifiers: ModifiersState) -> bool {
+        let implied = self.buttons.iter().fold(ModifiersState::empty(), |implied, button| implied | button.modifier());
+
+        self.buttons.iter().all(|&button| is_button_down(button)) && modifiers - implied == self.modifiers - implied
+    }
+}
+
+const MODIFIER_NAMES: [(ModifiersState, &str); 4] = [
+    (ModifiersState::CONTROL, "Ctrl"),
+    (ModifiersState::SHIFT, "Shift"),
+    (ModifiersState::ALT, "Alt"),
+    (ModifiersState::SUPER, "Super"),
+];
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (modifier, name) in MODIFIER_NAMES {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{}+", name)?;
+            }
+        }
+
+        for (i, button) in self.buttons.iter().enumerate() {
+            if i > 0 {
+                f.write_str("+")?;
+            }
+
+            write!(f, "{}", button)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for Chord {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        let mut modifiers = ModifiersState::empty();
+        let mut buttons = Vec::new();
+
+        for part in source.split('+').map(str::trim) {
+            match MODIFIER_NAMES.iter().find(|(_, name)| *name == part) {
+                Some((modifier, _)) => modifiers |= *modifier,
+                None => buttons.push(Button::try_from(part)?),
+            }
+        }
+
+        if buttons.is_empty() {
+            return Err(format!("chord {:?} has no buttons", source));
+        }
+
+        Ok(Chord { modifiers, buttons })
+    }
+}
+
+impl From<Chord> for String {
+    fn from(chord: Chord) -> Self {
+        chord.to_string()
+    }
+}
+
+/// Mouse movement that can drive an axis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseAxis {
+    /// Horizontal motion, positive to the right.
+    X,
+    /// Vertical motion, positive downwards.
+    Y,
+    /// Scroll wheel, positive away from the user.
+    Wheel,
+}
+
+/// Analog input on a gamepad. Sticks run from -1.0 to 1.0, positive right and up, and triggers from 0.0
+/// to 1.0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+impl GamepadAxis {
+    pub const ALL: [GamepadAxis; 6] = [
+        GamepadAxis::LeftStickX,
+        GamepadAxis::LeftStickY,
+        GamepadAxis::RightStickX,
+        GamepadAxis::RightStickY,
+        GamepadAxis::LeftTrigger,
+        GamepadAxis::RightTrigger,
+    ];
+}
+
+/// A source for an axis value: a pair of buttons pulling it to -1.0 and 1.0, a mouse axis multiplied by
+/// `scale`, or a gamepad axis shaped by a dead zone and a response curve, then multiplied by `scale`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AxisBinding {
+    Buttons {
+        #[serde(with = "button_name")]
+        negative: Button,
+        #[serde(with = "button_name")]
+        positive: Button,
+    },
+    Mouse {
+        mouse: MouseAxis,
+        #[serde(default = "default_scale")]
+        scale: f32,
+    },
+    Gamepad {
+        gamepad: GamepadAxis,
+        /// Deflection below which the axis reads 0.0, to hide sticks that don't quite center.
+        #[serde(default = "default_dead_zone")]
+        dead_zone: f32,
+        /// Exponent applied past the dead zone. Above 1.0, small deflections are finer.
+        #[serde(default = "default_scale")]
+        curve: f32,
+        #[serde(default = "default_scale")]
+        scale: f32,
+    },
+}
+
+impl AxisBinding {
+    /// A gamepad binding with the default dead zone.
+    pub fn gamepad(gamepad: GamepadAxis, curve: f32, scale: f32) -> Self {
+        AxisBinding::Gamepad {
+            gamepad,
+            dead_zone: default_dead_zone(),
+            curve,
+            scale,
+        }
+    }
+
+    /// Clamps a gamepad binding's dead zone and curve to values that leave the axis usable. A dead zone of
+    /// 1.0 or more would silence it, and a curve of 0.0 or less read an idle stick as fully deflected.
+    fn clamp(&mut self, axis: Axis) {
+        if let AxisBinding::Gamepad { dead_zone, curve, .. } = self {
+            settings::clamp_setting(&format!("{:?} dead_zone", axis), dead_zone, 0.0..=MAX_DEAD_ZONE);
+            settings::clamp_setting(&format!("{:?} curve", axis), curve, MIN_CURVE..=MAX_CURVE);
+        }
+    }
+}
+
+const MAX_DEAD_ZONE: f32 = 0.9;
+const MIN_CURVE: f32 = 0.1;
+const MAX_CURVE: f32 = 10.0;
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+fn default_dead_zone() -> f32 {
+    0.125
+}
+
+mod button_name {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use super::Button;
+
+    pub fn serialize<S: Serializer>(button: &Button, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(button)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Button, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Button::try_from(name.as_str()).map_err(D::Error::custom)
+    }
+}
+
+/// A bindings file that couldn't be read or parsed.
+#[derive(Debug)]
+pub enum BindingsError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+}
+
+impl fmt::Display for BindingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BindingsError::Io(error) => write!(f, "{}", error),
+            BindingsError::Parse(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Which chords trigger each action and which sources drive each axis. Any number of either can be bound,
+/// and an action or axis without bindings is never triggered.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bindings {
+    pub actions: BTreeMap<Action, Vec<Chord>>,
+    pub axes: BTreeMap<Axis, Vec<AxisBinding>>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let key = |key| Chord::new(Button::Key(key));
+        let keys = |negative, positive| AxisBinding::Buttons {
+            negative: Button::Key(negative),
+            positive: Button::Key(positive),
+        };
+        let pad = |button| Chord::new(Button::Gamepad(button));
+
+        let mut alt_enter = key(KeyCode::Enter);
+        alt_enter.modifiers = ModifiersState::ALT;
+
+        let actions = BTreeMap::from([
+            (Action::Place, vec![Chord::new(Button::Mouse(MouseButton::Left)), pad(PadButton::RightTrigger)]),
+            (Action::Remove, vec![Chord::new(Button::Mouse(MouseButton::Right)), pad(PadButton::East)]),
+            (Action::RotateBuilding, vec![key(KeyCode::KeyR), pad(PadButton::West)]),
+            (Action::OpenInventory, vec![key(KeyCode::Tab), key(KeyCode::KeyI), pad(PadButton::North)]),
+            (Action::Sprint, vec![key(KeyCode::ShiftLeft), pad(PadButton::LeftThumb)]),
+            (Action::Pause, vec![key(KeyCode::Escape), pad(PadButton::Start)]),
+            (Action::RotateCamera, vec![Chord::new(Button::Mouse(MouseButton::Middle)), pad(PadButton::LeftTrigger)]),
+            (Action::CycleCameraMode, vec![key(KeyCode::KeyV), pad(PadButton::Select)]),
+            (Action::ToggleDebugUi, vec![key(KeyCode::F3)]),
+            (Action::CycleDebugView, vec![key(KeyCode::F5)]),
+            (Action::ToggleFullscreen, vec![key(KeyCode::F11), alt_enter]),
+        ]);
+
+        // Sticks drive look and zoom in pixels and lines per second, since the mouse and wheel report
+        // movement rather than position. Stick Y is up, mouse Y down.
+        let axes = BTreeMap::from([
+            (Axis::MoveForward, vec![
+                keys(KeyCode::KeyS, KeyCode::KeyW),
+                keys(KeyCode::ArrowDown, KeyCode::ArrowUp),
+                AxisBinding::gamepad(GamepadAxis::LeftStickY, 1.0, 1.0),
+            ]),
+            (Axis::MoveRight, vec![
+                keys(KeyCode::KeyA, KeyCode::KeyD),
+                keys(KeyCode::ArrowLeft, KeyCode::ArrowRight),
+                AxisBinding::gamepad(GamepadAxis::LeftStickX, 1.0, 1.0),
+            ]),
+            (Axis::MoveUp, vec![
+                keys(KeyCode::KeyC, KeyCode::Space),
+                AxisBinding::Buttons {
+                    negative: Button::Gamepad(PadButton::DPadDown),
+                    positive: Button::Gamepad(PadButton::South),
+                },
+            ]),
+            (Axis::LookX, vec![
+                AxisBinding::Mouse { mouse: MouseAxis::X, scale: 1.0 },
+                AxisBinding::gamepad(GamepadAxis::RightStickX, 2.0, 800.0),
+            ]),
+            (Axis::LookY, vec![
+                AxisBinding::Mouse { mouse: MouseAxis::Y, scale: 1.0 },
+                AxisBinding::gamepad(GamepadAxis::RightStickY, 2.0, -800.0),
+            ]),
+            (Axis::Zoom, vec![
+                AxisBinding::Mouse { mouse: MouseAxis::Wheel, scale: 1.0 },
+                AxisBinding::gamepad(GamepadAxis::RightTrigger, 1.0, 6.0),
+                AxisBinding::gamepad(GamepadAxis::LeftTrigger, 1.0, -6.0),
+            ]),
+        ]);
+
+        Bindings { actions, axes }
+    }
+}
+
+impl Bindings {
+    /// Loads a bindings file. Unlike assets, the path isn't relative to the assets directory, since
+    /// bindings are the player's to edit.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, BindingsError> {
+        let source = std::fs::read_to_string(path).map_err(BindingsError::Io)?;
+
+        Self::from_toml(&source).map_err(BindingsError::Parse)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        settings::write_config(path.as_ref(), &self.to_toml())
+    }
+
+    /// Actions and axes missing from `source` keep their default bindings, so files written by older
+    /// versions pick up new actions.
+    pub fn from_toml(source: &str) -> Result<Self, toml::de::Error> {
+        let file: Bindings = toml::from_str(source)?;
+        let mut bindings = Self::default();
+
+        bindings.actions.extend(file.actions);
+        bindings.axes.extend(file.axes);
+
+        for (axis, axis_bindings) in &mut bindings.axes {
+            for binding in axis_bindings {
+                binding.clamp(*axis);
+            }
+        }
+
+        Ok(bindings)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("Failed to serialize bindings.")
+    }
+
+    /// Adds `chord` to the chords that trigger `action`.
+    pub fn bind(&mut self, action: Action, chord: Chord) {
+        let chords = self.actions.entry(action).or_default();
+
+        if !chords.contains(&chord) {
+            chords.push(chord);
+        }
+    }
+
+    /// Removes every chord bound to `action`.
+    pub fn unbind(&mut self, action: Action) {
+        self.actions.insert(action, Vec::new());
+    }
+
+    pub fn chords(&self, action: Action) -> &[Chord] {
+        self.actions.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn axis_bindings(&self, axis: Axis) -> &[AxisBinding] {
+        self.axes.get(&axis).map_or(&[], Vec::as_slice)
+    }
+}
+
+macro_rules! key_names {
+    ($($key:ident),* $(,)?) => {
+        /// Keys that can be bound, by the name they're written as in bindings files.
+        const KEY_NAMES: &[(KeyCode, &str)] = &[$((KeyCode::$key, stringify!($key))),*];
+    };
+}
+
+// Every `KeyCode` winit 0.29 has.
+key_names!(
+    KeyA, KeyB, KeyC, KeyD, KeyE, KeyF, KeyG, KeyH, KeyI, KeyJ, KeyK, KeyL, KeyM,
+    KeyN, KeyO, KeyP, KeyQ, KeyR, KeyS, KeyT, KeyU, KeyV, KeyW, KeyX, KeyY, KeyZ,
+    Digit0, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, F13, F14, F15, F16, F17, F18,
+    F19, F20, F21, F22, F23, F24, F25, F26, F27, F28, F29, F30, F31, F32, F33, F34, F35,
+    Backquote, Minus, Equal, BracketLeft, BracketRight, Backslash, Semicolon, Quote, Comma, Period, Slash,
+    IntlBackslash, IntlRo, IntlYen,
+    Space, Enter, Escape, Tab, Backspace, Delete, Insert, Home, End, PageUp, PageDown, CapsLock,
+    ArrowUp, ArrowDown, ArrowLeft, ArrowRight,
+    ShiftLeft, ShiftRight, ControlLeft, ControlRight, AltLeft, AltRight, SuperLeft, SuperRight,
+    Meta, Hyper, Fn, FnLock, ContextMenu,
+    Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    NumpadAdd, NumpadSubtract, NumpadMultiply, NumpadDivide, NumpadDecimal, NumpadEnter, NumLock,
+    NumpadBackspace, NumpadClear, NumpadClearEntry, NumpadComma, NumpadEqual, NumpadHash, NumpadStar,
+    NumpadParenLeft, NumpadParenRight, NumpadMemoryAdd, NumpadMemoryClear, NumpadMemoryRecall,
+    NumpadMemoryStore, NumpadMemorySubtract,
+    PrintScreen, ScrollLock, Pause, Help, Convert, NonConvert, KanaMode, Hiragana, Katakana,
+    Lang1, Lang2, Lang3, Lang4, Lang5,
+    BrowserBack, BrowserFavorites, BrowserForward, BrowserHome, BrowserRefresh, BrowserSearch, BrowserStop,
+    MediaPlayPause, MediaSelect, MediaStop, MediaTrackNext, MediaTrackPrevious,
+    AudioVolumeDown, AudioVolumeMute, AudioVolumeUp, LaunchApp1, LaunchApp2, LaunchMail,
+    Eject, Power, Sleep, WakeUp, Turbo, Abort, Resume, Suspend,
+    Again, Copy, Cut, Find, Open, Paste, Props, Select, Undo,
+);
+
+/// Gamepad buttons that can be bound, by the name they're written as in bindings files. Bumpers are the
+/// shoulder buttons and triggers the ones below them. Every `gilrs::Button` but `Unknown`.
+const PAD_BUTTON_NAMES: &[(PadButton, &str)] = &[
+    (PadButton::South, "PadSouth"),
+    (PadButton::East, "PadEast"),
+    (PadButton::North, "PadNorth"),
+    (PadButton::West, "PadWest"),
+    (PadButton::C, "PadC"),
+    (PadButton::Z, "PadZ"),
+    (PadButton::LeftTrigger, "PadLeftBumper"),
+    (PadButton::LeftTrigger2, "PadLeftTrigger"),
+    (PadButton::RightTrigger, "PadRightBumper"),
+    (PadButton::RightTrigger2, "PadRightTrigger"),
+    (PadButton::Select, "PadSelect"),
+    (PadButton::Start, "PadStart"),
+    (PadButton::Mode, "PadMode"),
+    (PadButton::LeftThumb, "PadLeftStick"),
+    (PadButton::RightThumb, "PadRightStick"),
+    (PadButton::DPadUp, "PadUp"),
+    (PadButton::DPadDown, "PadDown"),
+    (PadButton::DPadLeft, "PadLeft"),
+    (PadButton::DPadRight, "PadRight"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn button_names_round_trip() {
+        let mouse = [MouseButton::Left, MouseButton::Right, MouseButton::Middle, MouseButton::Back, MouseButton::Forward];
+        let buttons = KEY_NAMES
+            .iter()
+            .map(|(key, _)| Button::Key(*key))
+            .chain(PAD_BUTTON_NAMES.iter().map(|(button, _)| Button::Gamepad(*button)))
+            .chain(mouse.into_iter().chain([MouseButton::Other(7)]).map(Button::Mouse))
+            .chain([Button::WheelUp, Button::WheelDown]);
+
+        for button in buttons {
+            assert!(button.is_bindable());
+            assert_eq!(Button::try_from(button.to_string().as_str()), Ok(button));
+        }
+        assert!(!Button::Gamepad(PadButton::Unknown).is_bindable());
+    }
+
+    #[test]
+    fn bindings_round_trip() {
+        let mut bindings = Bindings::default();
+
+        let mut screenshot = Chord::new(Button::Key(KeyCode::PrintScreen));
+        screenshot.modifiers = ModifiersState::CONTROL | ModifiersState::SHIFT;
+        bindings.bind(Action::ToggleDebugUi, screenshot);
+        bindings.bind(Action::Pause, Chord::new(Button::Gamepad(PadButton::C)));
+        bindings.bind(Action::Place, Chord {
+            modifiers: ModifiersState::empty(),
+            buttons: vec![Button::Key(KeyCode::NumpadStar), Button::Mouse(MouseButton::Other(4))],
+        });
+        bindings.unbind(Action::Sprint);
+        bindings.axes.insert(Axis::Zoom, vec![AxisBinding::Buttons {
+            negative: Button::Key(KeyCode::AudioVolumeDown),
+            positive: Button::Gamepad(PadButton::Z),
+        }]);
+
+        assert_eq!(Bindings::from_toml(&bindings.to_toml()).unwrap(), bindings);
+    }
+
+    #[test]
+    fn gamepad_axes_are_clamped_to_usable_values() {
+        let source = r#"
+            [[axes.look_x]]
+            gamepad = "right_stick_x"
+            dead_zone = 1.0
+            curve = 0.0
+
+            [[axes.look_y]]
+            gamepad = "right_stick_y"
+            dead_zone = -0.5
+            curve = nan
+        "#;
+        let bindings = Bindings::from_toml(source).unwrap();
+
+        assert_eq!(bindings.axes[&Axis::LookX], [AxisBinding::Gamepad {
+            gamepad: GamepadAxis::RightStickX,
+            dead_zone: MAX_DEAD_ZONE,
+            curve: MIN_CURVE,
+            scale: 1.0,
+        }]);
+        assert_eq!(bindings.axes[&Axis::LookY], [AxisBinding::Gamepad {
+            gamepad: GamepadAxis::RightStickY,
+            dead_zone: 0.0,
+            curve: MIN_CURVE,
+            scale: 1.0,
+        }]);
+    }
+}
@@ -0,0 +1,42 @@
+use gilrs::{EventType, GamepadId, Gilrs};
+
+/// Connected gamepads. Gamepads can be plugged in and out at any time.
+pub struct Gamepads {
+    gilrs: Option<Gilrs>,
+}
+
+impl Gamepads {
+    /// Without gamepad support on the platform, there are just never any gamepads.
+    pub fn new() -> Self {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(error) => {
+                log::warn!("Gamepads are unavailable, {}.", error);
+                None
+            }
+        };
+
+        for (_, gamepad) in gilrs.iter().flat_map(Gilrs::gamepads) {
+            log::info!("Gamepad connected: {}.", gamepad.name());
+        }
+
+        Self { gilrs }
+    }
+
+    /// Hands every event since the last poll to `handler`, in order.
+    pub fn poll(&mut self, mut handler: impl FnMut(GamepadId, &EventType)) {
+        let Some(gilrs) = &mut self.gilrs else {
+            return;
+        };
+
+        while let Some(event) = gilrs.next_event() {
+            match event.event {
+                EventType::Connected => log::info!("Gamepad connected: {}.", gilrs.gamepad(event.id).name()),
+                EventType::Disconnected => log::info!("Gamepad disconnected: {}.", gilrs.gamepad(event.id).name()),
+                _ => {}
+            }
+
+            handler(event.id, &event.event);
+        }
+    }
+}
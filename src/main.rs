@@ -1,34 +1,161 @@
+mod camera;
+mod cli;
 mod container;
+mod debug_ui;
+mod display;
+mod input;
+mod material;
 mod utility;
 mod renderer;
+mod settings;
+mod tools;
+mod voxel;
 
+use std::path::Path;
+use std::process::ExitCode;
+use clap::Parser;
 use simple_logger::SimpleLogger;
 use winit::{
-    dpi::PhysicalSize, event::{Event, WindowEvent}, event_loop::EventLoop, window::{Window, WindowBuilder}
+    dpi::{LogicalSize, PhysicalPosition},
+    event::{Event, WindowEvent},
+    event_loop::EventLoop,
+    window::{CursorGrabMode, Window, WindowBuilder},
 };
+use glam::{IVec3, UVec3, Vec2, Vec3};
+use camera::{Camera, CameraRig, ControllerContext};
+use cli::{Cli, Command};
+use debug_ui::DebugUi;
+use input::{Action, Bindings, Gamepads, Input, BINDINGS_FILE};
+use material::MaterialRegistry;
+use settings::{Settings, SettingsFile, WindowMode, WindowSettings, SETTINGS_FILE};
 use utility::Clock;
-use renderer::Renderer;
+use renderer::{ColorGradingLut, PickResult, PlacementGhost, PointLight, Renderer, RenderSettings, TextStyle};
+use voxel::{Voxel, VoxelOctree};
 
-fn main() {
-    SimpleLogger::new().init().unwrap();
+/// How often the frame pacing statistics are written to the log, in microseconds.
+const FRAME_STATS_LOG_INTERVAL: u128 = 5_000_000;
+
+/// How often the settings file is checked for changes, in microseconds.
+const SETTINGS_POLL_INTERVAL: u128 = 1_000_000;
+
+/// Material of the blocks the player places, until there's an inventory to pick from.
+const PLACED_MATERIAL: &str = "stone";
+
+/// Length of a simulation step, in microseconds.
+const FIXED_TIMESTEP: u128 = 16_667;
+/// Most simulation steps run in one frame. After a long stall the simulation slows down rather than
+/// trying to catch up all at once.
+const MAX_STEPS_PER_FRAME: u128 = 8;
+
+/// How often a headless run logs where the simulation is, in microseconds.
+const HEADLESS_STATUS_INTERVAL: u128 = 10_000_000;
+
+/// Time of day the game starts at, as a fraction of the day.
+const START_TIME_OF_DAY: f32 = 0.3;
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let logger = SimpleLogger::new();
+    let logger = match cli.log_level {
+        Some(level) => logger.with_level(level),
+        None => logger,
+    };
+    logger.init().unwrap();
+
+    if let Some(command) = &cli.command {
+        return run_tool(command, &cli);
+    }
+
+    if cli.server {
+        log::error!("Running a server isn't supported yet, there's no multiplayer to serve.");
+        return ExitCode::FAILURE;
+    }
+
+    if cli.headless {
+        return run_headless(&cli);
+    }
 
     log::info!("Initializing client...");
 
     let mut is_running = true;
     let mut delta_clock = Clock::new();
-    let mut delta_time = 0u128;
+    let mut step_accumulator = 0;
+    let mut time_of_day = START_TIME_OF_DAY;
 
     let mut dirty_swapchain = false;
+    let mut cursor_position = None;
+    let mut ghost_rotation = 0;
+    let mut frame_stats_log_clock = Clock::new();
+    let mut settings_poll_clock = Clock::new();
+
+    // Options override the file for this run, so reloads have them applied again.
+    let mut settings_file = SettingsFile::open(settings::config_path(SETTINGS_FILE));
+    let mut settings = settings_file.settings().clone();
+    cli.apply_to(&mut settings);
 
     let event_loop = EventLoop::new().unwrap();
 
-    let window = WindowBuilder::new()
-        .with_title("Industria")
-        .with_inner_size(PhysicalSize::new(800, 600))
-        .build(&event_loop)
-        .expect("Failed to create client window.");
+    let mut window_builder = WindowBuilder::new()
+        .with_title(&settings.window.title)
+        .with_inner_size(LogicalSize::new(settings.window.width, settings.window.height))
+        .with_min_inner_size(LogicalSize::new(320, 240));
+
+    // A monitor that's since been disconnected would leave the window out of reach.
+    if let Some([x, y]) = settings.window.position {
+        let position = PhysicalPosition::new(x, y);
+
+        if display::is_on_screen(position, event_loop.available_monitors()) {
+            window_builder = window_builder.with_position(position);
+        }
+    }
+
+    let window = window_builder.build(&event_loop).expect("Failed to create client window.");
+
+    display::log_monitors(&window);
+    window.set_fullscreen(display::fullscreen(&window, &settings.window));
+
+    // Where the window is while windowed, saved when the game closes.
+    let mut windowed_size = [settings.window.width, settings.window.height];
+    let mut windowed_position = settings.window.position;
+
+    let preferred_gpu = Some(settings.graphics.gpu.as_str()).filter(|gpu| !gpu.is_empty());
+    let mut renderer = Renderer::new(&window, settings.graphics.apply_to(RenderSettings::default()), preferred_gpu);
+
+    let mut materials = MaterialRegistry::load("materials.toml").expect("Failed to parse materials.");
+    let Some(mut world) = load_world(cli.world.as_deref(), &mut materials) else {
+        return ExitCode::FAILURE;
+    };
+    renderer.set_materials(&materials);
 
-    let renderer = Renderer::new(&window);
+    let placed_voxel = Voxel::new(materials.id(PLACED_MATERIAL).unwrap_or(0));
+    let mut pending_edit = None;
+
+    match ColorGradingLut::load("luts/warm.cube") {
+        Ok(lut) => renderer.set_color_grading_lut(&lut),
+        Err(error) => log::warn!("Failed to parse color grading LUT, {}.", error),
+    }
+
+    let is_test_scene = cli.world.is_none();
+    let world_instance = renderer.add_voxel_instance(&world, Vec3::ZERO);
+
+    if is_test_scene {
+        renderer.set_point_lights(&[TEST_SCENE_LAMP]);
+    }
+
+    let mut debug_ui = DebugUi::new(&window);
+    let mut input = Input::new(load_bindings());
+    input.set_look_sensitivity(settings.input.look_sensitivity, settings.input.invert_look_y);
+    let mut gamepads = Gamepads::new();
+
+    let camera = Camera {
+        position: Vec3::new(-12.0, 22.0, -12.0),
+        rotation: Vec3::new(-0.6, std::f32::consts::FRAC_PI_4, 0.0),
+        ..Default::default()
+    };
+    let mut camera_rig = CameraRig::new(settings.gameplay.camera_mode, &camera);
+    grab_cursor(&window, camera_rig.grabs_cursor());
+    renderer.set_camera(&camera_rig.camera());
 
     event_loop
         .run(move |event, elwt| {
@@ -36,14 +163,528 @@ fn main() {
                 Event::NewEvents(_) => {
 
                 }
-                Event::WindowEvent { event, .. } => match event {
-                    WindowEvent::CloseRequested => elwt.exit(),
-                    WindowEvent::Resized { .. } => dirty_swapchain = true,
-                    _ => {}
+                Event::DeviceEvent { event, .. } => input.on_device_event(&event),
+                Event::WindowEvent { event, .. } => {
+                    let is_captured = debug_ui.on_window_event(&window, &event);
+                    input.on_window_event(&event, is_captured);
+
+                    if is_captured {
+                        return;
+                    }
+
+                    match event {
+                        WindowEvent::CloseRequested => elwt.exit(),
+                        WindowEvent::Resized(size) => {
+                            dirty_swapchain = true;
+
+                            // Fullscreen sizes belong to the monitor, not the window. Nor do minimized ones.
+                            if window.fullscreen().is_none() && size.width > 0 && size.height > 0 {
+                                let size = size.to_logical::<f64>(window.scale_factor());
+                                windowed_size = [size.width.round() as u32, size.height.round() as u32];
+                            }
+                        }
+                        WindowEvent::Moved(position) if window.fullscreen().is_none() => {
+                            windowed_position = Some([position.x, position.y]);
+                        }
+                        // The window keeps its logical size, so the resize that follows brings the new
+                        // physical one.
+                        WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                            log::debug!("Scale factor changed to {}.", scale_factor);
+                            dirty_swapchain = true;
+                        }
+                        WindowEvent::CursorMoved { position, .. } => cursor_position = Some(position),
+                        WindowEvent::CursorLeft { .. } => cursor_position = None,
+                        WindowEvent::RedrawRequested => {
+                            let size = window.inner_size();
+
+                            // Nothing to present to while minimized.
+                            if size.width == 0 || size.height == 0 {
+                                return;
+                            }
+
+                            if dirty_swapchain {
+                                renderer.invalidate_swapchain();
+                                dirty_swapchain = false;
+                            }
+
+                            if settings_poll_clock.elapsed() >= SETTINGS_POLL_INTERVAL {
+                                if settings_file.poll().is_some() {
+                                    let mut reloaded = settings_file.settings().clone();
+                                    cli.apply_to(&mut reloaded);
+
+                                    apply_settings(&window, &mut renderer, &mut input, &settings, &reloaded);
+                                    settings = reloaded;
+                                }
+
+                                settings_poll_clock.reset();
+                            }
+
+                            let delta_time = delta_clock.elapsed();
+                            delta_clock.reset();
+
+                            time_of_day = advance_time_of_day(time_of_day, delta_time, settings.gameplay.day_length);
+                            renderer.environment_mut().set_time_of_day(time_of_day);
+
+                            input.update(delta_time as f32 / 1_000_000.0);
+
+                            if input.is_pressed(Action::RotateBuilding) {
+                                ghost_rotation = (ghost_rotation + 1) % 4;
+                            }
+
+                            if input.is_pressed(Action::ToggleDebugUi) {
+                                debug_ui.toggle_visible();
+                            }
 
+                            if input.is_pressed(Action::CycleDebugView) {
+                                let mut settings = *renderer.settings();
+                                settings.debug_view = settings.debug_view.next();
+
+                                log::info!("Debug view: {:?}.", settings.debug_view);
+                                renderer.apply_settings(settings);
+                            }
+
+                            if input.is_pressed(Action::ToggleFullscreen) {
+                                let mut toggled = settings.clone();
+                                toggled.window.mode = match settings.window.mode {
+                                    WindowMode::Windowed => WindowMode::Borderless,
+                                    WindowMode::Borderless | WindowMode::Exclusive => WindowMode::Windowed,
+                                };
+
+                                settings_file.modify(|settings| settings.window.mode = toggled.window.mode);
+                                apply_settings(&window, &mut renderer, &mut input, &settings, &toggled);
+                                settings = toggled;
+                            }
+
+                            if input.is_pressed(Action::CycleCameraMode) {
+                                camera_rig.set_mode(camera_rig.mode().next());
+                                grab_cursor(&window, camera_rig.grabs_cursor());
+
+                                log::info!("Camera mode: {:?}.", camera_rig.mode());
+                            }
+
+                            // Orbiting hides the cursor while it turns the camera, so it can't wander off the window
+                            // or into edge panning.
+                            if !camera_rig.grabs_cursor() {
+                                if input.is_pressed(Action::RotateCamera) {
+                                    grab_cursor(&window, true);
+                                } else if input.is_released(Action::RotateCamera) {
+                                    grab_cursor(&window, false);
+                                }
+                            }
+
+                            camera_rig.update(&input, delta_time as f32 / 1_000_000.0);
+
+                            let ground_height = |point: Vec3| ground_height(&world, point);
+                            let controller_context = ControllerContext {
+                                ground_height: &ground_height,
+                                cursor: cursor_position
+                                    .filter(|_| settings.input.edge_panning)
+                                    .map(|position| Vec2::new(position.x as f32, position.y as f32)),
+                                window_size: Vec2::new(size.width as f32, size.height as f32),
+                            };
+
+                            step_accumulator = (step_accumulator + delta_time).min(FIXED_TIMESTEP * MAX_STEPS_PER_FRAME);
+
+                            while step_accumulator >= FIXED_TIMESTEP {
+                                camera_rig.step(&input, &controller_context, FIXED_TIMESTEP as f32 / 1_000_000.0);
+                                step_accumulator -= FIXED_TIMESTEP;
+                            }
+
+                            camera_rig.set_alpha(step_accumulator as f32 / FIXED_TIMESTEP as f32);
+                            let mut camera = camera_rig.camera();
+
+                            if debug_ui.update(&window, &mut renderer, &mut camera, &mut input) {
+                                camera_rig.teleport(&camera);
+                            }
+
+                            renderer.set_camera(&camera);
+
+                            // Look around controllers pick at the middle of the screen, where the cursor would be
+                            // if it weren't hidden.
+                            let pick_position = if camera_rig.grabs_cursor() {
+                                Some(PhysicalPosition::new(size.width as f64 / 2.0, size.height as f64 / 2.0))
+                            } else {
+                                cursor_position
+                            };
+
+                            // Don't pick through the debug UI.
+                            let pick = pick_position.filter(|_| !debug_ui.wants_pointer()).and_then(|position| {
+                                renderer.pick(position.x.max(0.0) as u32, position.y.max(0.0) as u32)
+                            });
+
+                            renderer.set_selection(pick.as_ref());
+                            renderer.set_placement_ghost(pick.map(|pick| placement_ghost(&world, &pick, ghost_rotation)).as_ref());
+
+                            // Edits go through the GPU pick, which reads back what was drawn under the cursor a
+                            // frame or two later. Without a G-buffer to read, the CPU pick stands in.
+                            let edit = [Action::Place, Action::Remove].into_iter().find(|action| input.is_pressed(*action));
+
+                            if let Some((action, position)) = edit.zip(pick_position).filter(|_| !debug_ui.wants_pointer()) {
+                                if renderer.request_gpu_pick(position.x.max(0.0) as u32, position.y.max(0.0) as u32) {
+                                    pending_edit = Some(action);
+                                } else if let Some(pick) = pick {
+                                    edit_world(&mut renderer, &mut world, world_instance, &pick, action, placed_voxel);
+                                }
+                            }
+
+                            if let Some(gpu_pick) = renderer.take_gpu_pick() {
+                                if let (Some(action), Some(pick)) = (pending_edit.take(), gpu_pick.result) {
+                                    edit_world(&mut renderer, &mut world, world_instance, &pick, action, placed_voxel);
+                                }
+                            }
+
+                            if renderer.begin_frame() {
+                                draw_labels(&mut renderer, pick.as_ref(), size.height as f32, window.scale_factor() as f32, is_test_scene);
+                                draw_debug(&mut renderer, &world, is_test_scene);
+                                renderer.end_frame();
+                            }
+
+                            if frame_stats_log_clock.elapsed() >= FRAME_STATS_LOG_INTERVAL {
+                                log::info!("{}", renderer.frame_stats().summary());
+                                frame_stats_log_clock.reset();
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                // Only what changed while running is saved, so options from the command line don't stick.
+                Event::LoopExiting => {
+                    let is_resized = windowed_size != [settings.window.width, settings.window.height];
+                    let is_moved = windowed_position != settings.window.position;
+
+                    if is_resized || is_moved {
+                        settings_file.modify(|settings| {
+                            if is_resized {
+                                [settings.window.width, settings.window.height] = windowed_size;
+                            }
+
+                            if is_moved {
+                                settings.window.position = windowed_position;
+                            }
+                        });
+                    }
+                }
+                Event::AboutToWait => {
+                    // Gamepads aren't part of winit, so their events are collected once the window's are done.
+                    gamepads.poll(|id, event| input.on_gamepad_event(id, event));
+                    window.request_redraw();
                 }
                 _ => {}
             }
         })
         .unwrap();
+
+    ExitCode::SUCCESS
+}
+
+fn run_tool(command: &Command, cli: &Cli) -> ExitCode {
+    let load_world = || {
+        let mut materials = MaterialRegistry::load("materials.toml").expect("Failed to parse materials.");
+        load_world(cli.world.as_deref(), &mut materials).map(|world| (world, materials))
+    };
+
+    let result = match command {
+        Command::ConvertVox { input, output } => tools::convert_vox(input, output),
+        Command::RenderThumbnail { output, size } => {
+            let Some((world, materials)) = load_world() else {
+                return ExitCode::FAILURE;
+            };
+
+            tools::render_thumbnail(&world, &materials, output, *size)
+        }
+        Command::Bench { rays } => {
+            let Some((world, _)) = load_world() else {
+                return ExitCode::FAILURE;
+            };
+
+            tools::bench(&world, *rays, cli.seed);
+            Ok(())
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            log::error!("Failed to run the tool, {}.", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Steps the simulation at the fixed timestep without a window or renderer, until the process is stopped.
+/// Only the world and the day/night cycle exist to simulate so far.
+fn run_headless(cli: &Cli) -> ExitCode {
+    log::info!("Initializing headless...");
+
+    let mut settings = SettingsFile::open(settings::config_path(SETTINGS_FILE)).settings().clone();
+    cli.apply_to(&mut settings);
+
+    let mut materials = MaterialRegistry::load("materials.toml").expect("Failed to parse materials.");
+    let Some(world) = load_world(cli.world.as_deref(), &mut materials) else {
+        return ExitCode::FAILURE;
+    };
+
+    log::info!("Running headless with a world of size {}, stop with Ctrl+C.", world.size());
+
+    let mut delta_clock = Clock::new();
+    let mut status_clock = Clock::new();
+    let mut step_accumulator = 0;
+    let mut time_of_day = START_TIME_OF_DAY;
+
+    loop {
+        let delta_time = delta_clock.elapsed();
+        delta_clock.reset();
+
+        step_accumulator = (step_accumulator + delta_time).min(FIXED_TIMESTEP * MAX_STEPS_PER_FRAME);
+
+        while step_accumulator >= FIXED_TIMESTEP {
+            time_of_day = advance_time_of_day(time_of_day, FIXED_TIMESTEP, settings.gameplay.day_length);
+            step_accumulator -= FIXED_TIMESTEP;
+        }
+
+        if status_clock.elapsed() >= HEADLESS_STATUS_INTERVAL {
+            log::info!("Time of day {:.3}.", time_of_day);
+            status_clock.reset();
+        }
+
+        std::thread::sleep(std::time::Duration::from_micros((FIXED_TIMESTEP - step_accumulator) as u64));
+    }
+}
+
+/// `time_of_day` after `delta_time` microseconds of a day `day_length` seconds long, wrapped to [0, 1).
+fn advance_time_of_day(time_of_day: f32, delta_time: u128, day_length: f32) -> f32 {
+    (time_of_day + delta_time as f32 / (day_length * 1_000_000.0)).fract()
+}
+
+/// The MagicaVoxel model at `path`, with its colors added to `materials`, or the test scene without one.
+fn load_world(path: Option<&Path>, materials: &mut MaterialRegistry) -> Option<VoxelOctree> {
+    let Some(path) = path else {
+        return Some(create_test_scene(materials));
+    };
+
+    match voxel::load_vox(path, materials) {
+        Ok(world) => Some(world),
+        Err(error) => {
+            log::error!("Failed to load world {}, {}.", path.display(), error);
+            None
+        }
+    }
+}
+
+/// Applies what changed between `previous` and `settings` to the window, renderer and input.
+fn apply_settings(window: &Window, renderer: &mut Renderer, input: &mut Input, previous: &Settings, settings: &Settings) {
+    if settings.window.title != previous.window.title {
+        window.set_title(&settings.window.title);
+    }
+
+    let fullscreen = |window: &WindowSettings| {
+        (window.mode, window.monitor.clone(), window.fullscreen_width, window.fullscreen_height, window.refresh_rate)
+    };
+
+    if fullscreen(&settings.window) != fullscreen(&previous.window) {
+        window.set_fullscreen(display::fullscreen(window, &settings.window));
+
+        // Not every platform reports a resize when only the video mode changes.
+        renderer.invalidate_swapchain();
+    }
+
+    if (settings.window.width, settings.window.height) != (previous.window.width, previous.window.height) {
+        // The swapchain follows once the window reports its new size.
+        let _ = window.request_inner_size(LogicalSize::new(settings.window.width, settings.window.height));
+    }
+
+    if let Some([x, y]) = settings.window.position.filter(|_| settings.window.position != previous.window.position) {
+        window.set_outer_position(PhysicalPosition::new(x, y));
+    }
+
+    if settings.graphics != previous.graphics {
+        renderer.apply_settings(settings.graphics.apply_to(*renderer.settings()));
+
+        if settings.graphics.max_instances != previous.graphics.max_instances
+            || settings.graphics.validation != previous.graphics.validation
+            || settings.graphics.gpu != previous.graphics.gpu
+        {
+            log::info!("Instance limit, validation and GPU changes take effect after a restart.");
+        }
+    }
+
+    if settings.input != previous.input {
+        input.set_look_sensitivity(settings.input.look_sensitivity, settings.input.invert_look_y);
+    }
+}
+
+/// The player's bindings, or the defaults if there are none yet, which are then saved for the player to
+/// edit.
+fn load_bindings() -> Bindings {
+    let path = settings::config_path(BINDINGS_FILE);
+
+    if !path.exists() {
+        let bindings = Bindings::default();
+
+        if let Err(error) = bindings.save(&path) {
+            log::warn!("Failed to save default bindings, {}.", error);
+        }
+
+        return bindings;
+    }
+
+    Bindings::load(&path).unwrap_or_else(|error| {
+        log::warn!("Failed to load bindings, using the defaults, {}.", error);
+        Bindings::default()
+    })
+}
+
+/// Locks the cursor in place and hides it for mouse look, or gives it back.
+fn grab_cursor(window: &Window, is_grabbed: bool) {
+    let result = if is_grabbed {
+        // Not every platform can lock the cursor in place. Confining it to the window works almost as well.
+        window.set_cursor_grab(CursorGrabMode::Locked)
+            .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined))
+    } else {
+        window.set_cursor_grab(CursorGrabMode::None)
+    };
+
+    if let Err(error) = result {
+        log::warn!("Failed to grab the cursor, {}.", error);
+    }
+
+    window.set_cursor_visible(!is_grabbed);
+}
+
+/// Top of the highest voxel of `octree` at or below `point`, in its column.
+fn ground_height(octree: &VoxelOctree, point: Vec3) -> Option<f32> {
+    let cell = point.floor().as_ivec3();
+    let size = octree.size() as i32;
+
+    if cell.x < 0 || cell.z < 0 || cell.x >= size || cell.z >= size || cell.y < 0 {
+        return None;
+    }
+
+    (0..=cell.y.min(size - 1))
+        .rev()
+        .find(|&y| octree.get(UVec3::new(cell.x as u32, y as u32, cell.z as u32)).is_some())
+        .map(|y| y as f32 + 1.0)
+}
+
+/// Removes the picked block, or places one against the picked face, and uploads the changed world.
+fn edit_world(renderer: &mut Renderer, world: &mut VoxelOctree, instance: u32, pick: &PickResult, action: Action, voxel: Voxel) {
+    if pick.instance != instance {
+        return;
+    }
+
+    match action {
+        Action::Remove if world.get(pick.voxel).is_some() => world.set(pick.voxel, None),
+        Action::Place => {
+            let ghost = placement_ghost(world, pick, 0);
+
+            if !ghost.is_valid {
+                return;
+            }
+
+            world.set(ghost.position.as_uvec3(), Some(voxel));
+        }
+        _ => return,
+    }
+
+    renderer.update_voxel_instance(instance, world);
+}
+
+/// A single block ghost on the face of the picked voxel, which is only valid on an empty cell within the
+/// world.
+fn placement_ghost(octree: &VoxelOctree, pick: &PickResult, rotation: u32) -> PlacementGhost {
+    let target = pick.voxel.as_ivec3() + pick.normal;
+
+    let is_valid = target.cmpge(IVec3::ZERO).all()
+        && target.cmplt(IVec3::splat(octree.size() as i32)).all()
+        && octree.get(target.as_uvec3()).is_none();
+
+    PlacementGhost {
+        position: target.as_vec3(),
+        size: UVec3::ONE,
+        rotation,
+        is_valid,
+    }
+}
+
+/// World labels over the test scene and the hovered voxel in the corner, scaled by the window's scale factor
+/// like the debug UI.
+fn draw_labels(renderer: &mut Renderer, pick: Option<&PickResult>, window_height: f32, scale_factor: f32, is_test_scene: bool) {
+    if is_test_scene {
+        let [x, y, z] = TEST_SCENE_LAMP.position;
+        renderer.draw_world_text("Lamp", Vec3::new(x, y + 1.0, z), &TextStyle { size: 16.0 * scale_factor, ..Default::default() });
+    }
+
+    if let Some(pick) = pick {
+        let text = format!("Voxel {} {} {}", pick.voxel.x, pick.voxel.y, pick.voxel.z);
+        let position = Vec2::new(8.0, window_height - 28.0 * scale_factor);
+        renderer.draw_text(&text, position, &TextStyle { size: 20.0 * scale_factor, ..Default::default() });
+    }
+}
+
+/// Bounds of the world and the reach of the test scene's lamp, in debug builds.
+fn draw_debug(renderer: &mut Renderer, octree: &VoxelOctree, is_test_scene: bool) {
+    let debug_draw = renderer.debug_draw();
+
+    debug_draw.aabb(Vec3::ZERO, Vec3::splat(octree.size() as f32), [80, 200, 255, 255]);
+
+    if is_test_scene {
+        debug_draw.sphere(Vec3::from(TEST_SCENE_LAMP.position), TEST_SCENE_LAMP.radius, [255, 200, 80, 96]);
+    }
+
+    debug_draw.arrow(Vec3::ZERO, Vec3::X * 4.0, [255, 64, 64, 255]);
+    debug_draw.arrow(Vec3::ZERO, Vec3::Y * 4.0, [64, 255, 64, 255]);
+    debug_draw.arrow(Vec3::ZERO, Vec3::Z * 4.0, [64, 64, 255, 255]);
+    debug_draw.text(Vec3::Y * 4.5, "Origin", [255, 255, 255, 255]);
+}
+
+/// Lights the test scene's lamp voxel, from its center.
+const TEST_SCENE_LAMP: PointLight = PointLight {
+    position: [16.5, 1.5, 4.5],
+    radius: 16.0,
+    color: [1.0, 0.7, 0.4],
+    intensity: 40.0,
+};
+
+/// A floor with a few pillars, a pool and a lamp, for when no world is given.
+fn create_test_scene(materials: &MaterialRegistry) -> VoxelOctree {
+    let mut octree = VoxelOctree::new(5);
+    let size = octree.size();
+
+    let voxel = |name: &str| {
+        Voxel::new(materials.id(name).unwrap_or_else(|| panic!("Test scene material {} is missing.", name)))
+    };
+
+    let stone = voxel("stone");
+    let water = voxel("water");
+    let pillars = [voxel("copper"), voxel("glass"), voxel("painted_steel")];
+
+    for x in 0..size {
+        for z in 0..size {
+            octree.set(UVec3::new(x, 0, z), Some(stone));
+        }
+    }
+
+    for (i, pillar) in pillars.iter().enumerate() {
+        let base = UVec3::new(6 + 8 * i as u32, 1, 10 + 5 * i as u32);
+
+        for y in 0..(4 + 4 * i as u32) {
+            for x in 0..3 {
+                for z in 0..3 {
+                    octree.set(base + UVec3::new(x, y, z), Some(*pillar));
+                }
+            }
+        }
+    }
+
+    // A pool walled in with stone.
+    for x in 20..30 {
+        for z in 1..9 {
+            let is_wall = x == 20 || x == 29 || z == 1 || z == 8;
+            octree.set(UVec3::new(x, 1, z), Some(if is_wall { stone } else { water }));
+        }
+    }
+
+    octree.set(UVec3::new(16, 1, 4), Some(voxel("lamp")));
+
+    octree
 }
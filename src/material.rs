@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::utility;
+
+/// Most materials a registry holds. The renderer's material buffer is sized for this many.
+pub const MAX_MATERIALS: usize = 4096;
+
+/// Surface properties shared by every voxel that refers to the material.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Material {
+    pub name: String,
+    pub albedo: [f32; 3],
+    /// 0.0 is a perfect mirror, 1.0 fully diffuse.
+    pub roughness: f32,
+    pub metallic: f32,
+    pub emission: [f32; 3],
+    pub emission_strength: f32,
+    /// Opacity. Anything below 1.0 lets light through, refracted by `ior`.
+    pub alpha: f32,
+    /// Index of refraction.
+    pub ior: f32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            name: String::from("default"),
+            albedo: [0.8; 3],
+            roughness: 1.0,
+            metallic: 0.0,
+            emission: [0.0; 3],
+            emission_strength: 0.0,
+            alpha: 1.0,
+            ior: 1.5,
+        }
+    }
+}
+
+/// Layout of a material file: a list of `[[material]]` tables.
+#[derive(Serialize)]
+struct MaterialFile<'a> {
+    material: &'a [Material],
+}
+
+/// A `[[material]]` table. Unlike the other properties the name has no default, since a material without
+/// one would replace the default material.
+#[derive(Deserialize)]
+struct MaterialEntry {
+    name: String,
+    #[serde(flatten)]
+    material: Material,
+}
+
+#[derive(Deserialize)]
+struct MaterialFileEntries {
+    #[serde(default)]
+    material: Vec<MaterialEntry>,
+}
+
+/// Materials by id, looked up by name. Id 0 is always the default material.
+pub struct MaterialRegistry {
+    materials: Vec<Material>,
+    ids: HashMap<String, u32>,
+}
+
+impl MaterialRegistry {
+    pub fn new() -> Self {
+        let mut registry = MaterialRegistry {
+            materials: Vec::new(),
+            ids: HashMap::new(),
+        };
+
+        registry.register(Material::default());
+
+        registry
+    }
+
+    /// Loads a material file relative to the assets directory.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, toml::de::Error> {
+        let mut source = String::new();
+        utility::fs::load(path).read_to_string(&mut source).expect("Material file is not valid UTF-8.");
+
+        Self::from_toml(&source)
+    }
+
+    /// Materials are registered in file order, after the default material, and each needs a name. A material
+    /// named "default" replaces it.
+    pub fn from_toml(source: &str) -> Result<Self, toml::de::Error> {
+        let file: MaterialFileEntries = toml::from_str(source)?;
+        let mut registry = Self::new();
+
+        for entry in file.material {
+            registry.register(Material { name: entry.name, ..entry.material });
+        }
+
+        Ok(registry)
+    }
+
+    pub fn to_toml(&self) -> String {
+        let file = MaterialFile { material: &self.materials };
+        let mut value = toml::Value::try_from(&file).expect("Failed to serialize materials.");
+        utility::shorten_floats(&mut value);
+
+        toml::to_string(&value).expect("Failed to serialize materials.")
+    }
+
+    /// Adds `material` and returns its id. A material with the same name is replaced and keeps its id. A new
+    /// material is skipped with a warning once the registry holds [`MAX_MATERIALS`].
+    pub fn register(&mut self, material: Material) -> Option<u32> {
+        if let Some(&id) = self.ids.get(&material.name) {
+            self.materials[id as usize] = material;
+            return Some(id);
+        }
+
+        if self.materials.len() >= MAX_MATERIALS {
+            log::warn!("Material registry is full, skipping material {}.", material.name);
+            return None;
+        }
+
+        let id = self.materials.len() as u32;
+        self.ids.insert(material.name.clone(), id);
+        self.materials.push(material);
+
+        Some(id)
+    }
+
+    pub fn id(&self, name: &str) -> Option<u32> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Material> {
+        self.materials.get(id as usize)
+    }
+
+    /// Materials in id order.
+    pub fn materials(&self) -> &[Material] {
+        &self.materials
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn materials_round_trip() {
+        let source = r#"
+            [[material]]
+            name = "glass"
+            alpha = 0.25
+            ior = 1.45
+
+            [[material]]
+            name = "lamp"
+            emission = [1.0, 0.5, 0.25]
+            emission_strength = 4.0
+        "#;
+        let registry = MaterialRegistry::from_toml(source).unwrap();
+
+        assert_eq!(registry.materials().len(), 3);
+        assert_eq!(registry.get(0), Some(&Material::default()));
+        assert_eq!(registry.id("glass"), Some(1));
+        assert_eq!(registry.get(1).unwrap().alpha, 0.25);
+        assert_eq!(registry.get(1).unwrap().roughness, Material::default().roughness);
+        assert_eq!(registry.get(2).unwrap().emission, [1.0, 0.5, 0.25]);
+
+        let reloaded = MaterialRegistry::from_toml(&registry.to_toml()).unwrap();
+        assert_eq!(reloaded.materials(), registry.materials());
+        assert_eq!(reloaded.id("lamp"), Some(2));
+    }
+
+    #[test]
+    fn materials_need_a_name() {
+        assert!(MaterialRegistry::from_toml("[[material]]\nroughness = 0.5\n").is_err());
+
+        let registry = MaterialRegistry::from_toml("[[material]]\nname = \"default\"\nroughness = 0.5\n").unwrap();
+        assert_eq!(registry.materials().len(), 1);
+        assert_eq!(registry.get(0).unwrap().roughness, 0.5);
+    }
+
+    #[test]
+    fn full_registries_skip_new_materials() {
+        let mut registry = MaterialRegistry::new();
+
+        for index in 1..MAX_MATERIALS {
+            let material = Material { name: format!("material_{}", index), ..Default::default() };
+            assert_eq!(registry.register(material), Some(index as u32));
+        }
+
+        assert_eq!(registry.register(Material { name: String::from("extra"), ..Default::default() }), None);
+        assert_eq!(registry.id("extra"), None);
+        assert_eq!(registry.register(Material { name: String::from("material_1"), ..Default::default() }), Some(1));
+    }
+}
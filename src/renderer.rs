@@ -1,28 +1,158 @@
+mod buffer;
 mod command_buffer;
 mod debug;
+mod debug_draw;
+mod environment;
+mod frame_stats;
+mod gbuffer;
+mod gpu_timer;
+mod image;
+mod light;
+mod overlay;
+mod picking;
 mod pipeline;
+mod post;
+mod render_graph;
+mod settings;
 mod shader;
 mod swapchain;
+mod text;
+mod ui;
 mod utility;
 mod vkcontext;
 
+use std::collections::BTreeMap;
+
 use ash::{vk, Device};
+use glam::{UVec2, Vec2, Vec3};
 
 use swapchain::Swapchain;
 use vkcontext::VkContext;
 use command_buffer::CommandBuffer;
-use shader::VoxelShader;
+#[cfg(debug_assertions)]
+use debug_draw::DebugDrawRenderer;
+use shader::{record_octree_writes, OctreeWrite, ResolveShader, TonemapPushConstants, TonemapShader, VoxelShader};
+use frame_stats::FrameStats;
+use gbuffer::{GBuffer, GBufferAttachment};
+use gpu_timer::GpuTimer;
+use image::Image;
+use light::MAX_POINT_LIGHTS;
+use picking::{PendingPick, PickReadback};
+use post::PostStack;
+use render_graph::{Access, BufferUse, ImageUse, RenderGraph, TransientMemory};
+use text::TextRenderer;
+use ui::UiRenderer;
+use crate::camera::Camera;
+use crate::material::MaterialRegistry;
+use crate::utility::Clock;
+use crate::voxel::VoxelOctree;
 
 use winit::window::Window;
 
-const MAX_FRAMES_IN_FLIGHT: u32 = 2;
+pub use debug_draw::DebugDraw;
+pub use environment::Environment;
+pub use frame_stats::FramePhase;
+pub use light::PointLight;
+pub use overlay::PlacementGhost;
+pub use picking::{GpuPick, PickResult};
+pub use post::ColorGradingLut;
+pub use settings::{
+    DebugView,
+    PostSettings,
+    RenderScale,
+    RenderSettings,
+    Tonemapper,
+    UpscaleFilter,
+    VsyncMode,
+    DEFAULT_TARGET_FRAME_TIME,
+    MAX_FRAMES_IN_FLIGHT,
+    MAX_RENDER_SCALE,
+    MIN_RENDER_SCALE,
+};
+pub use text::TextStyle;
+pub use vkcontext::MemoryUsage;
+
+/// The frame limiter sleeps until this many microseconds before the deadline and spins for the rest.
+const FRAME_LIMITER_SPIN_TIME: u128 = 1_000;
+
+/// Number of frames the dynamic render scale averages over between adjustments.
+const DYNAMIC_SCALE_INTERVAL: u64 = 30;
+/// Dynamic render scale is rounded to multiples of this, so the render extent doesn't jitter.
+const DYNAMIC_SCALE_STEP: f32 = 0.05;
+
+/// Where a frame's commands wait for its swapchain image to be acquired.
+const IMAGE_AVAILABLE_WAIT_STAGE: vk::PipelineStageFlags = vk::PipelineStageFlags::COMPUTE_SHADER;
+
+/// Format the voxel pass renders into.
+const HDR_TARGET_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+/// Format the tonemap pass writes display encoded color into, before it is blitted to the swapchain.
+const DISPLAY_TARGET_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+/// Format the voxel pass writes emitted light into, for bloom.
+const EMISSION_TARGET_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+/// Format the voxel pass writes hit distance bits and surface ids into.
+const DEPTH_ID_TARGET_FORMAT: vk::Format = vk::Format::R32G32_UINT;
+/// Format of the ambient occlusion history images.
+const AO_HISTORY_FORMAT: vk::Format = vk::Format::R16_SFLOAT;
 
 pub struct Renderer {
     command_buffers: Vec<CommandBuffer>,
 
     voxel_shader: VoxelShader,
+    resolve_shader: ResolveShader,
+    tonemap_shader: TonemapShader,
+    post_stack: PostStack,
+    text_renderer: TextRenderer,
+    #[cfg(debug_assertions)]
+    debug_draw_renderer: DebugDrawRenderer,
+    ui_renderer: UiRenderer,
+
+    hdr_targets: Vec<Image>,
+    depth_id_targets: Vec<Image>,
+    emission_targets: Vec<Image>,
+    gbuffers: Vec<GBuffer>,
+    display_targets: Vec<Image>,
+    ao_history: [Image; 2],
+    color_history: [Image; 2],
+    depth_id_history: [Image; 2],
+    /// Backs the render targets that only live within a frame, see [`Renderer::bind_transient_targets`].
+    transient_memory: TransientMemory,
 
+    environment: Environment,
+    point_lights: Vec<PointLight>,
+    previous_environment: Option<Environment>,
+    reset_accumulation: bool,
+    /// Set for frames that can't use the previous frame's history, which then starts over.
+    is_history_reset: bool,
+    render_scale: f32,
+
+    /// Counts submitted frames. Selects which history image is read and which is written.
+    frame_index: u32,
     current_frame: u64,
+    current_image_index: Option<u32>,
+    /// The frame slot being recorded and its sync object. Command buffers, uniforms and render targets are
+    /// per slot, so they're free once its fence is signalled, whichever swapchain image the frame gets.
+    current_sync_object: Option<(usize, SyncObject)>,
+
+    frame_stats: FrameStats,
+    frame_clock: Option<Clock>,
+    /// Times frames on the GPU for the dynamic render scale, if the GPU supports it.
+    gpu_timer: Option<GpuTimer>,
+
+    /// CPU copies of the instance octrees, by instance id, for [`Renderer::pick`].
+    pick_instances: BTreeMap<u32, (VoxelOctree, Vec3)>,
+    pick_readback: PickReadback,
+    requested_pick: Option<UVec2>,
+    pending_pick: Option<PendingPick>,
+    finished_pick: Option<GpuPick>,
+
+    /// Lines and labels for the current frame, see [`Renderer::debug_draw`].
+    debug_draw: DebugDraw,
+
+    /// Drawn over every frame until replaced, see [`Renderer::set_ui`].
+    ui_primitives: Vec<egui::ClippedPrimitive>,
+    ui_pixels_per_point: f32,
+
+    settings: RenderSettings,
 
     sync_objects: Vec<SyncObject>,
     command_pool: vk::CommandPool,
@@ -31,103 +161,609 @@ pub struct Renderer {
 }
 
 impl Renderer {
-    pub fn new(window: &Window) -> Self {
+    /// Renders with the first suitable GPU whose name contains `preferred_gpu`, if there is one.
+    pub fn new(window: &Window, settings: RenderSettings, preferred_gpu: Option<&str>) -> Self {
         // Create context.
-        let vk_context = VkContext::new(window);
+        let vk_context = VkContext::new(window, settings.validation, preferred_gpu);
 
-        let swapchain = Swapchain::new(&vk_context, vk_context.queue_family_indices);
+        let swapchain = Swapchain::new(
+            &vk_context,
+            vk_context.queue_family_indices,
+            settings.vsync,
+            settings.hdr_output,
+        );
 
         // Command pool.
         let command_pool = {
             let create_info = vk::CommandPoolCreateInfo::builder()
+                .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
                 .queue_family_index(vk_context.queue_family_indices.graphics_index)
                 .build();
 
             unsafe { vk_context.device.create_command_pool(&create_info, None).unwrap() }
         };
 
-        // Sync objects.
-        let mut sync_objects = Vec::with_capacity(MAX_FRAMES_IN_FLIGHT as usize);
-        for _ in 0..MAX_FRAMES_IN_FLIGHT {
-            let image_available_semaphore = {
-                let create_info = vk::SemaphoreCreateInfo::builder().build();
-                unsafe { vk_context.device.create_semaphore(&create_info, None).unwrap() }
-            };
-
-            let queue_complete_semaphore = {
-                let create_info = vk::SemaphoreCreateInfo::builder().build();
-                unsafe { vk_context.device.create_semaphore(&create_info, None).unwrap() }
-            };
-
-            let in_flight_fence = {
-                let create_info = vk::FenceCreateInfo::builder()
-                    .flags(vk::FenceCreateFlags::SIGNALED)
-                    .build();
-                unsafe { vk_context.device.create_fence(&create_info, None).unwrap() }
-            };
-
-            sync_objects.push(SyncObject {
-                image_available_semaphore,
-                queue_complete_semaphore,
-                in_flight_fence,
-            });
-        }
+        let sync_objects = SyncObject::create_many(&vk_context.device, settings.frames_in_flight());
 
+        // Descriptor sets and buffers are cheap enough to have for as many frames in flight as the settings
+        // allow, so changing them doesn't rebuild the shaders. Render targets only follow the current setting.
         let voxel_shader =
-            VoxelShader::new(&vk_context, swapchain.images.len() as u32);
+            VoxelShader::new(&vk_context, MAX_FRAMES_IN_FLIGHT, settings.max_instance_count);
 
-        voxel_shader.update_color_buffer_descriptors(&vk_context, &swapchain);
-        
-        let command_buffers = (0..swapchain.images.len()).map(|_| {
+        let resolve_shader =
+            ResolveShader::new(&vk_context, MAX_FRAMES_IN_FLIGHT);
+
+        let tonemap_shader =
+            TonemapShader::new(&vk_context, MAX_FRAMES_IN_FLIGHT);
+
+        let mut post_stack = PostStack::new(&vk_context, MAX_FRAMES_IN_FLIGHT);
+
+        #[cfg(debug_assertions)]
+        let debug_draw_renderer = DebugDrawRenderer::new(
+            &vk_context,
+            MAX_FRAMES_IN_FLIGHT,
+            swapchain.swapchain_properties.format.format,
+        );
+        let text_renderer = TextRenderer::new(&vk_context, command_pool, swapchain.swapchain_properties.format.format);
+        let ui_renderer = UiRenderer::new(&vk_context, swapchain.swapchain_properties.format.format);
+
+        let hdr_targets = Self::create_render_targets(&vk_context, &swapchain, &settings, HDR_TARGET_FORMAT);
+        let depth_id_targets = Self::create_render_targets(&vk_context, &swapchain, &settings, DEPTH_ID_TARGET_FORMAT);
+        let emission_targets = Self::create_render_targets(&vk_context, &swapchain, &settings, EMISSION_TARGET_FORMAT);
+        let display_targets = Self::create_render_targets(&vk_context, &swapchain, &settings, DISPLAY_TARGET_FORMAT);
+        let gbuffers = Self::create_gbuffers(&vk_context, &settings, hdr_targets[0].extent);
+        post_stack.create_targets(&vk_context, settings.frames_in_flight() as usize, hdr_targets[0].extent);
+
+        let history_extent = hdr_targets[0].extent;
+        let ao_history = Self::create_history(&vk_context, history_extent, AO_HISTORY_FORMAT);
+        let color_history = Self::create_history(&vk_context, history_extent, HDR_TARGET_FORMAT);
+        let depth_id_history = Self::create_history(&vk_context, history_extent, DEPTH_ID_TARGET_FORMAT);
+
+        voxel_shader.update_materials(MaterialRegistry::new().materials());
+
+        let command_buffers = (0..MAX_FRAMES_IN_FLIGHT).map(|_| {
             CommandBuffer::new(&vk_context, command_pool, true)
         }).collect::<Vec<_>>();
 
-        Renderer {
+        let mut renderer = Renderer {
             command_buffers,
             voxel_shader,
+            resolve_shader,
+            tonemap_shader,
+            post_stack,
+            text_renderer,
+            #[cfg(debug_assertions)]
+            debug_draw_renderer,
+            ui_renderer,
+            render_scale: settings.render_scale.max_scale(),
+            hdr_targets,
+            depth_id_targets,
+            emission_targets,
+            gbuffers,
+            display_targets,
+            ao_history,
+            color_history,
+            depth_id_history,
+            transient_memory: TransientMemory::new(),
+            environment: Environment::default(),
+            point_lights: Vec::new(),
+            previous_environment: None,
+            reset_accumulation: true,
+            is_history_reset: true,
+            frame_index: 0,
             current_frame: 0,
+            current_image_index: None,
+            current_sync_object: None,
+            frame_stats: FrameStats::new(),
+            frame_clock: None,
+            gpu_timer: GpuTimer::new(&vk_context),
+            pick_instances: BTreeMap::new(),
+            pick_readback: PickReadback::new(&vk_context),
+            requested_pick: None,
+            pending_pick: None,
+            finished_pick: None,
+            debug_draw: DebugDraw::default(),
+            ui_primitives: Vec::new(),
+            ui_pixels_per_point: 1.0,
+            settings,
             sync_objects,
             command_pool,
             swapchain,
             vk_context,
-        }
+        };
+
+        renderer.bind_transient_targets();
+        renderer.update_target_descriptors();
+
+        renderer
     }
 }
 
 impl Renderer {
+    /// Waits for a frame slot and acquires the next swapchain image. Returns false if the frame should be
+    /// skipped, for example because the swapchain had to be recreated.
     pub fn begin_frame(&mut self) -> bool {
-        let sync_object = self.next_sync_object();
+        let phase_clock = Clock::new();
+        self.limit_frame_rate();
+        self.frame_stats.record_phase(FramePhase::Limiter, phase_clock.elapsed());
+
+        if let Some(frame_clock) = self.frame_clock.as_mut() {
+            self.frame_stats.push_frame(frame_clock.elapsed());
+            frame_clock.reset();
+        } else {
+            self.frame_clock = Some(Clock::new());
+        }
+
+        self.update_render_scale();
+
+        if self.swapchain.out_of_date {
+            self.recreate_swapchain();
+        }
+
+        let (frame_slot, sync_object) = self.next_sync_object();
 
         let wait_fences = [sync_object.in_flight_fence];
 
         // Wait for current frame to finish rendering.
+        let phase_clock = Clock::new();
+
         unsafe {
             self.vk_context.device.wait_for_fences(&wait_fences, true, std::u64::MAX).unwrap();
         }
 
+        self.frame_stats.record_phase(FramePhase::FenceWait, phase_clock.elapsed());
+
+        if let Some(gpu_frame_time) = self.gpu_timer.as_mut().and_then(|timer| timer.read(&self.vk_context, frame_slot)) {
+            self.frame_stats.push_gpu_frame_time(gpu_frame_time);
+        }
+
+        self.poll_gpu_pick();
+
         let next_image_index =
             match self.swapchain.acquire_next_image_index(&self.vk_context, sync_object.image_available_semaphore) {
                 Some(next_index) => next_index,
-                None => return true,
+                None => return false,
         };
 
         unsafe { self.vk_context.device.reset_fences(&wait_fences).unwrap() };
 
+        self.current_image_index = Some(next_image_index);
+        self.current_sync_object = Some((frame_slot, sync_object));
+
         true
     }
 
-    pub fn end_frame(&self) {
+    /// Records, submits and presents the frame started by [`Renderer::begin_frame`].
+    pub fn end_frame(&mut self) {
+        let (Some(image_index), Some((frame_slot, sync_object))) =
+            (self.current_image_index.take(), self.current_sync_object.take()) else {
+            return;
+        };
+
+        let mut phase_clock = Clock::new();
+
+        let render_extent = self.render_extent();
+        self.environment.render_extent = [render_extent.width, render_extent.height];
+        self.environment.frame_index = self.frame_index;
+        self.environment.write_gbuffer = self.settings.gbuffer as u32;
+        self.environment.debug_view = self.settings.debug_view as u32;
+        self.update_accumulation();
+
+        if self.settings.ambient_occlusion {
+            self.voxel_shader.update_environment(frame_slot as u32, &self.environment);
+        } else {
+            let environment = Environment { ao_samples: 0, ..self.environment };
+            self.voxel_shader.update_environment(frame_slot as u32, &environment);
+        }
 
+        self.voxel_shader.update_lights(frame_slot as u32, &self.point_lights);
+        #[cfg(debug_assertions)]
+        {
+            for (text, position, color) in self.debug_draw.take_labels() {
+                self.draw_world_text(&text, position, &TextStyle { color, ..Default::default() });
+            }
+
+            self.debug_draw_renderer.prepare(&self.vk_context, frame_slot, self.debug_draw.vertices());
+            self.debug_draw.clear();
+        }
+
+        self.text_renderer.prepare(&self.vk_context, frame_slot);
+        self.ui_renderer.prepare(
+            &self.vk_context,
+            frame_slot,
+            &self.ui_primitives,
+            self.ui_pixels_per_point,
+            self.swapchain.swapchain_properties.extent,
+        );
+
+        // One pick is read back at a time. Later requests wait for the pending one to finish.
+        let pick = match self.pending_pick {
+            None => self.requested_pick.take().map(|position| (position, self.window_to_render_pixel(position))),
+            Some(_) => None,
+        };
+
+        let octree_writes = self.voxel_shader.take_octree_writes();
+        self.record_command_buffer(frame_slot, image_index, pick.map(|(_, pixel)| pixel), &octree_writes);
+
+        if let Some((position, pixel)) = pick {
+            self.pending_pick = Some(PendingPick {
+                position,
+                pixel,
+                fence: sync_object.in_flight_fence,
+                environment: self.environment,
+            });
+        }
+
+        self.frame_stats.record_phase(FramePhase::Record, phase_clock.elapsed());
+        phase_clock.reset();
+
+        {
+            let wait_semaphores = [sync_object.image_available_semaphore];
+            let wait_stages = [IMAGE_AVAILABLE_WAIT_STAGE];
+            let signal_semaphores = [sync_object.queue_complete_semaphore];
+            let command_buffers = [self.command_buffers[frame_slot].handle];
+
+            let submit_info = vk::SubmitInfo::builder()
+                .wait_semaphores(&wait_semaphores)
+                .wait_dst_stage_mask(&wait_stages)
+                .command_buffers(&command_buffers)
+                .signal_semaphores(&signal_semaphores)
+                .build();
+
+            unsafe {
+                self.vk_context.device
+                    .queue_submit(self.vk_context.graphics_queue, &[submit_info], sync_object.in_flight_fence)
+                    .unwrap();
+            }
+        }
+
+        if let Some(gpu_timer) = self.gpu_timer.as_mut() {
+            gpu_timer.submitted(frame_slot);
+        }
+
+        self.frame_stats.record_phase(FramePhase::Submit, phase_clock.elapsed());
+        phase_clock.reset();
+
+        // A suboptimal swapchain is recreated at the start of the next frame.
+        if self.swapchain.present(&self.vk_context, sync_object.queue_complete_semaphore, image_index) {
+            self.swapchain.out_of_date = true;
+        }
+
+        self.frame_index = self.frame_index.wrapping_add(1);
+
+        self.frame_stats.record_phase(FramePhase::Present, phase_clock.elapsed());
+    }
+
+    /// Marks the swapchain for recreation at the start of the next frame.
+    pub fn invalidate_swapchain(&mut self) {
+        self.swapchain.out_of_date = true;
+    }
+
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    /// Applies new settings, recreating the swapchain and sync objects where needed.
+    pub fn apply_settings(&mut self, settings: RenderSettings) {
+        let previous = std::mem::replace(&mut self.settings, settings);
+
+        if previous.frames_in_flight() != settings.frames_in_flight() {
+            log::debug!("Recreating sync objects for {} frames in flight.", settings.frames_in_flight());
+
+            self.vk_context.wait_gpu_idle();
+
+            // The pending pick's fence is about to be destroyed, but its copy has landed by now.
+            if self.pending_pick.is_some() {
+                self.finish_gpu_pick();
+            }
+
+            for sync_object in self.sync_objects.iter() {
+                sync_object.destroy(&self.vk_context.device);
+            }
+
+            self.sync_objects = SyncObject::create_many(&self.vk_context.device, settings.frames_in_flight());
+            self.current_frame = 0;
+
+            // There's a set of render targets per frame in flight.
+            self.swapchain.out_of_date = true;
+        }
+
+        if previous.render_scale != settings.render_scale {
+            self.render_scale = settings.render_scale.max_scale();
+        }
+
+        // History accumulated with a debug view on, or off, doesn't carry over.
+        if previous.ambient_occlusion != settings.ambient_occlusion || previous.debug_view != settings.debug_view {
+            self.reset_accumulation = true;
+        }
+
+        // Render targets are recreated along with the swapchain.
+        if previous.vsync != settings.vsync
+            || previous.hdr_output != settings.hdr_output
+            || previous.render_scale.max_scale() != settings.render_scale.max_scale()
+        {
+            self.swapchain.out_of_date = true;
+        }
+    }
+
+    /// The current render resolution scale, relative to the swapchain extent.
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
+    /// Sun, sky and camera parameters. Changes take effect from the next frame.
+    pub fn environment_mut(&mut self) -> &mut Environment {
+        &mut self.environment
+    }
+
+    /// Replaces the material table voxels index into.
+    pub fn set_materials(&mut self, materials: &MaterialRegistry) {
+        self.vk_context.wait_gpu_idle();
+        self.voxel_shader.update_materials(materials.materials());
+        self.reset_accumulation = true;
+    }
+
+    /// Replaces the lookup table of the color grading pass.
+    pub fn set_color_grading_lut(&mut self, lut: &ColorGradingLut) {
+        self.vk_context.wait_gpu_idle();
+        self.post_stack.set_lut(lut);
+    }
+
+    /// Replaces the point lights. Takes effect from the next frame.
+    pub fn set_point_lights(&mut self, lights: &[PointLight]) {
+        if lights.len() > MAX_POINT_LIGHTS {
+            log::warn!("{} point lights exceed the limit of {}, extra lights are ignored.", lights.len(), MAX_POINT_LIGHTS);
+        }
+
+        self.point_lights.clear();
+        self.point_lights.extend_from_slice(lights);
+    }
+
+    pub fn set_camera(&mut self, camera: &Camera) {
+        self.environment.set_camera(camera);
+    }
+
+    /// Uploads `octree` as a voxel instance placed at `position`. Returns the instance id.
+    pub fn add_voxel_instance(&mut self, octree: &VoxelOctree, position: Vec3) -> u32 {
+        self.reset_accumulation = true;
+
+        let id = self.voxel_shader.allocate_instance(&self.vk_context, octree, position);
+        self.pick_instances.insert(id, (octree.clone(), position));
+
+        id
+    }
+
+    /// Replaces the octree of an instance. Only the parts that changed are uploaded, with the next frame,
+    /// unless the octree outgrew its buffers and they have to be replaced once the GPU is idle.
+    pub fn update_voxel_instance(&mut self, id: u32, octree: &VoxelOctree) {
+        let Some((pick_octree, _)) = self.pick_instances.get_mut(&id) else {
+            log::warn!("Tried to update nonexistent voxel instance {}.", id);
+            return;
+        };
+
+        if !self.voxel_shader.queue_instance_update(id, pick_octree, octree) {
+            self.vk_context.wait_gpu_idle();
+            self.voxel_shader.update_instance(&self.vk_context, id, octree);
+        }
+
+        *pick_octree = octree.clone();
+        self.reset_accumulation = true;
+    }
+
+    /// Every live voxel instance's id and position, in id order.
+    pub fn voxel_instances(&self) -> impl Iterator<Item = (u32, Vec3)> + '_ {
+        self.pick_instances.iter().map(|(id, (_, position))| (*id, *position))
+    }
+
+    pub fn set_voxel_instance_position(&mut self, id: u32, position: Vec3) {
+        self.voxel_shader.set_instance_position(id, position);
+        self.reset_accumulation = true;
+
+        if let Some((_, pick_position)) = self.pick_instances.get_mut(&id) {
+            *pick_position = position;
+        }
+    }
+
+    pub fn remove_voxel_instance(&mut self, id: u32) {
+        self.vk_context.wait_gpu_idle();
+        self.voxel_shader.free_instance(&self.vk_context, id);
+        self.reset_accumulation = true;
+
+        self.pick_instances.remove(&id);
+    }
+
+    /// What the window pixel at `x`, `y` shows with the current camera, ray cast on the CPU against copies
+    /// of the instance octrees.
+    pub fn pick(&self, x: u32, y: u32) -> Option<PickResult> {
+        let render_extent = self.render_extent();
+        let pixel = self.window_to_render_pixel(UVec2::new(x, y));
+
+        self.cast_pick_ray(&self.environment, UVec2::new(render_extent.width, render_extent.height), pixel)
+    }
+
+    /// Reads back what the window pixel at `x`, `y` shows from the G-buffer of the next frame. The result is
+    /// available from [`Renderer::take_gpu_pick`] once that frame has finished rendering. Returns false if
+    /// the G-buffer is disabled in the render settings, so there's nothing to read back.
+    pub fn request_gpu_pick(&mut self, x: u32, y: u32) -> bool {
+        if !self.settings.gbuffer {
+            return false;
+        }
+
+        self.requested_pick = Some(UVec2::new(x, y));
+
+        true
+    }
+
+    pub fn take_gpu_pick(&mut self) -> Option<GpuPick> {
+        self.finished_pick.take()
+    }
+
+    /// Outlines the picked voxel and highlights the face it was picked from. The outline is drawn where the
+    /// voxel is when this is called; it doesn't follow the instance if it moves.
+    pub fn set_selection(&mut self, selection: Option<&PickResult>) {
+        let selection = selection.and_then(|pick| {
+            let (_, position) = self.pick_instances.get(&pick.instance)?;
+            Some((*position + pick.voxel.as_vec3(), pick.normal))
+        });
+
+        self.environment.set_selection(selection);
+    }
+
+    pub fn set_placement_ghost(&mut self, ghost: Option<&PlacementGhost>) {
+        self.environment.set_placement_ghost(ghost);
+    }
+
+    /// Replaces the UI drawn over the frame with tessellated egui output. Texture changes apply right away.
+    pub fn set_ui(
+        &mut self,
+        textures_delta: &egui::TexturesDelta,
+        primitives: Vec<egui::ClippedPrimitive>,
+        pixels_per_point: f32,
+    ) {
+        self.ui_renderer.update_textures(&self.vk_context, self.command_pool, textures_delta);
+        self.ui_primitives = primitives;
+        self.ui_pixels_per_point = pixels_per_point;
+    }
+
+    /// Draws `text` over the current frame with its top left at `position`, in window pixels. Call between
+    /// [`Renderer::begin_frame`] and [`Renderer::end_frame`], every frame it should show.
+    pub fn draw_text(&mut self, text: &str, position: Vec2, style: &TextStyle) {
+        self.text_renderer.queue(text, position, Vec2::ZERO, style);
+    }
+
+    /// Like [`Renderer::draw_text`], but centered above `position` in the world, as seen by the current
+    /// camera. Nothing is drawn if the position is behind the camera.
+    pub fn draw_world_text(&mut self, text: &str, position: Vec3, style: &TextStyle) {
+        let extent = self.swapchain.swapchain_properties.extent;
+        let extent = Vec2::new(extent.width as f32, extent.height as f32);
+
+        if let Some(ndc) = self.environment.camera().project(position, extent.x / extent.y) {
+            self.text_renderer.queue(text, (ndc * 0.5 + 0.5) * extent, Vec2::new(0.5, 1.0), style);
+        }
+    }
+
+    /// Lines and labels drawn over the current frame, in the world. Add to it between
+    /// [`Renderer::begin_frame`] and [`Renderer::end_frame`], every frame they should show. Does nothing
+    /// in release builds.
+    pub fn debug_draw(&mut self) -> &mut DebugDraw {
+        &mut self.debug_draw
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.vk_context.memory_usage()
+    }
+
+    pub fn frame_stats(&self) -> &FrameStats {
+        &self.frame_stats
+    }
+
+    pub fn frame_stats_mut(&mut self) -> &mut FrameStats {
+        &mut self.frame_stats
     }
 }
 
 impl Renderer {
-    fn next_sync_object(&mut self) -> SyncObject {
-        let next = self.sync_objects[self.current_frame as usize];
+    /// The next frame slot and its sync object.
+    fn next_sync_object(&mut self) -> (usize, SyncObject) {
+        let frame_slot = self.current_frame as usize;
+        let next = self.sync_objects[frame_slot];
+
+        self.current_frame = (self.current_frame + 1) % self.sync_objects.len() as u64;
+
+        (frame_slot, next)
+    }
+
+    /// Maps a window coordinate to the render target pixel covering it.
+    fn window_to_render_pixel(&self, position: UVec2) -> UVec2 {
+        let swapchain_extent = self.swapchain.swapchain_properties.extent;
+        let render_extent = self.render_extent();
+
+        let scale = |coordinate: u32, from: u32, to: u32| {
+            ((coordinate as u64 * to as u64 / from.max(1) as u64) as u32).min(to - 1)
+        };
+
+        UVec2::new(
+            scale(position.x, swapchain_extent.width, render_extent.width),
+            scale(position.y, swapchain_extent.height, render_extent.height),
+        )
+    }
+
+    /// Casts the ray `voxel.comp` traces for `pixel` against every instance.
+    fn cast_pick_ray(&self, environment: &Environment, render_extent: UVec2, pixel: UVec2) -> Option<PickResult> {
+        let camera = environment.camera();
+        let extent = render_extent.as_vec2();
+
+        let ndc = (pixel.as_vec2() + 0.5) / extent * 2.0 - 1.0;
+        let direction = camera.ray_direction(ndc, extent.x / extent.y);
 
-        self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT as u64;
+        let instances = self.pick_instances.iter().map(|(id, (octree, position))| (*id, octree, *position));
 
-        next
+        picking::raycast_instances(instances, camera.position, direction, environment::FAR_DISTANCE)
+    }
+
+    fn poll_gpu_pick(&mut self) {
+        let Some(pending) = self.pending_pick.as_ref() else {
+            return;
+        };
+
+        if unsafe { self.vk_context.device.get_fence_status(pending.fence) } == Ok(true) {
+            self.finish_gpu_pick();
+        }
+    }
+
+    /// Reads the pending pick back. The frame that copied it must have completed.
+    fn finish_gpu_pick(&mut self) {
+        let Some(pending) = self.pending_pick.take() else {
+            return;
+        };
+
+        let result = self.pick_readback.read();
+
+        // Both picks trace the same ray through the same octrees, so they should only ever disagree if the
+        // scene changed while the frame was in flight. A single CPU ray per click is cheap enough to always check.
+        let render_extent = UVec2::from(pending.environment.render_extent);
+        let cpu_result = self.cast_pick_ray(&pending.environment, render_extent, pending.pixel);
+
+        let is_same_surface = match (result, cpu_result) {
+            (Some(gpu), Some(cpu)) => {
+                gpu.instance == cpu.instance
+                    && gpu.voxel == cpu.voxel
+                    && gpu.normal == cpu.normal
+                    && (gpu.distance - cpu.distance).abs() <= 1e-3 * cpu.distance.max(1.0)
+            }
+            (gpu, cpu) => gpu.is_none() && cpu.is_none(),
+        };
+
+        if !is_same_surface {
+            log::warn!("GPU and CPU picks disagree.\n\tGPU: {:?}\n\tCPU: {:?}", result, cpu_result);
+        }
+
+        self.finished_pick = Some(GpuPick {
+            position: pending.position,
+            result,
+        });
+    }
+
+    fn limit_frame_rate(&self) {
+        let (Some(frame_time_limit), Some(frame_clock)) = (self.settings.frame_time_limit(), self.frame_clock.as_ref()) else {
+            return;
+        };
+
+        let elapsed = frame_clock.elapsed();
+
+        if elapsed + FRAME_LIMITER_SPIN_TIME < frame_time_limit {
+            let sleep_time = frame_time_limit - elapsed - FRAME_LIMITER_SPIN_TIME;
+            std::thread::sleep(std::time::Duration::from_micros(sleep_time as u64));
+        }
+
+        // Sleep is too coarse to hit the deadline on most platforms.
+        while frame_clock.elapsed() < frame_time_limit {
+            std::hint::spin_loop();
+        }
     }
 
     fn recreate_swapchain(&mut self) {
@@ -137,9 +773,509 @@ impl Renderer {
 
         self.swapchain.destroy(&self.vk_context);
 
-        let swapchain = Swapchain::new(&self.vk_context, self.vk_context.queue_family_indices);
+        let swapchain = Swapchain::new(
+            &self.vk_context,
+            self.vk_context.queue_family_indices,
+            self.settings.vsync,
+            self.settings.hdr_output,
+        );
 
         self.swapchain = swapchain;
+        #[cfg(debug_assertions)]
+        self.debug_draw_renderer.set_color_format(&self.vk_context, self.swapchain.swapchain_properties.format.format);
+        self.text_renderer.set_color_format(&self.vk_context, self.swapchain.swapchain_properties.format.format);
+        self.ui_renderer.set_color_format(&self.vk_context, self.swapchain.swapchain_properties.format.format);
+
+        for target in self.render_targets() {
+            target.destroy(&self.vk_context);
+        }
+
+        self.transient_memory.destroy(&self.vk_context);
+
+        self.hdr_targets =
+            Self::create_render_targets(&self.vk_context, &self.swapchain, &self.settings, HDR_TARGET_FORMAT);
+        self.depth_id_targets =
+            Self::create_render_targets(&self.vk_context, &self.swapchain, &self.settings, DEPTH_ID_TARGET_FORMAT);
+        self.emission_targets =
+            Self::create_render_targets(&self.vk_context, &self.swapchain, &self.settings, EMISSION_TARGET_FORMAT);
+        self.display_targets =
+            Self::create_render_targets(&self.vk_context, &self.swapchain, &self.settings, DISPLAY_TARGET_FORMAT);
+        self.gbuffers = Self::create_gbuffers(&self.vk_context, &self.settings, self.hdr_targets[0].extent);
+        self.post_stack.create_targets(&self.vk_context, self.hdr_targets.len(), self.hdr_targets[0].extent);
+
+        let history_extent = self.hdr_targets[0].extent;
+        self.ao_history = Self::create_history(&self.vk_context, history_extent, AO_HISTORY_FORMAT);
+        self.color_history = Self::create_history(&self.vk_context, history_extent, HDR_TARGET_FORMAT);
+        self.depth_id_history = Self::create_history(&self.vk_context, history_extent, DEPTH_ID_TARGET_FORMAT);
+        self.reset_accumulation = true;
+
+        self.bind_transient_targets();
+        self.update_target_descriptors();
+    }
+
+    /// Binds the render targets that only live within a frame to memory they share wherever their lifetimes
+    /// allow. Lifetimes come from the frame graph with every optional pass enabled, which any actual frame
+    /// only shortens.
+    fn bind_transient_targets(&mut self) {
+        let settings = RenderSettings {
+            gbuffer: true,
+            post: PostSettings {
+                fog: true,
+                bloom: true,
+                color_grading: true,
+                fxaa: true,
+                ..self.settings.post
+            },
+            ..self.settings
+        };
+
+        let mut transient_memory = TransientMemory::new();
+
+        // The swapchain image is imported, so any one of them gives the same lifetimes.
+        for frame_slot in 0..self.hdr_targets.len() {
+            let graph = self.build_frame_graph(frame_slot, 0, &settings, Some(UVec2::ZERO));
+            transient_memory.add_graph(&self.vk_context, &graph);
+        }
+
+        for target in self.hdr_targets.iter_mut()
+            .chain(self.depth_id_targets.iter_mut())
+            .chain(self.emission_targets.iter_mut())
+            .chain(self.display_targets.iter_mut())
+            .chain(self.post_stack.targets_mut())
+        {
+            transient_memory.bind(&self.vk_context, target);
+        }
+
+        self.transient_memory = transient_memory;
+    }
+
+    /// Points every descriptor set at the current render targets and history.
+    fn update_target_descriptors(&mut self) {
+        self.voxel_shader.update_color_buffer_descriptors(&self.vk_context, &self.hdr_targets);
+        self.voxel_shader.update_depth_id_buffer_descriptors(&self.vk_context, &self.depth_id_targets);
+        self.voxel_shader.update_gbuffer_descriptors(&self.vk_context, &self.gbuffers);
+        self.voxel_shader.update_emission_buffer_descriptors(&self.vk_context, &self.emission_targets);
+        self.voxel_shader.update_ao_history_descriptors(&self.vk_context, &self.ao_history);
+        self.resolve_shader.update_frame_descriptors(
+            &self.vk_context,
+            &self.hdr_targets,
+            &self.depth_id_targets,
+            self.voxel_shader.environment_buffers(),
+        );
+        self.resolve_shader.update_history_descriptors(&self.vk_context, &self.color_history, &self.depth_id_history);
+        self.tonemap_shader.update_descriptors(&self.vk_context, &self.hdr_targets, &self.display_targets);
+        self.post_stack.update_descriptors(
+            &self.vk_context,
+            &self.hdr_targets,
+            &self.depth_id_targets,
+            &self.emission_targets,
+            &self.display_targets,
+            self.voxel_shader.environment_buffers(),
+        );
+        #[cfg(debug_assertions)]
+        self.debug_draw_renderer.update_depth_descriptors(&self.vk_context, &self.depth_id_targets);
+    }
+
+    /// Every image sized to the render extent, which all have to be recreated along with the swapchain.
+    fn render_targets(&self) -> impl Iterator<Item = &Image> {
+        self.hdr_targets.iter()
+            .chain(self.depth_id_targets.iter())
+            .chain(self.emission_targets.iter())
+            .chain(self.gbuffers.iter().flat_map(GBuffer::images))
+            .chain(self.display_targets.iter())
+            .chain(self.ao_history.iter())
+            .chain(self.color_history.iter())
+            .chain(self.depth_id_history.iter())
+            .chain(self.post_stack.targets())
+    }
+
+    /// One render target per frame in flight, created at the largest scale the current settings can reach.
+    /// They are transient, and have no memory until [`Renderer::bind_transient_targets`].
+    fn create_render_targets(
+        vk_context: &VkContext,
+        swapchain: &Swapchain,
+        settings: &RenderSettings,
+        format: vk::Format,
+    ) -> Vec<Image> {
+        let extent = scale_extent(swapchain.swapchain_properties.extent, settings.render_scale.max_scale());
+
+        log::debug!("Creating render targets.\n\tFormat: {:?}\n\tExtent:{:?}", format, extent);
+
+        (0..settings.frames_in_flight())
+            .map(|_| {
+                Image::new_unbound(
+                    vk_context,
+                    extent,
+                    format,
+                    vk::ImageUsageFlags::STORAGE
+                        | vk::ImageUsageFlags::SAMPLED
+                        | vk::ImageUsageFlags::TRANSFER_SRC
+                        | vk::ImageUsageFlags::TRANSFER_DST,
+                )
+            })
+            .collect()
+    }
+
+    /// One G-buffer per frame in flight, the same size as the render targets.
+    fn create_gbuffers(vk_context: &VkContext, settings: &RenderSettings, extent: vk::Extent2D) -> Vec<GBuffer> {
+        (0..settings.frames_in_flight()).map(|_| GBuffer::new(vk_context, extent)).collect()
+    }
+
+    /// A pair of history images, shared by all frames since history carries over from one frame to the next.
+    /// Each frame reads one and writes the other.
+    fn create_history(vk_context: &VkContext, extent: vk::Extent2D, format: vk::Format) -> [Image; 2] {
+        [(); 2].map(|_| Image::new(vk_context, extent, format, vk::ImageUsageFlags::STORAGE))
+    }
+
+    /// Decides which history this frame can use. Reprojected history survives camera movement, but ambient
+    /// occlusion history doesn't.
+    fn update_accumulation(&mut self) {
+        let previous = self.previous_environment.unwrap_or(self.environment);
+
+        self.is_history_reset = self.reset_accumulation
+            || self.previous_environment.is_none()
+            || previous.render_extent != self.environment.render_extent;
+
+        let is_ao_reset = self.is_history_reset || self.environment.is_view_different(&previous);
+
+        self.environment.ao_accumulated_frames = if is_ao_reset {
+            0
+        } else {
+            self.environment.ao_accumulated_frames.saturating_add(1)
+        };
+
+        self.environment.previous_camera_pos = previous.camera_pos;
+        self.environment.previous_camera_fov = previous.camera_fov;
+        self.environment.previous_camera_rot = previous.camera_rot;
+        self.environment.temporal_blend = if self.is_history_reset || self.settings.debug_view != DebugView::Off {
+            1.0
+        } else {
+            self.settings.temporal_blend
+        };
+
+        self.previous_environment = Some(self.environment);
+        self.reset_accumulation = false;
+    }
+
+    /// The region of the render target drawn into this frame.
+    fn render_extent(&self) -> vk::Extent2D {
+        let extent = scale_extent(self.swapchain.swapchain_properties.extent, self.render_scale);
+        let max_extent = self.hdr_targets[0].extent;
+
+        vk::Extent2D {
+            width: extent.width.min(max_extent.width),
+            height: extent.height.min(max_extent.height),
+        }
+    }
+
+    fn update_render_scale(&mut self) {
+        if !matches!(self.settings.render_scale, RenderScale::Dynamic { .. })
+            || !self.frame_stats.frame_count().is_multiple_of(DYNAMIC_SCALE_INTERVAL)
+        {
+            return;
+        }
+
+        // The wall clock frame time includes waiting for vsync and the frame limiter, which a lower scale
+        // wouldn't shorten. The GPU's time doesn't, and without it the time spent sleeping and presenting
+        // is left out, though vsync can still hold up the fence.
+        let frame_time = if self.gpu_timer.is_some() {
+            self.frame_stats.recent_average_gpu_frame_time(DYNAMIC_SCALE_INTERVAL as usize)
+        } else {
+            self.frame_stats.recent_average_busy_time(DYNAMIC_SCALE_INTERVAL as usize)
+        };
+        let scale = next_render_scale(self.render_scale, self.settings.render_scale, frame_time);
+
+        if scale != self.render_scale {
+            log::debug!("Render scale: {:.2} -> {:.2}.", self.render_scale, scale);
+            self.render_scale = scale;
+        }
+    }
+
+    fn record_command_buffer(
+        &self,
+        frame_slot: usize,
+        image_index: u32,
+        pick_pixel: Option<UVec2>,
+        octree_writes: &[OctreeWrite],
+    ) {
+        let command_buffer = &self.command_buffers[frame_slot];
+
+        command_buffer.begin(&self.vk_context, true, false, false);
+
+        // Debug views are shown as the voxel pass wrote them, without post-processing blurring or tinting them.
+        let mut settings = self.settings;
+
+        if settings.debug_view != DebugView::Off {
+            settings.post = PostSettings { fog: false, bloom: false, color_grading: false, fxaa: false, ..settings.post };
+        }
+
+        if let Some(gpu_timer) = self.gpu_timer.as_ref() {
+            gpu_timer.record_start(&self.vk_context, command_buffer, frame_slot, IMAGE_AVAILABLE_WAIT_STAGE);
+        }
+
+        record_octree_writes(&self.vk_context, command_buffer, octree_writes);
+
+        self.build_frame_graph(frame_slot, image_index as usize, &settings, pick_pixel)
+            .record(&self.vk_context, command_buffer);
+
+        if let Some(gpu_timer) = self.gpu_timer.as_ref() {
+            gpu_timer.record_end(&self.vk_context, command_buffer, frame_slot);
+        }
+
+        command_buffer.end(&self.vk_context);
+    }
+
+    /// Every pass of a frame, from clearing the render targets to presenting. Passes left out by `settings`
+    /// aren't added, and the graph works out the barriers between the rest.
+    fn build_frame_graph(
+        &self,
+        frame_slot: usize,
+        image_index: usize,
+        settings: &RenderSettings,
+        pick_pixel: Option<UVec2>,
+    ) -> RenderGraph<'_> {
+        let swapchain_image = self.swapchain.images[image_index];
+        let swapchain_extent = self.swapchain.swapchain_properties.extent;
+        let hdr_target = &self.hdr_targets[frame_slot];
+        let depth_id_target = &self.depth_id_targets[frame_slot];
+        let emission_target = &self.emission_targets[frame_slot];
+        let gbuffer = &self.gbuffers[frame_slot];
+        let display_target = &self.display_targets[frame_slot];
+        let render_extent = self.render_extent();
+        let history_index = (self.frame_index & 1) as usize;
+
+        let mut graph = RenderGraph::new();
+
+        // History carries over from the previous frame, unless it's discarded. The G-buffer is cleared every
+        // frame, but outlives it for picking.
+        let history = [
+            (self.environment.ao_accumulated_frames == 0, &self.ao_history),
+            (self.is_history_reset, &self.color_history),
+            (self.is_history_reset, &self.depth_id_history),
+        ];
+
+        for (is_discarded, images) in history {
+            for image in images {
+                graph.import_image(image.handle, (!is_discarded).then_some(ImageUse::ComputeReadWrite));
+                graph.export_image(image.handle, None);
+            }
+        }
+
+        for image in gbuffer.images() {
+            graph.import_image(image.handle, None);
+        }
+
+        graph.import_image(swapchain_image, None);
+        graph.export_image(swapchain_image, Some(ImageUse::Present));
+
+        // HDR target cleared to the sky. The alpha channel holds the hit distance. The depth and id, emission
+        // and G-buffer targets are cleared to the sky's values.
+        let cleared_targets = [hdr_target, depth_id_target, emission_target].into_iter()
+            .chain(gbuffer.images().filter(|_| settings.gbuffer))
+            .map(|target| Access::image(target, ImageUse::TransferWrite))
+            .collect::<Vec<_>>();
+
+        let sky_color = Vec3::from(self.environment.sky_color);
+        let is_gbuffer_enabled = settings.gbuffer;
+
+        graph.add_pass("clear", &cleared_targets, move |vkcontext, command_buffer| {
+            let clears = [
+                (hdr_target, vk::ClearColorValue { float32: [sky_color.x, sky_color.y, sky_color.z, environment::FAR_DISTANCE] }),
+                (depth_id_target, vk::ClearColorValue { uint32: [environment::FAR_DISTANCE.to_bits(), 0, 0, 0] }),
+                (emission_target, vk::ClearColorValue { float32: [0.0; 4] }),
+            ];
+
+            let gbuffer_clears = GBufferAttachment::ALL.into_iter()
+                .filter(|_| is_gbuffer_enabled)
+                .map(|attachment| (gbuffer.image(attachment), attachment.clear_value()));
+
+            for (target, clear_value) in clears.into_iter().chain(gbuffer_clears) {
+                unsafe {
+                    vkcontext.device.cmd_clear_color_image(
+                        command_buffer.handle,
+                        target.handle,
+                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                        &clear_value,
+                        &[COLOR_SUBRESOURCE_RANGE],
+                    );
+                }
+            }
+        });
+
+        // Both history images of a pair are bound, one read and one written.
+        let voxel_accesses = [
+            Access::image(hdr_target, ImageUse::ComputeReadWrite),
+            Access::image(depth_id_target, ImageUse::ComputeReadWrite),
+            Access::image(emission_target, ImageUse::ComputeReadWrite),
+        ].into_iter()
+            .chain(gbuffer.images().filter(|_| settings.gbuffer).map(|image| Access::image(image, ImageUse::ComputeReadWrite)))
+            .chain(self.ao_history.iter().map(|image| Access::image(image, ImageUse::ComputeReadWrite)))
+            .collect::<Vec<_>>();
+
+        graph.add_pass("voxel", &voxel_accesses, move |vkcontext, command_buffer| {
+            self.voxel_shader.bind(vkcontext, command_buffer, frame_slot as u32);
+            self.voxel_shader.dispatch_instances(vkcontext, command_buffer, frame_slot as u32, render_extent);
+        });
+
+        let resolve_accesses = [
+            Access::image(hdr_target, ImageUse::ComputeReadWrite),
+            Access::image(depth_id_target, ImageUse::ComputeRead),
+        ].into_iter()
+            .chain(self.color_history.iter().map(|image| Access::image(image, ImageUse::ComputeReadWrite)))
+            .chain(self.depth_id_history.iter().map(|image| Access::image(image, ImageUse::ComputeReadWrite)))
+            .collect::<Vec<_>>();
+
+        graph.add_pass("resolve", &resolve_accesses, move |vkcontext, command_buffer| {
+            self.resolve_shader.bind(vkcontext, command_buffer, frame_slot as u32, history_index);
+
+            unsafe {
+                vkcontext.device.cmd_dispatch(
+                    command_buffer.handle,
+                    render_extent.width.div_ceil(8),
+                    render_extent.height.div_ceil(8),
+                    1,
+                );
+            }
+        });
+
+        if let Some(pixel) = pick_pixel {
+            let pick_buffer = self.pick_readback.buffer();
+
+            let pick_accesses = gbuffer.images()
+                .map(|image| Access::image(image, ImageUse::TransferRead))
+                .chain([Access::Buffer(pick_buffer, BufferUse::TransferWrite)])
+                .collect::<Vec<_>>();
+
+            graph.export_buffer(pick_buffer, BufferUse::HostRead);
+            graph.add_pass("pick_readback", &pick_accesses, move |vkcontext, command_buffer| {
+                self.pick_readback.record(vkcontext, command_buffer, gbuffer, pixel);
+            });
+        }
+
+        let output_transfer = self.swapchain.swapchain_properties.output_transfer();
+
+        self.post_stack.add_hdr_passes(
+            &mut graph,
+            &settings.post,
+            frame_slot,
+            hdr_target,
+            depth_id_target,
+            emission_target,
+            render_extent,
+        );
+
+        let tonemap_push_constants = TonemapPushConstants {
+            extent: [render_extent.width, render_extent.height],
+            exposure: settings.exposure,
+            tonemapper: settings.tonemapper as u32,
+            output_transfer: output_transfer as u32,
+            paper_white: settings.hdr_paper_white,
+        };
+
+        graph.add_pass(
+            "tonemap",
+            &[Access::image(hdr_target, ImageUse::ComputeRead), Access::image(display_target, ImageUse::ComputeWrite)],
+            move |vkcontext, command_buffer| {
+                self.tonemap_shader.bind(vkcontext, command_buffer, frame_slot as u32, &tonemap_push_constants);
+
+                unsafe {
+                    vkcontext.device.cmd_dispatch(
+                        command_buffer.handle,
+                        render_extent.width.div_ceil(8),
+                        render_extent.height.div_ceil(8),
+                        1,
+                    );
+                }
+            },
+        );
+
+        let output_target = self.post_stack.add_display_passes(
+            &mut graph,
+            &settings.post,
+            frame_slot,
+            display_target,
+            render_extent,
+            output_transfer,
+        );
+
+        let blit = vk::ImageBlit {
+            src_subresource: COLOR_SUBRESOURCE_LAYERS,
+            src_offsets: [
+                vk::Offset3D::default(),
+                vk::Offset3D { x: render_extent.width as i32, y: render_extent.height as i32, z: 1 },
+            ],
+            dst_subresource: COLOR_SUBRESOURCE_LAYERS,
+            dst_offsets: [
+                vk::Offset3D::default(),
+                vk::Offset3D { x: swapchain_extent.width as i32, y: swapchain_extent.height as i32, z: 1 },
+            ],
+        };
+        let upscale_filter = settings.upscale_filter.to_vk();
+
+        graph.add_pass(
+            "blit",
+            &[Access::image(output_target, ImageUse::TransferRead), Access::Image(swapchain_image, ImageUse::TransferWrite)],
+            move |vkcontext, command_buffer| unsafe {
+                vkcontext.device.cmd_blit_image(
+                    command_buffer.handle,
+                    output_target.handle,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    swapchain_image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[blit],
+                    upscale_filter,
+                );
+            },
+        );
+
+        // Rasterized UI and debug geometry draw over the upscaled frame, at window resolution. Debug lines
+        // are hidden behind voxels by the hit distances, in debug builds.
+        let swapchain_view = self.swapchain.image_views[image_index];
+        let paper_white = settings.hdr_paper_white;
+        #[cfg(debug_assertions)]
+        let camera = self.environment.camera();
+
+        let overlay_accesses = [Access::Image(swapchain_image, ImageUse::ColorAttachment)].into_iter()
+            .chain(cfg!(debug_assertions).then(|| Access::image(depth_id_target, ImageUse::FragmentRead)))
+            .collect::<Vec<_>>();
+
+        graph.add_pass(
+            "raster_overlay",
+            &overlay_accesses,
+            move |vkcontext, command_buffer| {
+                command_buffer.begin_rendering(vkcontext, swapchain_view, swapchain_extent);
+                #[cfg(debug_assertions)]
+                self.debug_draw_renderer.record(
+                    vkcontext,
+                    command_buffer,
+                    frame_slot,
+                    &camera,
+                    swapchain_extent,
+                    render_extent,
+                    output_transfer,
+                    paper_white,
+                );
+                self.text_renderer.record(
+                    vkcontext,
+                    command_buffer,
+                    frame_slot,
+                    swapchain_extent,
+                    output_transfer,
+                    paper_white,
+                );
+                self.ui_renderer.record(
+                    vkcontext,
+                    command_buffer,
+                    frame_slot,
+                    swapchain_extent,
+                    output_transfer,
+                    paper_white,
+                );
+                command_buffer.end_rendering(vkcontext);
+            },
+        );
+
+        graph
     }
 }
 
@@ -147,10 +1283,31 @@ impl Drop for Renderer {
     fn drop(&mut self) {
         log::debug!("Dropping renderer.");
 
+        self.vk_context.wait_gpu_idle();
+
         let device = &self.vk_context.device;
 
         unsafe {
             self.voxel_shader.destroy(&self.vk_context);
+            self.tonemap_shader.destroy(&self.vk_context);
+            self.post_stack.destroy(&self.vk_context);
+            #[cfg(debug_assertions)]
+            self.debug_draw_renderer.destroy(&self.vk_context);
+            self.text_renderer.destroy(&self.vk_context);
+            self.ui_renderer.destroy(&self.vk_context);
+
+            self.resolve_shader.destroy(&self.vk_context);
+            self.pick_readback.destroy(&self.vk_context);
+
+            if let Some(gpu_timer) = self.gpu_timer.as_ref() {
+                gpu_timer.destroy(&self.vk_context);
+            }
+
+            for target in self.render_targets() {
+                target.destroy(&self.vk_context);
+            }
+
+            self.transient_memory.destroy(&self.vk_context);
 
             for sync_object in self.sync_objects.iter() {
                 sync_object.destroy(device);
@@ -164,6 +1321,46 @@ impl Drop for Renderer {
     }
 }
 
+const COLOR_SUBRESOURCE_RANGE: vk::ImageSubresourceRange = vk::ImageSubresourceRange {
+    aspect_mask: vk::ImageAspectFlags::COLOR,
+    base_mip_level: 0,
+    level_count: 1,
+    base_array_layer: 0,
+    layer_count: 1,
+};
+
+const COLOR_SUBRESOURCE_LAYERS: vk::ImageSubresourceLayers = vk::ImageSubresourceLayers {
+    aspect_mask: vk::ImageAspectFlags::COLOR,
+    mip_level: 0,
+    base_array_layer: 0,
+    layer_count: 1,
+};
+
+fn scale_extent(extent: vk::Extent2D, scale: f32) -> vk::Extent2D {
+    vk::Extent2D {
+        width: ((extent.width as f32 * scale).round() as u32).max(1),
+        height: ((extent.height as f32 * scale).round() as u32).max(1),
+    }
+}
+
+/// The render scale to use after a batch of frames took `frame_time` microseconds on average at `scale`.
+fn next_render_scale(scale: f32, render_scale: RenderScale, frame_time: f64) -> f32 {
+    let RenderScale::Dynamic { target_frame_time, .. } = render_scale else {
+        return render_scale.max_scale();
+    };
+
+    if frame_time <= 0.0 {
+        return scale;
+    }
+
+    // Ray march cost scales with the pixel count, which is the square of the scale. The adjustment is
+    // damped so a single slow batch of frames doesn't swing the resolution.
+    let ratio = ((target_frame_time as f64 / frame_time).sqrt() as f32).clamp(0.9, 1.1);
+    let scale = (scale * ratio).clamp(render_scale.min_scale(), render_scale.max_scale());
+
+    (scale / DYNAMIC_SCALE_STEP).round() * DYNAMIC_SCALE_STEP
+}
+
 #[derive(Clone, Copy)]
 struct SyncObject {
     image_available_semaphore: vk::Semaphore,
@@ -172,6 +1369,35 @@ struct SyncObject {
 }
 
 impl SyncObject {
+    fn new(device: &Device) -> Self {
+        let image_available_semaphore = {
+            let create_info = vk::SemaphoreCreateInfo::builder().build();
+            unsafe { device.create_semaphore(&create_info, None).unwrap() }
+        };
+
+        let queue_complete_semaphore = {
+            let create_info = vk::SemaphoreCreateInfo::builder().build();
+            unsafe { device.create_semaphore(&create_info, None).unwrap() }
+        };
+
+        let in_flight_fence = {
+            let create_info = vk::FenceCreateInfo::builder()
+                .flags(vk::FenceCreateFlags::SIGNALED)
+                .build();
+            unsafe { device.create_fence(&create_info, None).unwrap() }
+        };
+
+        SyncObject {
+            image_available_semaphore,
+            queue_complete_semaphore,
+            in_flight_fence,
+        }
+    }
+
+    fn create_many(device: &Device, count: u32) -> Vec<Self> {
+        (0..count).map(|_| SyncObject::new(device)).collect()
+    }
+
     fn destroy(&self, device: &Device) {
         unsafe {
             device.destroy_semaphore(self.image_available_semaphore, None);
@@ -180,3 +1406,53 @@ impl SyncObject {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DYNAMIC: RenderScale = RenderScale::Dynamic {
+        target_frame_time: 10_000,
+        min: 0.5,
+        max: 1.5,
+    };
+
+    /// Runs batches of frames that cost `full_scale_time` at scale 1.0, and more or less with the pixel
+    /// count at other scales. Returns the scale after every batch.
+    fn run_batches(mut scale: f32, full_scale_time: f64, batch_count: usize) -> Vec<f32> {
+        (0..batch_count)
+            .map(|_| {
+                scale = next_render_scale(scale, DYNAMIC, full_scale_time * (scale * scale) as f64);
+                scale
+            })
+            .collect()
+    }
+
+    #[test]
+    fn render_scale_settles_on_the_target_frame_time() {
+        // Frames take 16 ms at scale 1.0, so the 10 ms target is reached at about 0.79. From 1.5, where
+        // frames take 36 ms, the scale comes down over several batches.
+        let scales = run_batches(1.5, 16_000.0, 20);
+
+        assert!(scales[..4].windows(2).all(|pair| pair[1] < pair[0]), "{:?}", scales);
+        assert!(scales.windows(2).all(|pair| pair[1] >= pair[0] * 0.9 - DYNAMIC_SCALE_STEP), "{:?}", scales);
+        assert!(scales[10..].iter().all(|scale| (scale - 0.8).abs() < 0.06), "{:?}", scales);
+    }
+
+    #[test]
+    fn render_scale_stays_within_its_bounds() {
+        let slow = run_batches(1.0, 100_000.0, 20);
+        assert_eq!(*slow.last().unwrap(), 0.5);
+        assert!(slow.iter().all(|scale| *scale >= 0.5));
+
+        let fast = run_batches(1.0, 1_000.0, 20);
+        assert_eq!(*fast.last().unwrap(), 1.5);
+        assert!(fast.iter().all(|scale| *scale <= 1.5));
+    }
+
+    #[test]
+    fn render_scale_is_steady_without_timings_or_when_fixed() {
+        assert_eq!(next_render_scale(0.75, DYNAMIC, 0.0), 0.75);
+        assert_eq!(next_render_scale(0.75, RenderScale::Fixed(1.25), 50_000.0), 1.25);
+    }
+}
@@ -0,0 +1,94 @@
+use ash::vk;
+use std::ffi::c_void;
+use super::utility::find_memory_type;
+use super::vkcontext::VkContext;
+
+/// A host visible, persistently mapped buffer.
+pub struct Buffer {
+    mapped: *mut c_void,
+    pub memory: vk::DeviceMemory,
+    pub handle: vk::Buffer,
+
+    pub size: vk::DeviceSize,
+}
+
+impl Buffer {
+    pub fn new(vkcontext: &VkContext, size: vk::DeviceSize, usage: vk::BufferUsageFlags) -> Self {
+        // Zero sized buffers are invalid, but an empty octree or light list is not.
+        let size = size.max(16);
+
+        let handle = {
+            let create_info = vk::BufferCreateInfo::builder()
+                .size(size)
+                .usage(usage)
+                .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                .build();
+
+            unsafe { vkcontext.device.create_buffer(&create_info, None).unwrap() }
+        };
+
+        let memory = {
+            let requirements = unsafe { vkcontext.device.get_buffer_memory_requirements(handle) };
+
+            let allocate_info = vk::MemoryAllocateInfo::builder()
+                .allocation_size(requirements.size)
+                .memory_type_index(find_memory_type(
+                    vkcontext,
+                    requirements.memory_type_bits,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                ))
+                .build();
+
+            vkcontext.allocate_memory(&allocate_info)
+        };
+
+        let mapped = unsafe {
+            vkcontext.device.bind_buffer_memory(handle, memory, 0).unwrap();
+            vkcontext.device.map_memory(memory, 0, size, vk::MemoryMapFlags::empty()).unwrap()
+        };
+
+        Self {
+            mapped,
+            memory,
+            handle,
+            size,
+        }
+    }
+
+    /// Creates a buffer holding exactly `data`.
+    pub fn with_data<T: Copy>(vkcontext: &VkContext, data: &[T], usage: vk::BufferUsageFlags) -> Self {
+        let buffer = Self::new(vkcontext, std::mem::size_of_val(data) as vk::DeviceSize, usage);
+        buffer.write(0, data);
+        buffer
+    }
+
+    /// Copies `data` into the buffer, starting `offset` bytes in.
+    pub fn write<T: Copy>(&self, offset: vk::DeviceSize, data: &[T]) {
+        let byte_count = std::mem::size_of_val(data);
+
+        assert!(offset + byte_count as vk::DeviceSize <= self.size, "Buffer write out of bounds.");
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                data.as_ptr() as *const u8,
+                (self.mapped as *mut u8).add(offset as usize),
+                byte_count,
+            );
+        }
+    }
+
+    /// Reads a `T` from `offset` bytes in.
+    pub fn read<T: Copy>(&self, offset: vk::DeviceSize) -> T {
+        assert!(offset + std::mem::size_of::<T>() as vk::DeviceSize <= self.size, "Buffer read out of bounds.");
+
+        unsafe { std::ptr::read_unaligned((self.mapped as *const u8).add(offset as usize) as *const T) }
+    }
+
+    pub fn destroy(&self, vkcontext: &VkContext) {
+        unsafe {
+            vkcontext.device.unmap_memory(self.memory);
+            vkcontext.device.destroy_buffer(self.handle, None);
+            vkcontext.free_memory(self.memory);
+        }
+    }
+}
@@ -52,7 +52,52 @@ impl CommandBuffer {
         unsafe { vkcontext.device.end_command_buffer(self.handle).unwrap() }
     }
 
+    /// Begins dynamic rendering into the whole of `view`, keeping its contents, and sets the viewport and
+    /// scissor to cover it. The image must be in color attachment layout.
+    pub fn begin_rendering(&self, vkcontext: &VkContext, view: vk::ImageView, extent: vk::Extent2D) {
+        let color_attachments = [
+            vk::RenderingAttachmentInfo::builder()
+                .image_view(view)
+                .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .load_op(vk::AttachmentLoadOp::LOAD)
+                .store_op(vk::AttachmentStoreOp::STORE)
+                .build(),
+        ];
+
+        let render_area = vk::Rect2D {
+            offset: vk::Offset2D::default(),
+            extent,
+        };
+
+        let rendering_info = vk::RenderingInfo::builder()
+            .render_area(render_area)
+            .layer_count(1)
+            .color_attachments(&color_attachments)
+            .build();
+
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: extent.width as f32,
+            height: extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+
+        unsafe {
+            vkcontext.device.cmd_begin_rendering(self.handle, &rendering_info);
+            vkcontext.device.cmd_set_viewport(self.handle, 0, &[viewport]);
+            vkcontext.device.cmd_set_scissor(self.handle, 0, &[render_area]);
+        }
+    }
+
+    pub fn end_rendering(&self, vkcontext: &VkContext) {
+        unsafe { vkcontext.device.cmd_end_rendering(self.handle) }
+    }
+
     pub fn end_and_submit_single_use(&self, vkcontext: &VkContext, queue: vk::Queue) {
+        self.end(vkcontext);
+
         let buffers = [self.handle];
 
         let submit_info = vk::SubmitInfo::builder()
@@ -5,11 +5,6 @@ use std::{
     os::raw::{c_char, c_void},
 };
 
-#[cfg(debug_assertions)]
-pub const ENABLE_VALIDATION_LAYERS: bool = true;
-#[cfg(not(debug_assertions))]
-pub const ENABLE_VALIDATION_LAYERS: bool = false;
-
 const REQUIRED_LAYERS: [&str; 1] = ["VK_LAYER_KHRONOS_validation"];
 
 unsafe extern "system" fn vulkan_debug_callback(
@@ -46,7 +41,10 @@ pub fn get_layer_names_and_pointers() -> (Vec<CString>, Vec<*const c_char>) {
     (layer_names, layer_names_ptrs)
 }
 
-pub fn check_validation_layer_support(entry: &Entry) {
+/// Whether every layer validation needs is installed. Logs the ones that aren't.
+pub fn check_validation_layer_support(entry: &Entry) -> bool {
+    let mut is_supported = true;
+
     for required in REQUIRED_LAYERS.iter() {
         let found = entry
             .enumerate_instance_layer_properties()
@@ -60,16 +58,20 @@ pub fn check_validation_layer_support(entry: &Entry) {
             });
         
         if !found {
-            panic!("Validation layer not supported: {}", required);
+            log::warn!("Validation layer {} isn't installed, running without validation.", required);
+            is_supported = false;
         }
     }
+
+    is_supported
 }
 
 pub fn setup_debug_messenger(
     entry: &Entry,
-    instance: &Instance
+    instance: &Instance,
+    enable_validation: bool,
 ) -> Option<(DebugUtils, vk::DebugUtilsMessengerEXT)> {
-    if !ENABLE_VALIDATION_LAYERS {
+    if !enable_validation {
         return None;
     }
 
@@ -0,0 +1,423 @@
+use glam::Vec3;
+
+#[cfg(debug_assertions)]
+pub use renderer::DebugDrawRenderer;
+
+/// Segments in each circle of a sphere.
+const SPHERE_SEGMENTS: usize = 32;
+
+/// Vertex of a debug line. Mirrors the vertex input of `debug_draw.vert`.
+#[cfg(debug_assertions)]
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct DebugVertex {
+    position: [f32; 3],
+    color: [u8; 4],
+}
+
+/// Gathers world-space lines and labels for one frame, drawn over it and hidden behind voxels. Colors are
+/// sRGB encoded with straight alpha.
+///
+/// Everything here compiles to nothing in release builds.
+#[derive(Default)]
+pub struct DebugDraw {
+    #[cfg(debug_assertions)]
+    vertices: Vec<DebugVertex>,
+    #[cfg(debug_assertions)]
+    labels: Vec<(String, Vec3, [u8; 4])>,
+}
+
+impl DebugDraw {
+    #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+    #[inline]
+    pub fn line(&mut self, from: Vec3, to: Vec3, color: [u8; 4]) {
+        #[cfg(debug_assertions)]
+        self.vertices.extend([
+            DebugVertex { position: from.to_array(), color },
+            DebugVertex { position: to.to_array(), color },
+        ]);
+    }
+
+    /// The edges of the box between `min` and `max`.
+    #[inline]
+    pub fn aabb(&mut self, min: Vec3, max: Vec3, color: [u8; 4]) {
+        let corner = |i: usize| Vec3::select(glam::BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0), max, min);
+
+        // Each edge joins two corners whose indices differ in one bit.
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corner(i), corner(i | bit), color);
+                }
+            }
+        }
+    }
+
+    /// A circle around each axis.
+    #[inline]
+    pub fn sphere(&mut self, center: Vec3, radius: f32, color: [u8; 4]) {
+        let point = |i: usize| {
+            let (sin, cos) = (i as f32 / SPHERE_SEGMENTS as f32 * std::f32::consts::TAU).sin_cos();
+            (sin * radius, cos * radius)
+        };
+
+        for i in 0..SPHERE_SEGMENTS {
+            let ((sin_a, cos_a), (sin_b, cos_b)) = (point(i), point(i + 1));
+
+            self.line(center + Vec3::new(sin_a, cos_a, 0.0), center + Vec3::new(sin_b, cos_b, 0.0), color);
+            self.line(center + Vec3::new(sin_a, 0.0, cos_a), center + Vec3::new(sin_b, 0.0, cos_b), color);
+            self.line(center + Vec3::new(0.0, sin_a, cos_a), center + Vec3::new(0.0, sin_b, cos_b), color);
+        }
+    }
+
+    /// A line from `from` to `to` with a head at `to`, a fifth of its length.
+    #[inline]
+    pub fn arrow(&mut self, from: Vec3, to: Vec3, color: [u8; 4]) {
+        self.line(from, to, color);
+
+        let direction = to - from;
+        let length = direction.length();
+
+        if length <= f32::EPSILON {
+            return;
+        }
+
+        let (side, up) = (direction / length).any_orthonormal_pair();
+        let base = to - direction * 0.2;
+        let head_radius = length * 0.07;
+
+        for offset in [side, -side, up, -up] {
+            self.line(to, base + offset * head_radius, color);
+        }
+    }
+
+    /// `text` centered above `position`. Labels aren't hidden behind voxels.
+    #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+    #[inline]
+    pub fn text(&mut self, position: Vec3, text: &str, color: [u8; 4]) {
+        #[cfg(debug_assertions)]
+        self.labels.push((text.to_owned(), position, color));
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn vertices(&self) -> &[DebugVertex] {
+        &self.vertices
+    }
+
+    /// Hands over the labels gathered so far.
+    #[cfg(debug_assertions)]
+    pub fn take_labels(&mut self) -> Vec<(String, Vec3, [u8; 4])> {
+        std::mem::take(&mut self.labels)
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.labels.clear();
+    }
+}
+
+#[cfg(debug_assertions)]
+mod renderer {
+    use ash::vk;
+    use super::DebugVertex;
+    use super::super::buffer::Buffer;
+    use super::super::command_buffer::CommandBuffer;
+    use super::super::image::Image;
+    use super::super::pipeline::{BlendMode, GraphicsPipelineInfo, Pipeline};
+    use super::super::shader::ShaderStage;
+    use super::super::swapchain::OutputTransfer;
+    use super::super::vkcontext::VkContext;
+    use crate::camera::Camera;
+
+    /// Mirrors the `DebugDrawParameters` push constant block in `debug_draw.vert` and `debug_draw.frag`.
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug)]
+    struct DebugDrawPushConstants {
+        /// Camera position, then the tangent of half the vertical field of view.
+        camera_position: [f32; 4],
+        /// Camera right, then the aspect ratio.
+        camera_right: [f32; 4],
+        camera_up: [f32; 4],
+        camera_forward: [f32; 4],
+        /// Render extent over swapchain extent, to find a fragment's texel in the depth target.
+        depth_scale: [f32; 2],
+        output_transfer: u32,
+        paper_white: f32,
+    }
+
+    /// Geometry of one frame in flight's lines. The buffer only grows.
+    struct DebugDrawFrame {
+        vertex_buffer: Buffer,
+        vertex_count: u32,
+    }
+
+    /// Draws [`super::DebugDraw`] lines into the swapchain image, testing them against the voxel pass's hit
+    /// distances.
+    pub struct DebugDrawRenderer {
+        frames: Vec<DebugDrawFrame>,
+
+        sampler: vk::Sampler,
+
+        set_layout: vk::DescriptorSetLayout,
+
+        descriptor_pool: vk::DescriptorPool,
+        /// Samples each frame slot's depth and id target.
+        descriptor_sets: Vec<vk::DescriptorSet>,
+
+        pipeline: Pipeline,
+    }
+
+    impl DebugDrawRenderer {
+        pub fn new(vkcontext: &VkContext, frame_count: u32, color_format: vk::Format) -> Self {
+            let sampler = {
+                let create_info = vk::SamplerCreateInfo::builder()
+                    .mag_filter(vk::Filter::NEAREST)
+                    .min_filter(vk::Filter::NEAREST)
+                    .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+                    .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                    .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                    .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                    .max_lod(0.0)
+                    .build();
+
+                unsafe { vkcontext.device.create_sampler(&create_info, None).unwrap() }
+            };
+
+            let set_layout = {
+                let bindings = [
+                    vk::DescriptorSetLayoutBinding::builder()
+                        .binding(0)
+                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                        .descriptor_count(1)
+                        .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                        .build(),
+                ];
+
+                let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+                    .bindings(&bindings)
+                    .build();
+
+                unsafe { vkcontext.device.create_descriptor_set_layout(&create_info, None).unwrap() }
+            };
+
+            let descriptor_pool = {
+                let sizes = [
+                    vk::DescriptorPoolSize {
+                        ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                        descriptor_count: frame_count,
+                    },
+                ];
+
+                let create_info = vk::DescriptorPoolCreateInfo::builder()
+                    .max_sets(frame_count)
+                    .pool_sizes(&sizes)
+                    .build();
+
+                unsafe { vkcontext.device.create_descriptor_pool(&create_info, None).unwrap() }
+            };
+
+            let descriptor_sets = {
+                let set_layouts = vec![set_layout; frame_count as usize];
+
+                let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                    .descriptor_pool(descriptor_pool)
+                    .set_layouts(&set_layouts)
+                    .build();
+
+                unsafe { vkcontext.device.allocate_descriptor_sets(&allocate_info).unwrap() }
+            };
+
+            Self {
+                frames: Vec::new(),
+                sampler,
+                set_layout,
+                descriptor_pool,
+                descriptor_sets,
+                pipeline: Self::create_pipeline(vkcontext, set_layout, color_format),
+            }
+        }
+
+        pub fn destroy(&mut self, vkcontext: &VkContext) {
+            for frame in self.frames.drain(..) {
+                frame.vertex_buffer.destroy(vkcontext);
+            }
+
+            unsafe {
+                self.pipeline.destroy(vkcontext);
+
+                vkcontext.device.destroy_sampler(self.sampler, None);
+
+                vkcontext.device.destroy_descriptor_pool(self.descriptor_pool, None);
+
+                vkcontext.device.destroy_descriptor_set_layout(self.set_layout, None);
+            }
+        }
+
+        /// Rebuilds the pipeline for a new swapchain format. The GPU must be idle.
+        pub fn set_color_format(&mut self, vkcontext: &VkContext, color_format: vk::Format) {
+            self.pipeline.destroy(vkcontext);
+            self.pipeline = Self::create_pipeline(vkcontext, self.set_layout, color_format);
+        }
+
+        pub fn update_depth_descriptors(&self, vkcontext: &VkContext, depth_id_targets: &[Image]) {
+            for (set, target) in self.descriptor_sets.iter().zip(depth_id_targets) {
+                let image_info = [
+                    vk::DescriptorImageInfo::builder()
+                        .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                        .image_view(target.view)
+                        .sampler(self.sampler)
+                        .build(),
+                ];
+
+                let write_ops = [
+                    vk::WriteDescriptorSet::builder()
+                        .dst_set(*set)
+                        .dst_binding(0)
+                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                        .image_info(&image_info)
+                        .build(),
+                ];
+
+                unsafe { vkcontext.device.update_descriptor_sets(&write_ops, &[]) };
+            }
+        }
+
+        fn create_pipeline(
+            vkcontext: &VkContext,
+            set_layout: vk::DescriptorSetLayout,
+            color_format: vk::Format,
+        ) -> Pipeline {
+            let vertex_stage = ShaderStage::new(vkcontext, "shaders/debug_draw.vert.spv", vk::ShaderStageFlags::VERTEX);
+            let fragment_stage =
+                ShaderStage::new(vkcontext, "shaders/debug_draw.frag.spv", vk::ShaderStageFlags::FRAGMENT);
+
+            let vertex_bindings = [
+                vk::VertexInputBindingDescription {
+                    binding: 0,
+                    stride: std::mem::size_of::<DebugVertex>() as u32,
+                    input_rate: vk::VertexInputRate::VERTEX,
+                },
+            ];
+
+            // World position, then straight sRGBA.
+            let vertex_attributes = [
+                vk::VertexInputAttributeDescription { location: 0, binding: 0, format: vk::Format::R32G32B32_SFLOAT, offset: 0 },
+                vk::VertexInputAttributeDescription { location: 1, binding: 0, format: vk::Format::R8G8B8A8_UNORM, offset: 12 },
+            ];
+
+            let push_constant_ranges = [
+                vk::PushConstantRange {
+                    stage_flags: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                    offset: 0,
+                    size: std::mem::size_of::<DebugDrawPushConstants>() as u32,
+                },
+            ];
+
+            let pipeline = Pipeline::new_graphics(
+                vkcontext,
+                &[set_layout],
+                &push_constant_ranges,
+                &GraphicsPipelineInfo {
+                    stages: &[vertex_stage.shader_stage_create_info, fragment_stage.shader_stage_create_info],
+                    vertex_bindings: &vertex_bindings,
+                    vertex_attributes: &vertex_attributes,
+                    topology: vk::PrimitiveTopology::LINE_LIST,
+                    blend_mode: BlendMode::PremultipliedAlpha,
+                    color_format,
+                },
+            );
+
+            vertex_stage.destroy(vkcontext);
+            fragment_stage.destroy(vkcontext);
+
+            pipeline
+        }
+    }
+
+    impl DebugDrawRenderer {
+        /// Copies `vertices` into the buffer of frame slot `frame_slot`.
+        pub fn prepare(&mut self, vkcontext: &VkContext, frame_slot: usize, vertices: &[DebugVertex]) {
+            while self.frames.len() <= frame_slot {
+                self.frames.push(DebugDrawFrame {
+                    vertex_buffer: Buffer::new(vkcontext, 0, vk::BufferUsageFlags::VERTEX_BUFFER),
+                    vertex_count: 0,
+                });
+            }
+
+            let frame = &mut self.frames[frame_slot];
+            let vertex_bytes = std::mem::size_of_val(vertices) as vk::DeviceSize;
+
+            if vertex_bytes > frame.vertex_buffer.size {
+                frame.vertex_buffer.destroy(vkcontext);
+                frame.vertex_buffer =
+                    Buffer::new(vkcontext, vertex_bytes.next_power_of_two(), vk::BufferUsageFlags::VERTEX_BUFFER);
+            }
+
+            frame.vertex_buffer.write(0, vertices);
+            frame.vertex_count = vertices.len() as u32;
+        }
+
+        /// Draws the lines prepared for `frame_slot` as seen by `camera`. Dynamic rendering into an image of
+        /// `extent` must have begun, and the depth and id target rendered at `render_extent` must be readable.
+        #[allow(clippy::too_many_arguments)]
+        pub fn record(
+            &self,
+            vkcontext: &VkContext,
+            command_buffer: &CommandBuffer,
+            frame_slot: usize,
+            camera: &Camera,
+            extent: vk::Extent2D,
+            render_extent: vk::Extent2D,
+            output_transfer: OutputTransfer,
+            paper_white: f32,
+        ) {
+            let Some(frame) = self.frames.get(frame_slot).filter(|frame| frame.vertex_count > 0) else {
+                return;
+            };
+
+            let device = &vkcontext.device;
+
+            let push_constants = DebugDrawPushConstants {
+                camera_position: camera.position.extend((camera.fov * 0.5).tan()).to_array(),
+                camera_right: camera.right().extend(extent.width as f32 / extent.height as f32).to_array(),
+                camera_up: camera.up().extend(0.0).to_array(),
+                camera_forward: camera.forward().extend(0.0).to_array(),
+                depth_scale: [
+                    render_extent.width as f32 / extent.width as f32,
+                    render_extent.height as f32 / extent.height as f32,
+                ],
+                output_transfer: output_transfer as u32,
+                paper_white,
+            };
+
+            let scissor = vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            };
+
+            unsafe {
+                device.cmd_bind_pipeline(command_buffer.handle, vk::PipelineBindPoint::GRAPHICS, self.pipeline.handle);
+                device.cmd_bind_descriptor_sets(
+                    command_buffer.handle,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.pipeline.layout,
+                    0,
+                    &[self.descriptor_sets[frame_slot]],
+                    &[],
+                );
+                device.cmd_bind_vertex_buffers(command_buffer.handle, 0, &[frame.vertex_buffer.handle], &[0]);
+                device.cmd_set_scissor(command_buffer.handle, 0, &[scissor]);
+            }
+
+            self.pipeline.push_constants(
+                vkcontext,
+                command_buffer,
+                vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                &push_constants,
+            );
+
+            unsafe { device.cmd_draw(command_buffer.handle, frame.vertex_count, 1, 0, 0) };
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use glam::{IVec3, Vec3};
+use super::overlay::{self, PlacementGhost};
+use crate::camera::Camera;
+
+/// Half-float max, used as the cleared hit distance so every hit passes the depth test.
+pub const FAR_DISTANCE: f32 = 65504.0;
+
+/// Mirrors the std140 `EnvironmentBuffer` block in `voxel.comp`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct Environment {
+    pub camera_pos: [f32; 3],
+    pub camera_fov: f32,
+    pub camera_rot: [f32; 3],
+    pub time_of_day: f32,
+    /// Unit vector pointing towards the sun.
+    pub sun_direction: [f32; 3],
+    /// Angular radius of the sun in radians. Larger values give softer shadows.
+    pub sun_softness: f32,
+    pub sun_color: [f32; 3],
+    pub sun_intensity: f32,
+    pub sky_color: [f32; 3],
+    pub ambient_intensity: f32,
+    pub render_extent: [u32; 2],
+    pub frame_index: u32,
+    pub shadow_samples: u32,
+    /// Hemisphere rays per pixel per frame. Zero disables ambient occlusion.
+    pub ao_samples: u32,
+    /// Length of the ambient occlusion rays, in voxels.
+    pub ao_radius: f32,
+    /// Frames of ambient occlusion history that are still valid. Maintained by the renderer.
+    pub ao_accumulated_frames: u32,
+    /// Caps the history weight, trading noise for responsiveness to changes.
+    pub ao_max_frames: u32,
+    /// Camera of the previous frame, for reprojecting history. Maintained by the renderer.
+    pub previous_camera_pos: [f32; 3],
+    pub previous_camera_fov: f32,
+    pub previous_camera_rot: [f32; 3],
+    /// Weight of this frame against reprojected history. Maintained by the renderer from
+    /// [`super::RenderSettings::temporal_blend`].
+    pub temporal_blend: f32,
+    /// Non-zero if the voxel pass writes the G-buffer. Maintained by the renderer from
+    /// [`super::RenderSettings::gbuffer`].
+    pub write_gbuffer: u32,
+    /// Non-zero if the selection outline is drawn. The overlay fields are set through
+    /// [`Environment::set_selection`] and [`Environment::set_placement_ghost`].
+    pub selection_enabled: u32,
+    pub selection_face: u32,
+    pub ghost_enabled: u32,
+    pub selection_min: [f32; 3],
+    pub ghost_valid: u32,
+    pub selection_max: [f32; 3],
+    pub ghost_front_face: u32,
+    pub ghost_min: [f32; 3],
+    /// Width of the selection outline in voxels. Distant outlines are widened to stay at least a pixel
+    /// and a half wide.
+    pub outline_width: f32,
+    pub ghost_max: [f32; 3],
+    pub ghost_opacity: f32,
+    /// A [`super::DebugView`]. Maintained by the renderer from [`super::RenderSettings::debug_view`].
+    pub debug_view: u32,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        let mut environment = Environment {
+            camera_pos: [0.0; 3],
+            camera_fov: 0.0,
+            camera_rot: [0.0; 3],
+            time_of_day: 0.0,
+            sun_direction: [0.0, 1.0, 0.0],
+            sun_softness: 0.02,
+            sun_color: [1.0; 3],
+            sun_intensity: 1.0,
+            sky_color: [0.0; 3],
+            ambient_intensity: 0.0,
+            render_extent: [0; 2],
+            frame_index: 0,
+            shadow_samples: 1,
+            ao_samples: 2,
+            ao_radius: 4.0,
+            ao_accumulated_frames: 0,
+            ao_max_frames: 32,
+            previous_camera_pos: [0.0; 3],
+            previous_camera_fov: 0.0,
+            previous_camera_rot: [0.0; 3],
+            temporal_blend: 1.0,
+            write_gbuffer: 0,
+            selection_enabled: 0,
+            selection_face: overlay::NO_FACE,
+            ghost_enabled: 0,
+            selection_min: [0.0; 3],
+            ghost_valid: 0,
+            selection_max: [0.0; 3],
+            ghost_front_face: overlay::NO_FACE,
+            ghost_min: [0.0; 3],
+            outline_width: 0.03,
+            ghost_max: [0.0; 3],
+            ghost_opacity: 0.35,
+            debug_view: 0,
+        };
+
+        environment.set_camera(&Camera::default());
+        environment.set_time_of_day(0.35);
+
+        environment
+    }
+}
+
+impl Environment {
+    /// Whether anything that invalidates accumulated history differs between `self` and `other`.
+    pub fn is_view_different(&self, other: &Environment) -> bool {
+        self.camera_pos != other.camera_pos
+            || self.camera_rot != other.camera_rot
+            || self.camera_fov != other.camera_fov
+            || self.render_extent != other.render_extent
+            || self.ao_radius != other.ao_radius
+    }
+
+    pub fn camera(&self) -> Camera {
+        Camera {
+            position: Vec3::from(self.camera_pos),
+            rotation: Vec3::from(self.camera_rot),
+            fov: self.camera_fov,
+        }
+    }
+
+    pub fn set_camera(&mut self, camera: &Camera) {
+        self.camera_pos = camera.position.to_array();
+        self.camera_rot = camera.rotation.to_array();
+        self.camera_fov = camera.fov;
+    }
+
+    /// Outlines the voxel whose world space corner with the lowest coordinates is `voxel`, highlighting the
+    /// face with `normal`.
+    pub fn set_selection(&mut self, selection: Option<(Vec3, IVec3)>) {
+        let Some((voxel, normal)) = selection else {
+            self.selection_enabled = 0;
+            return;
+        };
+
+        self.selection_enabled = 1;
+        self.selection_min = voxel.to_array();
+        self.selection_max = (voxel + Vec3::ONE).to_array();
+        self.selection_face = overlay::face_index(normal);
+    }
+
+    pub fn set_placement_ghost(&mut self, ghost: Option<&PlacementGhost>) {
+        let Some(ghost) = ghost else {
+            self.ghost_enabled = 0;
+            return;
+        };
+
+        self.ghost_enabled = 1;
+        self.ghost_min = ghost.position.to_array();
+        self.ghost_max = (ghost.position + ghost.rotated_size().as_vec3()).to_array();
+        self.ghost_front_face = ghost.front_face();
+        self.ghost_valid = ghost.is_valid as u32;
+    }
+
+    /// Moves the sun along its daily arc. `time_of_day` wraps at 1.0; 0.25 is sunrise, 0.5 noon and 0.75
+    /// sunset.
+    pub fn set_time_of_day(&mut self, time_of_day: f32) {
+        let time_of_day = time_of_day.rem_euclid(1.0);
+        let angle = (time_of_day - 0.25) * std::f32::consts::TAU;
+
+        // Tilted so the sun never passes straight overhead, which makes for flat, shadowless noons.
+        let sun_direction = Vec3::new(angle.cos(), angle.sin(), 0.35).normalize();
+
+        // 1.0 at noon, 0.0 at and below the horizon.
+        let elevation = sun_direction.y.max(0.0);
+        let daylight = elevation.powf(0.5);
+
+        // Sunlight reddens as it travels through more atmosphere near the horizon.
+        let sun_color = Vec3::new(1.0, 0.6, 0.35).lerp(Vec3::new(1.0, 0.96, 0.9), elevation.powf(0.3));
+
+        let night_sky = Vec3::new(0.01, 0.015, 0.04);
+        let day_sky = Vec3::new(0.45, 0.65, 1.0);
+
+        self.time_of_day = time_of_day;
+        self.sun_direction = sun_direction.to_array();
+        self.sun_color = sun_color.to_array();
+        self.sun_intensity = 3.0 * daylight;
+        self.sky_color = night_sky.lerp(day_sky, daylight).to_array();
+        self.ambient_intensity = 0.3;
+    }
+}
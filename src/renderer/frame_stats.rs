@@ -0,0 +1,358 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+const DEFAULT_HISTORY_LENGTH: usize = 1000;
+/// Frames taking longer than this many microseconds are counted as hitches.
+const HITCH_THRESHOLD: u128 = 50_000;
+
+/// The parts of a frame the renderer times individually. All timings are CPU-side, in microseconds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FramePhase {
+    /// Sleeping to hold the frame rate limit.
+    Limiter,
+    FenceWait,
+    Record,
+    Submit,
+    Present,
+}
+
+impl FramePhase {
+    pub const COUNT: usize = 5;
+
+    pub const ALL: [FramePhase; Self::COUNT] = [
+        FramePhase::Limiter,
+        FramePhase::FenceWait,
+        FramePhase::Record,
+        FramePhase::Submit,
+        FramePhase::Present,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FramePhase::Limiter => "limiter",
+            FramePhase::FenceWait => "fence",
+            FramePhase::Record => "record",
+            FramePhase::Submit => "submit",
+            FramePhase::Present => "present",
+        }
+    }
+}
+
+/// Collects the times of the last `history_length` frames and derives pacing statistics from them.
+pub struct FrameStats {
+    history_length: usize,
+    /// Oldest first.
+    frame_times: VecDeque<u128>,
+    phase_times: VecDeque<[u128; FramePhase::COUNT]>,
+    /// Time the GPU took for each frame, as far as it's known. Arrives a few frames late.
+    gpu_frame_times: VecDeque<u128>,
+
+    current_phases: [u128; FramePhase::COUNT],
+
+    hitch_count: u64,
+    frame_count: u64,
+}
+
+impl FrameStats {
+    pub fn new() -> Self {
+        Self::with_history_length(DEFAULT_HISTORY_LENGTH)
+    }
+
+    pub fn with_history_length(history_length: usize) -> Self {
+        assert!(history_length > 0, "FrameStats needs room for at least one frame.");
+
+        FrameStats {
+            history_length,
+            frame_times: VecDeque::with_capacity(history_length),
+            phase_times: VecDeque::with_capacity(history_length),
+            gpu_frame_times: VecDeque::with_capacity(history_length),
+            current_phases: [0; FramePhase::COUNT],
+            hitch_count: 0,
+            frame_count: 0,
+        }
+    }
+}
+
+impl FrameStats {
+    /// Adds `elapsed` microseconds to the given phase of the frame currently being measured.
+    pub fn record_phase(&mut self, phase: FramePhase, elapsed: u128) {
+        self.current_phases[phase as usize] += elapsed;
+    }
+
+    /// Closes the current frame. Returns whether the frame was a hitch.
+    pub fn push_frame(&mut self, frame_time: u128) -> bool {
+        let phases = std::mem::replace(&mut self.current_phases, [0; FramePhase::COUNT]);
+
+        if self.frame_times.len() == self.history_length {
+            self.frame_times.pop_front();
+            self.phase_times.pop_front();
+        }
+
+        self.frame_times.push_back(frame_time);
+        self.phase_times.push_back(phases);
+        self.frame_count += 1;
+
+        let is_hitch = frame_time > HITCH_THRESHOLD;
+
+        if is_hitch {
+            self.hitch_count += 1;
+            log::debug!("Frame hitch: {:.2}ms.", frame_time as f64 / 1000.0);
+        }
+
+        is_hitch
+    }
+
+    /// Adds the time the GPU took for a frame, in microseconds.
+    pub fn push_gpu_frame_time(&mut self, gpu_frame_time: u128) {
+        if self.gpu_frame_times.len() == self.history_length {
+            self.gpu_frame_times.pop_front();
+        }
+
+        self.gpu_frame_times.push_back(gpu_frame_time);
+    }
+
+    /// Starts over, as if no frames had been pushed.
+    pub fn clear(&mut self) {
+        self.frame_times.clear();
+        self.phase_times.clear();
+        self.gpu_frame_times.clear();
+        self.current_phases = [0; FramePhase::COUNT];
+        self.hitch_count = 0;
+        self.frame_count = 0;
+    }
+}
+
+impl FrameStats {
+    /// Average frame time over the history, in microseconds.
+    pub fn average_frame_time(&self) -> f64 {
+        if self.frame_times.is_empty() {
+            return 0.0;
+        }
+
+        self.frame_times.iter().sum::<u128>() as f64 / self.frame_times.len() as f64
+    }
+
+    /// Average over the last `count` frames of the frame time less limiter sleep and presenting, which
+    /// only wait for the display. In microseconds.
+    pub fn recent_average_busy_time(&self, count: usize) -> f64 {
+        let count = count.min(self.frame_times.len());
+
+        if count == 0 {
+            return 0.0;
+        }
+
+        let start = self.frame_times.len() - count;
+        let busy_times = self.frame_times.range(start..).zip(self.phase_times.range(start..)).map(|(frame_time, phases)| {
+            frame_time.saturating_sub(phases[FramePhase::Limiter as usize] + phases[FramePhase::Present as usize])
+        });
+
+        busy_times.sum::<u128>() as f64 / count as f64
+    }
+
+    /// Average GPU time over the last `count` frames it's known for, in microseconds.
+    pub fn recent_average_gpu_frame_time(&self, count: usize) -> f64 {
+        let count = count.min(self.gpu_frame_times.len());
+
+        if count == 0 {
+            return 0.0;
+        }
+
+        self.gpu_frame_times.range(self.gpu_frame_times.len() - count..).sum::<u128>() as f64 / count as f64
+    }
+
+    /// The frame time that 99% of the frames in the history stay below, in microseconds.
+    pub fn p99_frame_time(&self) -> f64 {
+        let sorted = self.sorted_frame_times();
+
+        if sorted.is_empty() {
+            return 0.0;
+        }
+
+        let index = ((sorted.len() as f64 * 0.99).ceil() as usize).clamp(1, sorted.len()) - 1;
+
+        sorted[index] as f64
+    }
+
+    /// Frame rate over the slowest 1% of the frames in the history.
+    pub fn one_percent_low_fps(&self) -> f64 {
+        let sorted = self.sorted_frame_times();
+
+        if sorted.is_empty() {
+            return 0.0;
+        }
+
+        let count = (sorted.len() / 100).max(1);
+        let slowest = &sorted[sorted.len() - count..];
+
+        to_fps(slowest.iter().sum::<u128>() as f64 / count as f64)
+    }
+
+    /// Average time spent in each phase over the history, in microseconds.
+    pub fn average_phase_times(&self) -> [f64; FramePhase::COUNT] {
+        let mut averages = [0.0; FramePhase::COUNT];
+
+        if self.phase_times.is_empty() {
+            return averages;
+        }
+
+        for phases in self.phase_times.iter() {
+            for (average, time) in averages.iter_mut().zip(phases.iter()) {
+                *average += *time as f64;
+            }
+        }
+
+        for average in averages.iter_mut() {
+            *average /= self.phase_times.len() as f64;
+        }
+
+        averages
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    pub fn summary(&self) -> FrameStatsSummary {
+        let average_frame_time = self.average_frame_time();
+
+        FrameStatsSummary {
+            average_fps: to_fps(average_frame_time),
+            average_frame_time,
+            one_percent_low_fps: self.one_percent_low_fps(),
+            p99_frame_time: self.p99_frame_time(),
+            phase_times: self.average_phase_times(),
+            average_gpu_frame_time: self.recent_average_gpu_frame_time(self.gpu_frame_times.len()),
+            hitch_count: self.hitch_count,
+        }
+    }
+
+    fn sorted_frame_times(&self) -> Vec<u128> {
+        let mut sorted = Vec::from(self.frame_times.clone());
+        sorted.sort_unstable();
+        sorted
+    }
+}
+
+/// A snapshot of [`FrameStats`], suitable for an overlay or a log line. Times are in microseconds.
+#[derive(Clone, Copy, Debug)]
+pub struct FrameStatsSummary {
+    pub average_fps: f64,
+    pub average_frame_time: f64,
+    pub one_percent_low_fps: f64,
+    pub p99_frame_time: f64,
+    pub phase_times: [f64; FramePhase::COUNT],
+    /// Zero if the GPU's times aren't known.
+    pub average_gpu_frame_time: f64,
+    pub hitch_count: u64,
+}
+
+impl fmt::Display for FrameStatsSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} fps ({:.2}ms), 1% low {:.1} fps, p99 {:.2}ms, hitches {} |",
+            self.average_fps,
+            self.average_frame_time / 1000.0,
+            self.one_percent_low_fps,
+            self.p99_frame_time / 1000.0,
+            self.hitch_count,
+        )?;
+
+        for (phase, time) in FramePhase::ALL.iter().zip(self.phase_times.iter()) {
+            write!(f, " {} {:.2}ms", phase.name(), time / 1000.0)?;
+        }
+
+        if self.average_gpu_frame_time > 0.0 {
+            write!(f, " | gpu {:.2}ms", self.average_gpu_frame_time / 1000.0)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn to_fps(frame_time: f64) -> f64 {
+    if frame_time > 0.0 { 1_000_000.0 / frame_time } else { 0.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats_with(frame_times: impl IntoIterator<Item = u128>) -> FrameStats {
+        let mut stats = FrameStats::new();
+
+        for frame_time in frame_times {
+            stats.push_frame(frame_time);
+        }
+
+        stats
+    }
+
+    #[test]
+    fn percentiles_of_evenly_spread_frames() {
+        // 1 ms to 100 ms.
+        let stats = stats_with((1..=100).map(|i| i * 1000));
+
+        assert_eq!(stats.p99_frame_time(), 99_000.0);
+        assert_eq!(stats.one_percent_low_fps(), 10.0);
+    }
+
+    #[test]
+    fn percentiles_with_a_few_slow_frames() {
+        // 198 frames at 10 ms and 2 at 50 ms. The 1% low is exactly the slow ones.
+        let stats = stats_with(std::iter::repeat_n(10_000, 198).chain([50_000, 50_000]));
+
+        assert_eq!(stats.p99_frame_time(), 10_000.0);
+        assert_eq!(stats.one_percent_low_fps(), 20.0);
+        assert_eq!(stats.summary().average_fps, 1_000_000.0 / 10_400.0);
+    }
+
+    #[test]
+    fn history_keeps_the_newest_frames() {
+        let mut stats = FrameStats::with_history_length(4);
+
+        for frame_time in [60_000, 1, 2, 3, 4, 5] {
+            stats.push_frame(frame_time);
+        }
+
+        assert_eq!(stats.frame_times, [2, 3, 4, 5]);
+        assert_eq!(stats.recent_average_busy_time(2), 4.5);
+        assert_eq!(stats.frame_count(), 6);
+        assert_eq!(stats.summary().hitch_count, 1);
+
+        stats.clear();
+
+        assert_eq!(stats.frame_count(), 0);
+        assert_eq!(stats.summary().hitch_count, 0);
+        assert_eq!(stats.p99_frame_time(), 0.0);
+    }
+
+    #[test]
+    fn busy_time_leaves_out_waiting_for_the_display() {
+        let mut stats = FrameStats::new();
+
+        for _ in 0..4 {
+            stats.record_phase(FramePhase::Limiter, 6_000);
+            stats.record_phase(FramePhase::Record, 2_000);
+            stats.record_phase(FramePhase::Present, 4_000);
+            stats.push_frame(16_000);
+        }
+
+        assert_eq!(stats.average_frame_time(), 16_000.0);
+        assert_eq!(stats.recent_average_busy_time(4), 6_000.0);
+    }
+
+    #[test]
+    fn gpu_frame_times_average_the_newest() {
+        let mut stats = FrameStats::with_history_length(3);
+
+        assert_eq!(stats.recent_average_gpu_frame_time(2), 0.0);
+
+        for gpu_frame_time in [9_000, 1_000, 2_000, 4_000] {
+            stats.push_gpu_frame_time(gpu_frame_time);
+        }
+
+        assert_eq!(stats.recent_average_gpu_frame_time(2), 3_000.0);
+        assert_eq!(stats.summary().average_gpu_frame_time, 7_000.0 / 3.0);
+    }
+}
@@ -0,0 +1,78 @@
+use ash::vk;
+use super::image::Image;
+use super::vkcontext::VkContext;
+
+/// A per pixel output of the voxel pass besides color. Pixels without a hit keep their cleared value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GBufferAttachment {
+    /// Distance along the view ray, `r32f`. Cleared to [`super::environment::FAR_DISTANCE`].
+    Distance,
+    /// Face normal, `rgba8_snorm` with the normal in `xyz`. Cleared to zero.
+    Normal,
+    /// Instance id plus one, `r32ui`. Cleared to zero.
+    InstanceId,
+    /// Voxel coordinate within the instance's octree, `rgba16ui` with the coordinate in `xyz`. Cleared to
+    /// zero.
+    VoxelCoordinate,
+}
+
+impl GBufferAttachment {
+    pub const COUNT: usize = 4;
+
+    pub const ALL: [GBufferAttachment; Self::COUNT] = [
+        GBufferAttachment::Distance,
+        GBufferAttachment::Normal,
+        GBufferAttachment::InstanceId,
+        GBufferAttachment::VoxelCoordinate,
+    ];
+
+    pub fn format(self) -> vk::Format {
+        match self {
+            GBufferAttachment::Distance => vk::Format::R32_SFLOAT,
+            GBufferAttachment::Normal => vk::Format::R8G8B8A8_SNORM,
+            GBufferAttachment::InstanceId => vk::Format::R32_UINT,
+            GBufferAttachment::VoxelCoordinate => vk::Format::R16G16B16A16_UINT,
+        }
+    }
+
+    /// Binding in set 0 of `voxel.comp`.
+    pub fn binding(self) -> u32 {
+        7 + self as u32
+    }
+
+    pub fn clear_value(self) -> vk::ClearColorValue {
+        match self {
+            GBufferAttachment::Distance => vk::ClearColorValue { float32: [super::environment::FAR_DISTANCE, 0.0, 0.0, 0.0] },
+            GBufferAttachment::Normal => vk::ClearColorValue { float32: [0.0; 4] },
+            GBufferAttachment::InstanceId | GBufferAttachment::VoxelCoordinate => vk::ClearColorValue { uint32: [0; 4] },
+        }
+    }
+}
+
+/// The G-buffer images of one frame in flight.
+pub struct GBuffer {
+    images: [Image; GBufferAttachment::COUNT],
+}
+
+impl GBuffer {
+    pub fn new(vkcontext: &VkContext, extent: vk::Extent2D) -> Self {
+        let images = GBufferAttachment::ALL.map(|attachment| {
+            Image::new(
+                vkcontext,
+                extent,
+                attachment.format(),
+                vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC | vk::ImageUsageFlags::TRANSFER_DST,
+            )
+        });
+
+        Self { images }
+    }
+
+    pub fn image(&self, attachment: GBufferAttachment) -> &Image {
+        &self.images[attachment as usize]
+    }
+
+    pub fn images(&self) -> impl Iterator<Item = &Image> {
+        self.images.iter()
+    }
+}
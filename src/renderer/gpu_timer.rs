@@ -0,0 +1,109 @@
+use ash::vk;
+use super::command_buffer::CommandBuffer;
+use super::settings::MAX_FRAMES_IN_FLIGHT;
+use super::vkcontext::VkContext;
+
+/// Times the commands of each frame on the GPU, with a pair of timestamp queries per frame slot. A slot's
+/// queries are read once its fence has been waited on, so reading them never stalls.
+pub struct GpuTimer {
+    query_pool: vk::QueryPool,
+    /// Nanoseconds per timestamp tick.
+    timestamp_period: f64,
+    /// The bits of a timestamp the graphics queue writes, for taking differences across a wrap.
+    timestamp_mask: u64,
+    /// Whether each slot's queries were submitted and haven't been read yet.
+    is_pending: [bool; MAX_FRAMES_IN_FLIGHT as usize],
+}
+
+impl GpuTimer {
+    /// Returns `None` if the graphics queue can't write timestamps.
+    pub fn new(vkcontext: &VkContext) -> Option<Self> {
+        let properties = unsafe { vkcontext.instance.get_physical_device_properties(vkcontext.physical_device) };
+        let queue_families = unsafe {
+            vkcontext.instance.get_physical_device_queue_family_properties(vkcontext.physical_device)
+        };
+        let valid_bits = queue_families[vkcontext.queue_family_indices.graphics_index as usize].timestamp_valid_bits;
+
+        if valid_bits == 0 || properties.limits.timestamp_period <= 0.0 {
+            return None;
+        }
+
+        let create_info = vk::QueryPoolCreateInfo::builder()
+            .query_type(vk::QueryType::TIMESTAMP)
+            .query_count(2 * MAX_FRAMES_IN_FLIGHT)
+            .build();
+
+        let query_pool = unsafe { vkcontext.device.create_query_pool(&create_info, None).unwrap() };
+
+        Some(Self {
+            query_pool,
+            timestamp_period: properties.limits.timestamp_period as f64,
+            timestamp_mask: if valid_bits >= 64 { u64::MAX } else { (1 << valid_bits) - 1 },
+            is_pending: [false; MAX_FRAMES_IN_FLIGHT as usize],
+        })
+    }
+
+    pub fn destroy(&self, vkcontext: &VkContext) {
+        unsafe { vkcontext.device.destroy_query_pool(self.query_pool, None) };
+    }
+}
+
+impl GpuTimer {
+    /// Resets the slot's queries and writes the start timestamp once `stage` may run, which should be where
+    /// the frame waits for its swapchain image so that wait isn't counted. Goes first in the command buffer.
+    pub fn record_start(
+        &self,
+        vkcontext: &VkContext,
+        command_buffer: &CommandBuffer,
+        frame_slot: usize,
+        stage: vk::PipelineStageFlags,
+    ) {
+        let first_query = 2 * frame_slot as u32;
+
+        unsafe {
+            vkcontext.device.cmd_reset_query_pool(command_buffer.handle, self.query_pool, first_query, 2);
+            vkcontext.device.cmd_write_timestamp(command_buffer.handle, stage, self.query_pool, first_query);
+        }
+    }
+
+    /// Writes the end timestamp once everything before it has finished. Goes last in the command buffer.
+    pub fn record_end(&self, vkcontext: &VkContext, command_buffer: &CommandBuffer, frame_slot: usize) {
+        unsafe {
+            vkcontext.device.cmd_write_timestamp(
+                command_buffer.handle,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                self.query_pool,
+                2 * frame_slot as u32 + 1,
+            );
+        }
+    }
+
+    /// Marks the slot's queries as submitted, to be read by [`GpuTimer::read`].
+    pub fn submitted(&mut self, frame_slot: usize) {
+        self.is_pending[frame_slot] = true;
+    }
+
+    /// The GPU time of the last frame submitted in `frame_slot`, in microseconds, or `None` if there's no
+    /// frame to read. The slot's fence must have been signalled.
+    pub fn read(&mut self, vkcontext: &VkContext, frame_slot: usize) -> Option<u128> {
+        if !std::mem::take(&mut self.is_pending[frame_slot]) {
+            return None;
+        }
+
+        let mut timestamps = [0u64; 2];
+
+        unsafe {
+            vkcontext.device.get_query_pool_results(
+                self.query_pool,
+                2 * frame_slot as u32,
+                2,
+                &mut timestamps,
+                vk::QueryResultFlags::TYPE_64,
+            )
+        }.ok()?;
+
+        let ticks = timestamps[1].wrapping_sub(timestamps[0]) & self.timestamp_mask;
+
+        Some((ticks as f64 * self.timestamp_period / 1000.0) as u128)
+    }
+}
@@ -0,0 +1,98 @@
+use ash::vk;
+use super::utility::{create_image_view, find_memory_type};
+use super::vkcontext::VkContext;
+
+/// A device-local 2D image with a view covering the whole image. The memory is either its own allocation or
+/// part of someone else's, see [`Image::new_unbound`].
+pub struct Image {
+    pub view: vk::ImageView,
+    /// Null if the memory isn't owned by the image.
+    pub memory: vk::DeviceMemory,
+    pub handle: vk::Image,
+
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+}
+
+impl Image {
+    pub fn new(
+        vkcontext: &VkContext,
+        extent: vk::Extent2D,
+        format: vk::Format,
+        usage: vk::ImageUsageFlags,
+    ) -> Self {
+        let mut image = Self::new_unbound(vkcontext, extent, format, usage);
+
+        let memory = {
+            let requirements = unsafe { vkcontext.device.get_image_memory_requirements(image.handle) };
+
+            let allocate_info = vk::MemoryAllocateInfo::builder()
+                .allocation_size(requirements.size)
+                .memory_type_index(find_memory_type(
+                    vkcontext,
+                    requirements.memory_type_bits,
+                    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                ))
+                .build();
+
+            vkcontext.allocate_memory(&allocate_info)
+        };
+
+        image.bind_memory(vkcontext, memory, 0);
+        image.memory = memory;
+
+        image
+    }
+
+    /// Creates an image without memory or a view. It can't be used until [`Image::bind_memory`] is called.
+    pub fn new_unbound(
+        vkcontext: &VkContext,
+        extent: vk::Extent2D,
+        format: vk::Format,
+        usage: vk::ImageUsageFlags,
+    ) -> Self {
+        let handle = {
+            let create_info = vk::ImageCreateInfo::builder()
+                .image_type(vk::ImageType::TYPE_2D)
+                .format(format)
+                .extent(vk::Extent3D {
+                    width: extent.width.max(1),
+                    height: extent.height.max(1),
+                    depth: 1,
+                })
+                .mip_levels(1)
+                .array_layers(1)
+                .samples(vk::SampleCountFlags::TYPE_1)
+                .tiling(vk::ImageTiling::OPTIMAL)
+                .usage(usage)
+                .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                .initial_layout(vk::ImageLayout::UNDEFINED)
+                .build();
+
+            unsafe { vkcontext.device.create_image(&create_info, None).unwrap() }
+        };
+
+        Self {
+            view: vk::ImageView::null(),
+            memory: vk::DeviceMemory::null(),
+            handle,
+            format,
+            extent,
+        }
+    }
+
+    /// Binds memory owned by someone else at `offset` and creates the view.
+    pub fn bind_memory(&mut self, vkcontext: &VkContext, memory: vk::DeviceMemory, offset: vk::DeviceSize) {
+        unsafe { vkcontext.device.bind_image_memory(self.handle, memory, offset).unwrap() };
+
+        self.view = create_image_view(&vkcontext.device, self.handle, self.format, vk::ImageAspectFlags::COLOR, 1);
+    }
+
+    pub fn destroy(&self, vkcontext: &VkContext) {
+        unsafe {
+            vkcontext.device.destroy_image_view(self.view, None);
+            vkcontext.device.destroy_image(self.handle, None);
+            vkcontext.free_memory(self.memory);
+        }
+    }
+}
@@ -0,0 +1,22 @@
+/// Most point lights the light buffer holds. Lights past this are dropped.
+pub const MAX_POINT_LIGHTS: usize = 256;
+
+/// Mirrors `PointLight` in `voxel.comp`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointLight {
+    /// World space position. A light inside a voxel, such as a lamp block, isn't shadowed by it.
+    pub position: [f32; 3],
+    /// Distance in voxels at which the light has faded out completely.
+    pub radius: f32,
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+/// Mirrors the fixed part of the std140 `LightBuffer` block in `voxel.comp`. The lights follow it.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(super) struct LightBufferHeader {
+    pub light_count: u32,
+    pub _padding: [u32; 3],
+}
@@ -0,0 +1,46 @@
+use glam::{IVec3, UVec3, Vec3, Vec3Swizzles};
+
+/// Box faces are indexed 0 to 5 as +X, -X, +Y, -Y, +Z and -Z, in `resolve.comp` too. This index is none of them.
+pub(super) const NO_FACE: u32 = 6;
+
+/// Translucent preview of a block or machine about to be placed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlacementGhost {
+    /// World space corner with the lowest coordinates, after rotation.
+    pub position: Vec3,
+    /// Size in voxels before rotation. The front is the +Z face.
+    pub size: UVec3,
+    /// Quarter turns around +Y. One turn takes the front from +Z to +X.
+    pub rotation: u32,
+    /// Tints the ghost green if set, red otherwise.
+    pub is_valid: bool,
+}
+
+impl PlacementGhost {
+    /// Size in voxels after rotation.
+    pub fn rotated_size(&self) -> UVec3 {
+        if self.rotation % 2 == 1 {
+            self.size.zyx()
+        } else {
+            self.size
+        }
+    }
+
+    /// Face index of the front after rotation.
+    pub(super) fn front_face(&self) -> u32 {
+        [4, 0, 5, 1][(self.rotation % 4) as usize]
+    }
+}
+
+/// Face index of an axis aligned unit normal, or [`NO_FACE`] for anything else.
+pub(super) fn face_index(normal: IVec3) -> u32 {
+    match normal.to_array() {
+        [1, 0, 0] => 0,
+        [-1, 0, 0] => 1,
+        [0, 1, 0] => 2,
+        [0, -1, 0] => 3,
+        [0, 0, 1] => 4,
+        [0, 0, -1] => 5,
+        _ => NO_FACE,
+    }
+}
@@ -0,0 +1,178 @@
+use ash::vk;
+use glam::{IVec3, UVec2, UVec3, Vec3};
+use crate::voxel::VoxelOctree;
+use super::buffer::Buffer;
+use super::command_buffer::CommandBuffer;
+use super::environment::Environment;
+use super::gbuffer::{GBuffer, GBufferAttachment};
+use super::vkcontext::VkContext;
+use super::COLOR_SUBRESOURCE_LAYERS;
+
+/// What a pixel shows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PickResult {
+    pub instance: u32,
+    /// Coordinate within the instance's octree.
+    pub voxel: UVec3,
+    pub normal: IVec3,
+    /// Distance from the camera along the pixel's ray.
+    pub distance: f32,
+}
+
+/// A finished GPU pick, see [`super::Renderer::request_gpu_pick`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GpuPick {
+    /// The window coordinate that was requested.
+    pub position: UVec2,
+    pub result: Option<PickResult>,
+}
+
+/// Casts a ray against every instance, given as its id, octree and position, like `voxel.comp` traces it.
+/// Ties go to the lowest instance id, like they do on the GPU, so `instances` must be in id order.
+pub fn raycast_instances<'a>(
+    instances: impl Iterator<Item = (u32, &'a VoxelOctree, Vec3)>,
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+) -> Option<PickResult> {
+    let mut nearest: Option<PickResult> = None;
+
+    for (id, octree, position) in instances {
+        let Some(hit) = octree.raycast(origin - position, direction, max_distance) else {
+            continue;
+        };
+
+        if nearest.is_none_or(|nearest| hit.distance < nearest.distance) {
+            nearest = Some(PickResult {
+                instance: id,
+                voxel: hit.voxel,
+                normal: hit.normal,
+                distance: hit.distance,
+            });
+        }
+    }
+
+    nearest
+}
+
+/// Byte offset of every G-buffer attachment's texel in the readback buffer. Each is aligned to its texel size.
+const READBACK_OFFSETS: [vk::DeviceSize; GBufferAttachment::COUNT] = [0, 4, 8, 16];
+const READBACK_SIZE: vk::DeviceSize = 24;
+
+/// A GPU pick recorded into a frame that hasn't finished yet.
+pub struct PendingPick {
+    pub position: UVec2,
+    /// Render target pixel the window coordinate maps to.
+    pub pixel: UVec2,
+    /// Signalled once the frame that copies the texels completes.
+    pub fence: vk::Fence,
+    /// The environment of that frame, for checking the result against a CPU pick.
+    pub environment: Environment,
+}
+
+/// Copies single G-buffer texels into host visible memory.
+pub struct PickReadback {
+    buffer: Buffer,
+}
+
+impl PickReadback {
+    pub fn new(vkcontext: &VkContext) -> Self {
+        Self {
+            buffer: Buffer::new(vkcontext, READBACK_SIZE, vk::BufferUsageFlags::TRANSFER_DST),
+        }
+    }
+
+    /// Records copies of `pixel` from every attachment. The attachments must be in transfer source layout.
+    pub fn record(&self, vkcontext: &VkContext, command_buffer: &CommandBuffer, gbuffer: &GBuffer, pixel: UVec2) {
+        let device = &vkcontext.device;
+
+        for (attachment, offset) in GBufferAttachment::ALL.iter().zip(READBACK_OFFSETS) {
+            let region = vk::BufferImageCopy {
+                buffer_offset: offset,
+                buffer_row_length: 0,
+                buffer_image_height: 0,
+                image_subresource: COLOR_SUBRESOURCE_LAYERS,
+                image_offset: vk::Offset3D { x: pixel.x as i32, y: pixel.y as i32, z: 0 },
+                image_extent: vk::Extent3D { width: 1, height: 1, depth: 1 },
+            };
+
+            unsafe {
+                device.cmd_copy_image_to_buffer(
+                    command_buffer.handle,
+                    gbuffer.image(*attachment).handle,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    self.buffer.handle,
+                    &[region],
+                );
+            }
+        }
+    }
+
+    /// The buffer the copies land in, for declaring it in the frame's render graph.
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer.handle
+    }
+
+    /// Decodes the copied texels. Only valid once the frame that recorded the copy has completed.
+    pub fn read(&self) -> Option<PickResult> {
+        let instance_id = self.buffer.read::<u32>(READBACK_OFFSETS[GBufferAttachment::InstanceId as usize]);
+
+        // Zero is the cleared value, so nothing was hit.
+        if instance_id == 0 {
+            return None;
+        }
+
+        let normal = self.buffer.read::<[i8; 4]>(READBACK_OFFSETS[GBufferAttachment::Normal as usize]);
+        let voxel = self.buffer.read::<[u16; 4]>(READBACK_OFFSETS[GBufferAttachment::VoxelCoordinate as usize]);
+
+        Some(PickResult {
+            instance: instance_id - 1,
+            voxel: UVec3::new(voxel[0] as u32, voxel[1] as u32, voxel[2] as u32),
+            normal: IVec3::new(normal[0].signum() as i32, normal[1].signum() as i32, normal[2].signum() as i32),
+            distance: self.buffer.read::<f32>(READBACK_OFFSETS[GBufferAttachment::Distance as usize]),
+        })
+    }
+
+    pub fn destroy(&self, vkcontext: &VkContext) {
+        self.buffer.destroy(vkcontext);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::Voxel;
+
+    fn cube() -> VoxelOctree {
+        let mut octree = VoxelOctree::new(1);
+        octree.set(UVec3::ZERO, Some(Voxel::new(1)));
+
+        octree
+    }
+
+    #[test]
+    fn raycast_instances_picks_the_nearest() {
+        let (near, far) = (cube(), cube());
+        let instances = [(0, &far, Vec3::new(8.0, 0.0, 0.0)), (1, &near, Vec3::new(4.0, 0.0, 0.0))];
+
+        let pick = raycast_instances(instances.into_iter(), Vec3::new(0.0, 0.5, 0.5), Vec3::X, f32::MAX).unwrap();
+
+        assert_eq!(pick.instance, 1);
+        assert_eq!(pick.voxel, UVec3::ZERO);
+        assert_eq!(pick.normal, IVec3::NEG_X);
+        assert!((pick.distance - 4.0).abs() < 2e-3);
+
+        assert_eq!(raycast_instances(instances.into_iter(), Vec3::new(0.0, 0.5, 0.5), Vec3::X, 3.0), None);
+    }
+
+    #[test]
+    fn raycast_instances_breaks_ties_by_id() {
+        let (first, second) = (cube(), cube());
+        let instances = [(2, &first, Vec3::ZERO), (5, &second, Vec3::ZERO)];
+
+        let pick = raycast_instances(instances.into_iter(), Vec3::new(0.5, 4.0, 0.5), Vec3::NEG_Y, f32::MAX).unwrap();
+
+        assert_eq!(pick.instance, 2);
+        assert_eq!(pick.normal, IVec3::Y);
+    }
+}
@@ -1,4 +1,5 @@
 use ash::vk;
+use super::command_buffer::CommandBuffer;
 use super::vkcontext::VkContext;
 
 pub struct Pipeline {
@@ -6,10 +7,51 @@ pub struct Pipeline {
     pub layout: vk::PipelineLayout,
 }
 
+/// How a graphics pipeline's output combines with what the color attachment already holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    Opaque,
+    /// Alpha already multiplied into color, `src + dst * (1 - a)`.
+    PremultipliedAlpha,
+}
+
+impl BlendMode {
+    fn attachment_state(self) -> vk::PipelineColorBlendAttachmentState {
+        let (src_color, dst_color) = match self {
+            BlendMode::Opaque => (vk::BlendFactor::ONE, vk::BlendFactor::ZERO),
+            BlendMode::PremultipliedAlpha => (vk::BlendFactor::ONE, vk::BlendFactor::ONE_MINUS_SRC_ALPHA),
+        };
+
+        vk::PipelineColorBlendAttachmentState::builder()
+            .blend_enable(self != BlendMode::Opaque)
+            .src_color_blend_factor(src_color)
+            .dst_color_blend_factor(dst_color)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ONE)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .color_write_mask(vk::ColorComponentFlags::RGBA)
+            .build()
+    }
+}
+
+/// Fixed function state of a graphics pipeline. Viewport and scissor are dynamic and set while recording.
+pub struct GraphicsPipelineInfo<'a> {
+    /// Vertex and fragment stages.
+    pub stages: &'a [vk::PipelineShaderStageCreateInfo],
+    pub vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    pub vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pub topology: vk::PrimitiveTopology,
+    pub blend_mode: BlendMode,
+    /// Format of the single color attachment it renders into. There is no depth attachment.
+    pub color_format: vk::Format,
+}
+
 impl Pipeline {
     pub fn new_compute(
         vkcontext: &VkContext,
         descriptor_set_layouts: &[vk::DescriptorSetLayout],
+        push_constant_ranges: &[vk::PushConstantRange],
         compute_stage_create_info: vk::PipelineShaderStageCreateInfo,
     ) -> Self {
 
@@ -17,6 +59,7 @@ impl Pipeline {
         let layout = { 
             let create_info = vk::PipelineLayoutCreateInfo::builder()
                 .set_layouts(descriptor_set_layouts)
+                .push_constant_ranges(push_constant_ranges)
                 .build();
 
             unsafe { vkcontext.device.create_pipeline_layout(&create_info, None).unwrap() }
@@ -44,6 +87,115 @@ impl Pipeline {
         }
     }
 
+    /// A pipeline for dynamic rendering, so no render pass objects are needed. See
+    /// [`super::command_buffer::CommandBuffer::begin_rendering`].
+    pub fn new_graphics(
+        vkcontext: &VkContext,
+        descriptor_set_layouts: &[vk::DescriptorSetLayout],
+        push_constant_ranges: &[vk::PushConstantRange],
+        info: &GraphicsPipelineInfo,
+    ) -> Self {
+        let layout = {
+            let create_info = vk::PipelineLayoutCreateInfo::builder()
+                .set_layouts(descriptor_set_layouts)
+                .push_constant_ranges(push_constant_ranges)
+                .build();
+
+            unsafe { vkcontext.device.create_pipeline_layout(&create_info, None).unwrap() }
+        };
+
+        let handle = {
+            let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
+                .vertex_binding_descriptions(info.vertex_bindings)
+                .vertex_attribute_descriptions(info.vertex_attributes)
+                .build();
+
+            let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+                .topology(info.topology)
+                .primitive_restart_enable(false)
+                .build();
+
+            // Counts only, the viewport and scissor themselves are dynamic.
+            let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+                .viewport_count(1)
+                .scissor_count(1)
+                .build();
+
+            let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+                .polygon_mode(vk::PolygonMode::FILL)
+                .cull_mode(vk::CullModeFlags::NONE)
+                .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+                .line_width(1.0)
+                .build();
+
+            let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
+                .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+                .build();
+
+            let blend_attachments = [info.blend_mode.attachment_state()];
+
+            let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+                .attachments(&blend_attachments)
+                .build();
+
+            let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+
+            let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+                .dynamic_states(&dynamic_states)
+                .build();
+
+            let color_formats = [info.color_format];
+
+            let mut rendering_info = vk::PipelineRenderingCreateInfo::builder()
+                .color_attachment_formats(&color_formats)
+                .build();
+
+            let create_info = vk::GraphicsPipelineCreateInfo::builder()
+                .stages(info.stages)
+                .vertex_input_state(&vertex_input_state)
+                .input_assembly_state(&input_assembly_state)
+                .viewport_state(&viewport_state)
+                .rasterization_state(&rasterization_state)
+                .multisample_state(&multisample_state)
+                .color_blend_state(&color_blend_state)
+                .dynamic_state(&dynamic_state)
+                .layout(layout)
+                .push_next(&mut rendering_info)
+                .build();
+
+            let create_infos = [create_info];
+
+            unsafe {
+                vkcontext.device
+                .create_graphics_pipelines(vk::PipelineCache::null(), &create_infos, None)
+                .unwrap()[0]
+            }
+        };
+
+        Self {
+            handle,
+            layout,
+        }
+    }
+
+    /// Pushes `value` as raw bytes at offset 0. `T` must mirror the shader's push constant block.
+    pub fn push_constants<T: Copy>(
+        &self,
+        vkcontext: &VkContext,
+        command_buffer: &CommandBuffer,
+        stage_flags: vk::ShaderStageFlags,
+        value: &T,
+    ) {
+        unsafe {
+            let bytes = std::slice::from_raw_parts(
+                (value as *const T) as *const u8,
+                std::mem::size_of::<T>(),
+            );
+
+            vkcontext.device.cmd_push_constants(command_buffer.handle, self.layout, stage_flags, 0, bytes);
+        }
+    }
+
     pub fn destroy(&self, vkcontext: &VkContext){
         unsafe {
             vkcontext.device.destroy_pipeline_layout(self.layout, None);
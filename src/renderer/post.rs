@@ -0,0 +1,488 @@
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use ash::vk;
+use super::buffer::Buffer;
+use super::command_buffer::CommandBuffer;
+use super::image::Image;
+use super::render_graph::{Access, ImageUse, RenderGraph};
+use super::settings::PostSettings;
+use super::shader::{PostResource, PostShader};
+use super::swapchain::OutputTransfer;
+use super::vkcontext::VkContext;
+use crate::utility;
+
+/// Bloom is blurred at a fraction of the render resolution. Must match `SCALE` in `bloom_extract.comp`
+/// and `bloom_composite.comp`.
+const BLOOM_SCALE: u32 = 4;
+const BLOOM_TARGET_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+/// Format FXAA writes into. Blitted to the swapchain like the display target.
+const FXAA_TARGET_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+/// Largest supported lookup table. The LUT buffer is allocated for this size, so tables can be swapped
+/// without touching descriptors.
+pub const MAX_LUT_SIZE: u32 = 33;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FogPushConstants {
+    density: f32,
+    height_falloff: f32,
+    base_height: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct BloomExtractPushConstants {
+    source_extent: [u32; 2],
+    extent: [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct BloomBlurPushConstants {
+    extent: [u32; 2],
+    direction: [i32; 2],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct BloomCompositePushConstants {
+    extent: [u32; 2],
+    bloom_extent: [u32; 2],
+    intensity: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GradePushConstants {
+    extent: [u32; 2],
+    lut_size: u32,
+    strength: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FxaaPushConstants {
+    extent: [u32; 2],
+    is_linear: u32,
+}
+
+/// A line of a `.cube` file that couldn't be parsed.
+#[derive(Debug)]
+pub struct CubeError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CubeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// A 3D color lookup table applied to display encoded color.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorGradingLut {
+    size: u32,
+    /// Red varies fastest, then green, then blue. The fourth channel is padding.
+    entries: Vec<[f32; 4]>,
+}
+
+impl ColorGradingLut {
+    /// A table that leaves color unchanged.
+    pub fn identity(size: u32) -> Self {
+        let size = size.clamp(2, MAX_LUT_SIZE);
+        let scale = 1.0 / (size - 1) as f32;
+
+        let entries = (0..size * size * size)
+            .map(|i| {
+                let (r, g, b) = (i % size, i / size % size, i / (size * size));
+                [r as f32 * scale, g as f32 * scale, b as f32 * scale, 0.0]
+            })
+            .collect();
+
+        ColorGradingLut { size, entries }
+    }
+
+    /// Loads an Adobe `.cube` file relative to the assets directory.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, CubeError> {
+        let mut source = String::new();
+        utility::fs::load(path).read_to_string(&mut source).expect("LUT file is not valid UTF-8.");
+
+        Self::from_cube(&source)
+    }
+
+    /// Parses the 3D table of an Adobe `.cube` file. Domain bounds other than 0.0 to 1.0 aren't supported.
+    pub fn from_cube(source: &str) -> Result<Self, CubeError> {
+        let mut size = None;
+        let mut entries = Vec::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let error = |message: String| CubeError { line: index + 1, message };
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') || line.starts_with("TITLE") {
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap_or_default();
+
+            match keyword {
+                "LUT_3D_SIZE" => {
+                    let value = words.next()
+                        .and_then(|word| word.parse::<u32>().ok())
+                        .filter(|value| (2..=MAX_LUT_SIZE).contains(value))
+                        .ok_or_else(|| error(format!("LUT_3D_SIZE must be between 2 and {}.", MAX_LUT_SIZE)))?;
+
+                    size = Some(value);
+                }
+                "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                    let expected = if keyword == "DOMAIN_MIN" { 0.0 } else { 1.0 };
+
+                    if !words.all(|word| word.parse::<f32>() == Ok(expected)) {
+                        return Err(error(format!("{} other than {:.1} isn't supported.", keyword, expected)));
+                    }
+                }
+                "LUT_1D_SIZE" => return Err(error(String::from("1D lookup tables aren't supported."))),
+                _ => {
+                    let channels = line.split_whitespace()
+                        .map(|word| word.parse::<f32>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .ok()
+                        .filter(|channels| channels.len() == 3)
+                        .ok_or_else(|| error(format!("Expected three numbers or a keyword, found \"{}\".", line)))?;
+
+                    entries.push([channels[0], channels[1], channels[2], 0.0]);
+                }
+            }
+        }
+
+        let Some(size) = size else {
+            return Err(CubeError { line: 0, message: String::from("LUT_3D_SIZE is missing.") });
+        };
+
+        if entries.len() != (size * size * size) as usize {
+            return Err(CubeError {
+                line: 0,
+                message: format!("Expected {} entries, found {}.", size * size * size, entries.len()),
+            });
+        }
+
+        Ok(ColorGradingLut { size, entries })
+    }
+}
+
+/// Shaders and intermediate images of the post-processing passes.
+pub struct PostStack {
+    fog_shader: PostShader,
+    bloom_extract_shader: PostShader,
+    bloom_blur_shader: PostShader,
+    bloom_composite_shader: PostShader,
+    grade_shader: PostShader,
+    fxaa_shader: PostShader,
+
+    /// Two per frame in flight, blurred back and forth.
+    bloom_targets: Vec<[Image; 2]>,
+    fxaa_targets: Vec<Image>,
+
+    /// Shared by all frames. Only written while the GPU is idle.
+    lut_buffer: Buffer,
+    lut_size: u32,
+}
+
+impl PostStack {
+    pub fn new(vkcontext: &VkContext, frame_count: u32) -> Self {
+        use vk::DescriptorType as Type;
+
+        let count = frame_count;
+
+        let lut = ColorGradingLut::identity(2);
+        let lut_buffer = Buffer::new(
+            vkcontext,
+            (MAX_LUT_SIZE.pow(3) as usize * std::mem::size_of::<[f32; 4]>()) as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+        );
+        lut_buffer.write(0, &lut.entries);
+
+        Self {
+            fog_shader: PostShader::new(
+                vkcontext,
+                "shaders/fog.spv",
+                &[Type::STORAGE_IMAGE, Type::STORAGE_IMAGE, Type::UNIFORM_BUFFER],
+                std::mem::size_of::<FogPushConstants>() as u32,
+                count,
+            ),
+            bloom_extract_shader: PostShader::new(
+                vkcontext,
+                "shaders/bloom_extract.spv",
+                &[Type::STORAGE_IMAGE, Type::STORAGE_IMAGE],
+                std::mem::size_of::<BloomExtractPushConstants>() as u32,
+                count,
+            ),
+            // One set per blur direction.
+            bloom_blur_shader: PostShader::new(
+                vkcontext,
+                "shaders/bloom_blur.spv",
+                &[Type::STORAGE_IMAGE, Type::STORAGE_IMAGE],
+                std::mem::size_of::<BloomBlurPushConstants>() as u32,
+                2 * count,
+            ),
+            bloom_composite_shader: PostShader::new(
+                vkcontext,
+                "shaders/bloom_composite.spv",
+                &[Type::STORAGE_IMAGE, Type::STORAGE_IMAGE],
+                std::mem::size_of::<BloomCompositePushConstants>() as u32,
+                count,
+            ),
+            grade_shader: PostShader::new(
+                vkcontext,
+                "shaders/grade.spv",
+                &[Type::STORAGE_IMAGE, Type::STORAGE_BUFFER],
+                std::mem::size_of::<GradePushConstants>() as u32,
+                count,
+            ),
+            fxaa_shader: PostShader::new(
+                vkcontext,
+                "shaders/fxaa.spv",
+                &[Type::STORAGE_IMAGE, Type::STORAGE_IMAGE],
+                std::mem::size_of::<FxaaPushConstants>() as u32,
+                count,
+            ),
+            bloom_targets: Vec::new(),
+            fxaa_targets: Vec::new(),
+            lut_buffer,
+            lut_size: lut.size,
+        }
+    }
+
+    /// Destroys the shaders and the LUT buffer. The targets are destroyed by their owner, see
+    /// [`PostStack::targets`].
+    pub fn destroy(&mut self, vkcontext: &VkContext) {
+        self.fog_shader.destroy(vkcontext);
+        self.bloom_extract_shader.destroy(vkcontext);
+        self.bloom_blur_shader.destroy(vkcontext);
+        self.bloom_composite_shader.destroy(vkcontext);
+        self.grade_shader.destroy(vkcontext);
+        self.fxaa_shader.destroy(vkcontext);
+        self.lut_buffer.destroy(vkcontext);
+    }
+}
+
+impl PostStack {
+    /// Every image sized to the render extent, for destroying along with the other render targets.
+    pub fn targets(&self) -> impl Iterator<Item = &Image> {
+        self.bloom_targets.iter().flatten().chain(self.fxaa_targets.iter())
+    }
+
+    /// The same images, for binding their transient memory.
+    pub fn targets_mut(&mut self) -> impl Iterator<Item = &mut Image> {
+        self.bloom_targets.iter_mut().flatten().chain(self.fxaa_targets.iter_mut())
+    }
+
+    /// Replaces the targets with ones for `frame_count` frames in flight, of `extent`. The previous ones must
+    /// have been destroyed. The new ones are transient and have no memory until it's bound.
+    pub fn create_targets(&mut self, vkcontext: &VkContext, frame_count: usize, extent: vk::Extent2D) {
+        let bloom_extent = bloom_extent(extent);
+
+        self.bloom_targets = (0..frame_count)
+            .map(|_| [(); 2].map(|_| Image::new_unbound(vkcontext, bloom_extent, BLOOM_TARGET_FORMAT, vk::ImageUsageFlags::STORAGE)))
+            .collect();
+
+        self.fxaa_targets = (0..frame_count)
+            .map(|_| {
+                Image::new_unbound(
+                    vkcontext,
+                    extent,
+                    FXAA_TARGET_FORMAT,
+                    vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC,
+                )
+            })
+            .collect();
+    }
+
+    /// Points the per frame descriptor sets at the images of every frame slot that has targets.
+    pub fn update_descriptors(
+        &self,
+        vkcontext: &VkContext,
+        hdr_targets: &[Image],
+        depth_id_targets: &[Image],
+        emission_targets: &[Image],
+        display_targets: &[Image],
+        environment_buffers: &[Buffer],
+    ) {
+        use PostResource::{StorageBuffer, StorageImage, UniformBuffer};
+
+        for i in 0..hdr_targets.len() {
+            let [bloom_a, bloom_b] = &self.bloom_targets[i];
+
+            self.fog_shader.update_descriptors(
+                vkcontext,
+                i,
+                &[StorageImage(&hdr_targets[i]), StorageImage(&depth_id_targets[i]), UniformBuffer(&environment_buffers[i])],
+            );
+            self.bloom_extract_shader.update_descriptors(vkcontext, i, &[StorageImage(&emission_targets[i]), StorageImage(bloom_a)]);
+            self.bloom_blur_shader.update_descriptors(vkcontext, 2 * i, &[StorageImage(bloom_a), StorageImage(bloom_b)]);
+            self.bloom_blur_shader.update_descriptors(vkcontext, 2 * i + 1, &[StorageImage(bloom_b), StorageImage(bloom_a)]);
+            self.bloom_composite_shader.update_descriptors(vkcontext, i, &[StorageImage(&hdr_targets[i]), StorageImage(bloom_a)]);
+            self.grade_shader.update_descriptors(vkcontext, i, &[StorageImage(&display_targets[i]), StorageBuffer(&self.lut_buffer)]);
+            self.fxaa_shader.update_descriptors(vkcontext, i, &[StorageImage(&display_targets[i]), StorageImage(&self.fxaa_targets[i])]);
+        }
+    }
+
+    /// Uploads `lut`. The GPU must be idle.
+    pub fn set_lut(&mut self, lut: &ColorGradingLut) {
+        self.lut_buffer.write(0, &lut.entries);
+        self.lut_size = lut.size;
+    }
+
+    /// Adds the passes on scene color, before tonemapping.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_hdr_passes<'a>(
+        &'a self,
+        graph: &mut RenderGraph<'a>,
+        settings: &PostSettings,
+        frame_slot: usize,
+        hdr_target: &'a Image,
+        depth_id_target: &'a Image,
+        emission_target: &'a Image,
+        render_extent: vk::Extent2D,
+    ) {
+        let extent = [render_extent.width, render_extent.height];
+
+        if settings.fog {
+            let push_constants = FogPushConstants {
+                density: settings.fog_density,
+                height_falloff: settings.fog_height_falloff,
+                base_height: settings.fog_height,
+            };
+
+            graph.add_pass(
+                "fog",
+                &[Access::image(hdr_target, ImageUse::ComputeReadWrite), Access::image(depth_id_target, ImageUse::ComputeRead)],
+                move |vkcontext, command_buffer| {
+                    self.fog_shader.bind_with(vkcontext, command_buffer, frame_slot, &push_constants);
+                    dispatch(vkcontext, command_buffer, render_extent);
+                },
+            );
+        }
+
+        if settings.bloom {
+            let [bloom_a, bloom_b] = &self.bloom_targets[frame_slot];
+            let bloom_extent = bloom_extent(render_extent);
+            let bloom_size = [bloom_extent.width, bloom_extent.height];
+
+            graph.add_pass(
+                "bloom_extract",
+                &[Access::image(emission_target, ImageUse::ComputeRead), Access::image(bloom_a, ImageUse::ComputeWrite)],
+                move |vkcontext, command_buffer| {
+                    let push_constants = BloomExtractPushConstants { source_extent: extent, extent: bloom_size };
+
+                    self.bloom_extract_shader.bind_with(vkcontext, command_buffer, frame_slot, &push_constants);
+                    dispatch(vkcontext, command_buffer, bloom_extent);
+                },
+            );
+
+            // The blur ends up back in the first target, which the extract pass wrote.
+            let blurs = [
+                ("bloom_blur_horizontal", bloom_a, bloom_b, [1, 0]),
+                ("bloom_blur_vertical", bloom_b, bloom_a, [0, 1]),
+            ];
+
+            for (set_offset, (name, source, destination, direction)) in blurs.into_iter().enumerate() {
+                graph.add_pass(
+                    name,
+                    &[Access::image(source, ImageUse::ComputeRead), Access::image(destination, ImageUse::ComputeWrite)],
+                    move |vkcontext, command_buffer| {
+                        let push_constants = BloomBlurPushConstants { extent: bloom_size, direction };
+
+                        self.bloom_blur_shader.bind_with(vkcontext, command_buffer, 2 * frame_slot + set_offset, &push_constants);
+                        dispatch(vkcontext, command_buffer, bloom_extent);
+                    },
+                );
+            }
+
+            let composite_push_constants = BloomCompositePushConstants {
+                extent,
+                bloom_extent: bloom_size,
+                intensity: settings.bloom_intensity,
+            };
+
+            graph.add_pass(
+                "bloom_composite",
+                &[Access::image(hdr_target, ImageUse::ComputeReadWrite), Access::image(bloom_a, ImageUse::ComputeRead)],
+                move |vkcontext, command_buffer| {
+                    self.bloom_composite_shader.bind_with(vkcontext, command_buffer, frame_slot, &composite_push_constants);
+                    dispatch(vkcontext, command_buffer, render_extent);
+                },
+            );
+        }
+    }
+
+    /// Adds the passes on display color, after tonemapping. Returns the image holding the final color.
+    pub fn add_display_passes<'a>(
+        &'a self,
+        graph: &mut RenderGraph<'a>,
+        settings: &PostSettings,
+        frame_slot: usize,
+        display_target: &'a Image,
+        render_extent: vk::Extent2D,
+        output_transfer: OutputTransfer,
+    ) -> &'a Image {
+        let extent = [render_extent.width, render_extent.height];
+
+        if settings.color_grading {
+            let push_constants = GradePushConstants {
+                extent,
+                lut_size: self.lut_size,
+                strength: settings.color_grading_strength,
+            };
+
+            graph.add_pass(
+                "color_grading",
+                &[Access::image(display_target, ImageUse::ComputeReadWrite)],
+                move |vkcontext, command_buffer| {
+                    self.grade_shader.bind_with(vkcontext, command_buffer, frame_slot, &push_constants);
+                    dispatch(vkcontext, command_buffer, render_extent);
+                },
+            );
+        }
+
+        if !settings.fxaa {
+            return display_target;
+        }
+
+        let fxaa_target = &self.fxaa_targets[frame_slot];
+
+        let push_constants = FxaaPushConstants {
+            extent,
+            is_linear: (output_transfer == OutputTransfer::Linear) as u32,
+        };
+
+        graph.add_pass(
+            "fxaa",
+            &[Access::image(display_target, ImageUse::ComputeRead), Access::image(fxaa_target, ImageUse::ComputeWrite)],
+            move |vkcontext, command_buffer| {
+                self.fxaa_shader.bind_with(vkcontext, command_buffer, frame_slot, &push_constants);
+                dispatch(vkcontext, command_buffer, render_extent);
+            },
+        );
+
+        fxaa_target
+    }
+}
+
+fn bloom_extent(extent: vk::Extent2D) -> vk::Extent2D {
+    vk::Extent2D {
+        width: extent.width.div_ceil(BLOOM_SCALE),
+        height: extent.height.div_ceil(BLOOM_SCALE),
+    }
+}
+
+/// Dispatches one invocation per pixel of `extent`, in the 8 by 8 groups every post shader uses.
+fn dispatch(vkcontext: &VkContext, command_buffer: &CommandBuffer, extent: vk::Extent2D) {
+    unsafe {
+        vkcontext.device.cmd_dispatch(command_buffer.handle, extent.width.div_ceil(8), extent.height.div_ceil(8), 1);
+    }
+}
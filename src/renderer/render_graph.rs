@@ -0,0 +1,731 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use ash::vk;
+use super::command_buffer::CommandBuffer;
+use super::image::Image;
+use super::utility::{color_image_barrier, find_memory_type};
+use super::vkcontext::VkContext;
+
+/// How a pass uses an image. Each use implies the pipeline stage, access and layout the image has to be in.
+///
+/// Reads that share a layout also share a stage, so consecutive reads never need a barrier between them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageUse {
+    ComputeRead,
+    /// Overwrites every texel the pass covers without reading them. Previous contents are discarded.
+    ComputeWrite,
+    ComputeReadWrite,
+    /// Copied or blitted from.
+    TransferRead,
+    /// Cleared or blitted into as a whole. Previous contents are discarded.
+    TransferWrite,
+    /// Sampled by a graphics pipeline's fragment shader.
+    FragmentRead,
+    /// Rendered into by a graphics pipeline, blending with or keeping what was there.
+    ColorAttachment,
+    /// Handed to the presentation engine. Only valid as the final use of an exported image.
+    Present,
+}
+
+impl ImageUse {
+    fn is_write(self) -> bool {
+        matches!(
+            self,
+            ImageUse::ComputeWrite | ImageUse::ComputeReadWrite | ImageUse::TransferWrite | ImageUse::ColorAttachment
+        )
+    }
+
+    fn is_discarding(self) -> bool {
+        matches!(self, ImageUse::ComputeWrite | ImageUse::TransferWrite)
+    }
+
+    fn stage(self) -> vk::PipelineStageFlags {
+        match self {
+            ImageUse::ComputeRead | ImageUse::ComputeWrite | ImageUse::ComputeReadWrite => {
+                vk::PipelineStageFlags::COMPUTE_SHADER
+            }
+            ImageUse::TransferRead | ImageUse::TransferWrite => vk::PipelineStageFlags::TRANSFER,
+            ImageUse::FragmentRead => vk::PipelineStageFlags::FRAGMENT_SHADER,
+            ImageUse::ColorAttachment => vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            ImageUse::Present => vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+        }
+    }
+
+    fn access_flags(self) -> vk::AccessFlags {
+        match self {
+            ImageUse::ComputeRead | ImageUse::FragmentRead => vk::AccessFlags::SHADER_READ,
+            ImageUse::ComputeWrite => vk::AccessFlags::SHADER_WRITE,
+            ImageUse::ComputeReadWrite => vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+            ImageUse::TransferRead => vk::AccessFlags::TRANSFER_READ,
+            ImageUse::TransferWrite => vk::AccessFlags::TRANSFER_WRITE,
+            ImageUse::ColorAttachment => {
+                vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+            }
+            ImageUse::Present => vk::AccessFlags::empty(),
+        }
+    }
+
+    fn layout(self) -> vk::ImageLayout {
+        match self {
+            ImageUse::ComputeRead | ImageUse::ComputeWrite | ImageUse::ComputeReadWrite => vk::ImageLayout::GENERAL,
+            ImageUse::TransferRead => vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            ImageUse::TransferWrite => vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            ImageUse::FragmentRead => vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ImageUse::ColorAttachment => vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ImageUse::Present => vk::ImageLayout::PRESENT_SRC_KHR,
+        }
+    }
+}
+
+/// How a pass uses a buffer. Buffers written by the host before the frame is submitted need no declaring.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferUse {
+    TransferWrite,
+    /// Read back by the host. Only valid as the final use of an exported buffer.
+    HostRead,
+}
+
+impl BufferUse {
+    fn stage(self) -> vk::PipelineStageFlags {
+        match self {
+            BufferUse::TransferWrite => vk::PipelineStageFlags::TRANSFER,
+            BufferUse::HostRead => vk::PipelineStageFlags::HOST,
+        }
+    }
+
+    fn access_flags(self) -> vk::AccessFlags {
+        match self {
+            BufferUse::TransferWrite => vk::AccessFlags::TRANSFER_WRITE,
+            BufferUse::HostRead => vk::AccessFlags::HOST_READ,
+        }
+    }
+}
+
+/// A resource a pass uses, and how.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Image(vk::Image, ImageUse),
+    Buffer(vk::Buffer, BufferUse),
+}
+
+impl Access {
+    pub fn image(image: &Image, image_use: ImageUse) -> Self {
+        Access::Image(image.handle, image_use)
+    }
+
+    fn is_write(self) -> bool {
+        match self {
+            Access::Image(_, image_use) => image_use.is_write(),
+            Access::Buffer(_, buffer_use) => buffer_use == BufferUse::TransferWrite,
+        }
+    }
+
+    /// Reads the resource, or keeps part of what was there before.
+    fn is_read(self) -> bool {
+        match self {
+            Access::Image(_, image_use) => !image_use.is_discarding(),
+            Access::Buffer(..) => false,
+        }
+    }
+
+    fn resource(self) -> Resource {
+        match self {
+            Access::Image(handle, _) => Resource::Image(handle),
+            Access::Buffer(handle, _) => Resource::Buffer(handle),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Resource {
+    Image(vk::Image),
+    Buffer(vk::Buffer),
+}
+
+type RecordFn<'a> = Box<dyn FnOnce(&VkContext, &CommandBuffer) + 'a>;
+
+struct Pass<'a> {
+    name: &'static str,
+    accesses: Vec<Access>,
+    record: RecordFn<'a>,
+}
+
+/// The last use of an image, and the stages that have read it since it was last written.
+#[derive(Clone, Copy)]
+struct ImageState {
+    last_use: Option<ImageUse>,
+    read_stages: vk::PipelineStageFlags,
+}
+
+/// Barriers recorded in one call, before a pass or after the last one.
+#[derive(Default)]
+struct BarrierBatch {
+    src_stages: vk::PipelineStageFlags,
+    dst_stages: vk::PipelineStageFlags,
+    image_barriers: Vec<vk::ImageMemoryBarrier>,
+    buffer_barriers: Vec<vk::BufferMemoryBarrier>,
+}
+
+impl BarrierBatch {
+    fn record(&self, vkcontext: &VkContext, command_buffer: &CommandBuffer) {
+        if self.image_barriers.is_empty() && self.buffer_barriers.is_empty() {
+            return;
+        }
+
+        let src_stages = if self.src_stages.is_empty() { vk::PipelineStageFlags::TOP_OF_PIPE } else { self.src_stages };
+
+        unsafe {
+            vkcontext.device.cmd_pipeline_barrier(
+                command_buffer.handle,
+                src_stages,
+                self.dst_stages,
+                vk::DependencyFlags::empty(),
+                &[],
+                &self.buffer_barriers,
+                &self.image_barriers,
+            );
+        }
+    }
+}
+
+/// A frame as an ordered list of passes and the resources they use. Recording leaves out passes that don't
+/// contribute to an exported resource and inserts the barriers and layout transitions the rest call for, so
+/// passes can be added or left out without touching their neighbours.
+///
+/// Images the graph doesn't know as imported are transient: their contents don't outlive the frame, so
+/// their first use has to discard them, and images that are never alive at the same time can share memory,
+/// see [`TransientMemory`].
+pub struct RenderGraph<'a> {
+    passes: Vec<Pass<'a>>,
+    /// Use of imported images before the graph, `None` if their contents can be discarded.
+    imported_images: HashMap<vk::Image, Option<ImageUse>>,
+    /// Use of exported images after the graph, `None` to leave them as the last pass did.
+    exported_images: HashMap<vk::Image, Option<ImageUse>>,
+    exported_buffers: HashMap<vk::Buffer, BufferUse>,
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self {
+            passes: Vec::new(),
+            imported_images: HashMap::new(),
+            exported_images: HashMap::new(),
+            exported_buffers: HashMap::new(),
+        }
+    }
+
+    /// Declares an image whose contents may come from before the graph, last used as `previous_use`.
+    pub fn import_image(&mut self, image: vk::Image, previous_use: Option<ImageUse>) {
+        self.imported_images.insert(image, previous_use);
+    }
+
+    /// Marks an image as an output of the frame, left in `final_use`. Exported images must be imported.
+    pub fn export_image(&mut self, image: vk::Image, final_use: Option<ImageUse>) {
+        debug_assert!(self.imported_images.contains_key(&image), "Exported images must be imported.");
+
+        self.exported_images.insert(image, final_use);
+    }
+
+    /// Marks a buffer as an output of the frame, made available for `final_use`.
+    pub fn export_buffer(&mut self, buffer: vk::Buffer, final_use: BufferUse) {
+        self.exported_buffers.insert(buffer, final_use);
+    }
+
+    /// Adds a pass after the ones already added. `record` binds, dispatches, copies and so on; the barriers
+    /// before it are taken care of.
+    pub fn add_pass<F>(&mut self, name: &'static str, accesses: &[Access], record: F)
+    where
+        F: FnOnce(&VkContext, &CommandBuffer) + 'a,
+    {
+        debug_assert!(
+            (1..accesses.len()).all(|i| accesses[..i].iter().all(|access| access.resource() != accesses[i].resource())),
+            "Pass {} lists a resource twice.",
+            name,
+        );
+
+        self.passes.push(Pass {
+            name,
+            accesses: accesses.to_vec(),
+            record: Box::new(record),
+        });
+    }
+
+    /// Records the passes that contribute to an exported resource, with their barriers.
+    pub fn record(self, vkcontext: &VkContext, command_buffer: &CommandBuffer) {
+        let live_passes = self.live_passes();
+        let mut batches = self.barrier_batches(&live_passes).into_iter();
+
+        for ((pass, is_live), batch) in self.passes.into_iter().zip(live_passes).zip(&mut batches) {
+            if !is_live {
+                continue;
+            }
+
+            batch.record(vkcontext, command_buffer);
+
+            (pass.record)(vkcontext, command_buffer);
+        }
+
+        batches.next().unwrap().record(vkcontext, command_buffer);
+    }
+
+    /// The barriers before each pass, empty for passes that aren't live, followed by the ones after the
+    /// last pass.
+    fn barrier_batches(&self, live_passes: &[bool]) -> Vec<BarrierBatch> {
+        let mut image_states = self.imported_images.iter()
+            .map(|(image, previous_use)| {
+                let read_stages = match previous_use {
+                    Some(image_use) if !image_use.is_write() => image_use.stage(),
+                    _ => vk::PipelineStageFlags::empty(),
+                };
+
+                (*image, ImageState { last_use: *previous_use, read_stages })
+            })
+            .collect::<HashMap<_, _>>();
+        let mut buffer_states = HashMap::<vk::Buffer, BufferUse>::new();
+
+        let mut batches = Vec::with_capacity(self.passes.len() + 1);
+
+        for (pass, is_live) in self.passes.iter().zip(live_passes) {
+            let mut batch = BarrierBatch::default();
+
+            if !is_live {
+                batches.push(batch);
+                continue;
+            }
+
+            for access in &pass.accesses {
+                match *access {
+                    Access::Image(image, image_use) => {
+                        debug_assert!(
+                            image_states.contains_key(&image) || image_use.is_discarding(),
+                            "Pass {} reads a transient image before it's written.",
+                            pass.name,
+                        );
+
+                        let state = image_states.entry(image).or_insert(ImageState {
+                            last_use: None,
+                            read_stages: vk::PipelineStageFlags::empty(),
+                        });
+
+                        add_image_barrier(&mut batch, image, state, image_use);
+                    }
+                    Access::Buffer(buffer, buffer_use) => {
+                        add_buffer_barrier(&mut batch, buffer, buffer_states.get(&buffer).copied(), buffer_use);
+                        buffer_states.insert(buffer, buffer_use);
+                    }
+                }
+            }
+
+            batches.push(batch);
+        }
+
+        let mut batch = BarrierBatch::default();
+
+        for (image, final_use) in &self.exported_images {
+            if let (Some(final_use), Some(state)) = (final_use, image_states.get_mut(image)) {
+                add_image_barrier(&mut batch, *image, state, *final_use);
+            }
+        }
+
+        for (buffer, final_use) in &self.exported_buffers {
+            if let Some(previous_use) = buffer_states.get(buffer) {
+                add_buffer_barrier(&mut batch, *buffer, Some(*previous_use), *final_use);
+            }
+        }
+
+        batches.push(batch);
+
+        batches
+    }
+
+    /// Whether each pass contributes to an exported resource. Walks the passes backwards from the exports,
+    /// following what each live pass reads.
+    fn live_passes(&self) -> Vec<bool> {
+        let mut needed = self.exported_images.keys().map(|image| Resource::Image(*image))
+            .chain(self.exported_buffers.keys().map(|buffer| Resource::Buffer(*buffer)))
+            .collect::<HashSet<_>>();
+
+        let mut live_passes = vec![false; self.passes.len()];
+
+        for (pass, is_live) in self.passes.iter().zip(live_passes.iter_mut()).rev() {
+            *is_live = pass.accesses.iter().any(|access| access.is_write() && needed.contains(&access.resource()));
+
+            if !*is_live {
+                continue;
+            }
+
+            // Anything the pass overwrites as a whole doesn't need the passes before it.
+            for access in pass.accesses.iter().filter(|access| access.is_write() && !access.is_read()) {
+                needed.remove(&access.resource());
+            }
+
+            for access in pass.accesses.iter().filter(|access| access.is_read()) {
+                needed.insert(access.resource());
+            }
+        }
+
+        live_passes
+    }
+
+    /// Range of live passes using each transient image.
+    fn transient_lifetimes(&self) -> Vec<(vk::Image, Range<usize>)> {
+        let mut lifetimes = Vec::<(vk::Image, Range<usize>)>::new();
+
+        for (index, (pass, is_live)) in self.passes.iter().zip(self.live_passes()).enumerate() {
+            if !is_live {
+                continue;
+            }
+
+            for access in &pass.accesses {
+                let Access::Image(image, _) = *access else { continue };
+
+                if self.imported_images.contains_key(&image) {
+                    continue;
+                }
+
+                match lifetimes.iter_mut().find(|(other, _)| *other == image) {
+                    Some((_, lifetime)) => lifetime.end = index + 1,
+                    None => lifetimes.push((image, index..index + 1)),
+                }
+            }
+        }
+
+        lifetimes
+    }
+}
+
+fn add_image_barrier(batch: &mut BarrierBatch, image: vk::Image, state: &mut ImageState, image_use: ImageUse) {
+    let last_use = state.last_use;
+
+    // Reads after reads in the same layout need no barrier. The stage joins the ones a later write waits for.
+    if let Some(last_use) = last_use {
+        if !last_use.is_write() && !image_use.is_write() && last_use.layout() == image_use.layout() {
+            state.read_stages |= image_use.stage();
+            return;
+        }
+    }
+
+    let (src_stages, src_access_mask) = match last_use {
+        // Transient images may alias memory last written by another image, and imported images may come
+        // straight from the swapchain, whose acquire the frame waits for in the compute stage.
+        None => (
+            vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::TRANSFER_WRITE,
+        ),
+        Some(last_use) if last_use.is_write() => (last_use.stage(), last_use.access_flags()),
+        // Writes after reads, or layout changes, only have to wait for the reads to finish.
+        Some(_) => (state.read_stages, vk::AccessFlags::empty()),
+    };
+
+    let old_layout = match last_use {
+        Some(last_use) if !image_use.is_discarding() => last_use.layout(),
+        _ => vk::ImageLayout::UNDEFINED,
+    };
+
+    batch.src_stages |= src_stages;
+    batch.dst_stages |= image_use.stage();
+    batch.image_barriers.push(color_image_barrier(
+        image,
+        old_layout,
+        image_use.layout(),
+        src_access_mask,
+        image_use.access_flags(),
+    ));
+
+    *state = ImageState {
+        last_use: Some(image_use),
+        read_stages: if image_use.is_write() { vk::PipelineStageFlags::empty() } else { image_use.stage() },
+    };
+}
+
+/// Buffers are only ever written by one pass and read by the host, so the only barrier is the one after a
+/// write.
+fn add_buffer_barrier(batch: &mut BarrierBatch, buffer: vk::Buffer, last_use: Option<BufferUse>, buffer_use: BufferUse) {
+    let Some(last_use @ BufferUse::TransferWrite) = last_use else { return };
+
+    batch.src_stages |= last_use.stage();
+    batch.dst_stages |= buffer_use.stage();
+    batch.buffer_barriers.push(
+        vk::BufferMemoryBarrier::builder()
+            .src_access_mask(last_use.access_flags())
+            .dst_access_mask(buffer_use.access_flags())
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .buffer(buffer)
+            .offset(0)
+            .size(vk::WHOLE_SIZE)
+            .build(),
+    );
+}
+
+/// Memory for the transient images of render graphs. Images that no pass uses at the same time share
+/// memory, so a frame needs little more than its largest set of simultaneously alive images.
+pub struct TransientMemory {
+    blocks: Vec<vk::DeviceMemory>,
+    placements: HashMap<vk::Image, (vk::DeviceMemory, vk::DeviceSize)>,
+}
+
+/// A transient image placed in a block of memory.
+struct Placement {
+    offset: vk::DeviceSize,
+    size: vk::DeviceSize,
+    lifetime: Range<usize>,
+}
+
+impl TransientMemory {
+    pub fn new() -> Self {
+        Self {
+            blocks: Vec::new(),
+            placements: HashMap::new(),
+        }
+    }
+
+    /// Allocates memory for the transient images of `graph`. Graphs recorded into command buffers that may
+    /// be in flight together have to be added separately, so their images never alias each other.
+    ///
+    /// Images only alias when their lifetimes don't overlap in `graph`, so it must contain every pass the
+    /// frames using these images can add. Leaving passes out later only shortens lifetimes.
+    pub fn add_graph(&mut self, vkcontext: &VkContext, graph: &RenderGraph) {
+        let mut groups = HashMap::<u32, Vec<(vk::Image, vk::MemoryRequirements, Range<usize>)>>::new();
+
+        for (image, lifetime) in graph.transient_lifetimes() {
+            let requirements = unsafe { vkcontext.device.get_image_memory_requirements(image) };
+            let memory_type_index = find_memory_type(
+                vkcontext,
+                requirements.memory_type_bits,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            );
+
+            groups.entry(memory_type_index).or_default().push((image, requirements, lifetime));
+        }
+
+        for (memory_type_index, images) in groups {
+            let placements = place_images(images);
+
+            let size = placements.iter().map(|(_, placement)| placement.offset + placement.size).max().unwrap();
+
+            log::debug!(
+                "Allocating transient memory.\n\tImages: {}\n\tSize: {} bytes\n\tUnaliased: {} bytes",
+                placements.len(),
+                size,
+                placements.iter().map(|(_, placement)| placement.size).sum::<vk::DeviceSize>(),
+            );
+
+            let memory = {
+                let allocate_info = vk::MemoryAllocateInfo::builder()
+                    .allocation_size(size)
+                    .memory_type_index(memory_type_index)
+                    .build();
+
+                vkcontext.allocate_memory(&allocate_info)
+            };
+
+            self.blocks.push(memory);
+            self.placements.extend(placements.into_iter().map(|(image, placement)| (image, (memory, placement.offset))));
+        }
+    }
+
+    /// Binds `image` to its place. The image must have been created without memory and be transient in a
+    /// graph added before.
+    pub fn bind(&self, vkcontext: &VkContext, image: &mut Image) {
+        let (memory, offset) = self.placements.get(&image.handle)
+            .expect("Image isn't used by any graph added to the transient memory.");
+
+        image.bind_memory(vkcontext, *memory, *offset);
+    }
+
+    /// Frees the memory. Images bound to it must not be used anymore.
+    pub fn destroy(&mut self, vkcontext: &VkContext) {
+        for memory in self.blocks.drain(..) {
+            vkcontext.free_memory(memory);
+        }
+
+        self.placements.clear();
+    }
+}
+
+/// Places images in one block of memory so that only images whose lifetimes overlap take separate ranges.
+fn place_images(mut images: Vec<(vk::Image, vk::MemoryRequirements, Range<usize>)>) -> Vec<(vk::Image, Placement)> {
+    // Largest first, so the small images fill the gaps the large ones leave.
+    images.sort_by_key(|(_, requirements, _)| std::cmp::Reverse(requirements.size));
+
+    let mut placements = Vec::<(vk::Image, Placement)>::new();
+
+    for (image, requirements, lifetime) in images {
+        let overlapping = placements.iter()
+            .map(|(_, placement)| placement)
+            .filter(|placement| placement.lifetime.start < lifetime.end && lifetime.start < placement.lifetime.end)
+            .collect::<Vec<_>>();
+
+        let offset = std::iter::once(0)
+            .chain(overlapping.iter().map(|placement| placement.offset + placement.size))
+            .map(|offset| offset.next_multiple_of(requirements.alignment))
+            .filter(|offset| {
+                overlapping.iter().all(|placement| {
+                    offset + requirements.size <= placement.offset || placement.offset + placement.size <= *offset
+                })
+            })
+            .min()
+            .unwrap();
+
+        placements.push((image, Placement { offset, size: requirements.size, lifetime }));
+    }
+
+    placements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::vk::Handle;
+
+    fn image(handle: u64) -> vk::Image {
+        vk::Image::from_raw(handle)
+    }
+
+    fn requirements(size: vk::DeviceSize) -> vk::MemoryRequirements {
+        vk::MemoryRequirements { size, alignment: 256, memory_type_bits: 1 }
+    }
+
+    #[test]
+    fn passes_whose_outputs_nobody_reads_are_culled() {
+        let (output, scratch, unused) = (image(1), image(2), image(3));
+        let mut graph = RenderGraph::new();
+        graph.import_image(output, None);
+        graph.export_image(output, Some(ImageUse::Present));
+
+        graph.add_pass("scratch", &[Access::Image(scratch, ImageUse::ComputeWrite)], |_, _| {});
+        graph.add_pass("unused", &[Access::Image(unused, ImageUse::ComputeWrite)], |_, _| {});
+        graph.add_pass(
+            "output",
+            &[Access::Image(scratch, ImageUse::ComputeRead), Access::Image(output, ImageUse::ComputeWrite)],
+            |_, _| {},
+        );
+        graph.add_pass("overwritten", &[Access::Image(output, ImageUse::ComputeWrite)], |_, _| {});
+        graph.add_pass("last", &[Access::Image(output, ImageUse::TransferWrite)], |_, _| {});
+
+        assert_eq!(graph.live_passes(), [false, false, false, false, true]);
+
+        let mut graph = RenderGraph::new();
+        graph.import_image(output, None);
+        graph.export_image(output, Some(ImageUse::Present));
+
+        graph.add_pass("scratch", &[Access::Image(scratch, ImageUse::ComputeWrite)], |_, _| {});
+        graph.add_pass("unused", &[Access::Image(unused, ImageUse::ComputeWrite)], |_, _| {});
+        graph.add_pass(
+            "output",
+            &[Access::Image(scratch, ImageUse::ComputeRead), Access::Image(output, ImageUse::ComputeWrite)],
+            |_, _| {},
+        );
+
+        assert_eq!(graph.live_passes(), [true, false, true]);
+    }
+
+    #[test]
+    fn reads_after_writes_wait_for_the_write_in_the_read_layout() {
+        let (output, scratch) = (image(1), image(2));
+        let mut graph = RenderGraph::new();
+        graph.import_image(output, None);
+        graph.export_image(output, Some(ImageUse::Present));
+
+        graph.add_pass("scratch", &[Access::Image(scratch, ImageUse::ComputeWrite)], |_, _| {});
+        graph.add_pass(
+            "output",
+            &[Access::Image(scratch, ImageUse::FragmentRead), Access::Image(output, ImageUse::ColorAttachment)],
+            |_, _| {},
+        );
+
+        let live_passes = graph.live_passes();
+        let batches = graph.barrier_batches(&live_passes);
+        assert_eq!(batches.len(), 3);
+
+        let read = &batches[1];
+        let barrier = read.image_barriers.iter().find(|barrier| barrier.image == scratch).unwrap();
+        assert_eq!(barrier.old_layout, vk::ImageLayout::GENERAL);
+        assert_eq!(barrier.new_layout, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        assert_eq!(barrier.src_access_mask, vk::AccessFlags::SHADER_WRITE);
+        assert_eq!(barrier.dst_access_mask, vk::AccessFlags::SHADER_READ);
+        assert!(read.src_stages.contains(vk::PipelineStageFlags::COMPUTE_SHADER));
+        assert!(read.dst_stages.contains(vk::PipelineStageFlags::FRAGMENT_SHADER));
+
+        let present = &batches[2];
+        assert_eq!(present.image_barriers.len(), 1);
+        assert_eq!(present.image_barriers[0].image, output);
+        assert_eq!(present.image_barriers[0].old_layout, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+        assert_eq!(present.image_barriers[0].new_layout, vk::ImageLayout::PRESENT_SRC_KHR);
+    }
+
+    #[test]
+    fn reads_after_reads_need_no_barrier() {
+        let (output, input) = (image(1), image(2));
+        let mut graph = RenderGraph::new();
+        graph.import_image(input, Some(ImageUse::ComputeRead));
+        graph.import_image(output, None);
+        graph.export_image(output, None);
+
+        graph.add_pass(
+            "first",
+            &[Access::Image(input, ImageUse::ComputeRead), Access::Image(output, ImageUse::ComputeReadWrite)],
+            |_, _| {},
+        );
+        graph.add_pass(
+            "second",
+            &[Access::Image(input, ImageUse::ComputeRead), Access::Image(output, ImageUse::ComputeReadWrite)],
+            |_, _| {},
+        );
+
+        let live_passes = graph.live_passes();
+        let batches = graph.barrier_batches(&live_passes);
+
+        assert!(batches.iter().flat_map(|batch| &batch.image_barriers).all(|barrier| barrier.image != input));
+    }
+
+    #[test]
+    fn transient_lifetimes_span_their_live_passes() {
+        let (output, first, second) = (image(1), image(2), image(3));
+        let mut graph = RenderGraph::new();
+        graph.import_image(output, None);
+        graph.export_image(output, None);
+
+        graph.add_pass("first", &[Access::Image(first, ImageUse::ComputeWrite)], |_, _| {});
+        graph.add_pass(
+            "second",
+            &[Access::Image(first, ImageUse::ComputeRead), Access::Image(second, ImageUse::ComputeWrite)],
+            |_, _| {},
+        );
+        graph.add_pass(
+            "output",
+            &[Access::Image(second, ImageUse::ComputeRead), Access::Image(output, ImageUse::ComputeWrite)],
+            |_, _| {},
+        );
+
+        assert_eq!(graph.transient_lifetimes(), [(first, 0..2), (second, 1..3)]);
+    }
+
+    #[test]
+    fn images_alive_at_different_times_share_memory() {
+        let placements = place_images(vec![
+            (image(1), requirements(1024), 0..2),
+            (image(2), requirements(512), 2..4),
+        ]);
+
+        assert!(placements.iter().all(|(_, placement)| placement.offset == 0));
+    }
+
+    #[test]
+    fn images_alive_at_the_same_time_dont_share_memory() {
+        let placements = place_images(vec![
+            (image(1), requirements(1000), 0..2),
+            (image(2), requirements(512), 1..3),
+            (image(3), requirements(512), 2..4),
+        ]);
+
+        let placement = |handle| &placements.iter().find(|(image, _)| image.as_raw() == handle).unwrap().1;
+
+        assert_eq!(placement(1).offset, 0);
+        // Aligned past the first image, which it overlaps.
+        assert_eq!(placement(2).offset, 1024);
+        // Only overlaps the second, so it takes the first's place.
+        assert_eq!(placement(3).offset, 0);
+    }
+}
@@ -0,0 +1,233 @@
+use ash::vk;
+use serde::{Deserialize, Serialize};
+
+/// Upper bound for [`RenderSettings::frames_in_flight`].
+pub const MAX_FRAMES_IN_FLIGHT: u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VsyncMode {
+    /// Tearing, uncapped frame rate.
+    Off,
+    /// No tearing, uncapped frame rate. Newer frames replace queued ones.
+    Mailbox,
+    /// Frame rate capped to the refresh rate.
+    On,
+    /// Like `On`, but late frames are presented immediately instead of waiting for the next refresh.
+    Adaptive,
+}
+
+impl VsyncMode {
+    /// Present modes in order of preference. FIFO is last since it is the only mode the spec guarantees.
+    pub fn preferred_present_modes(&self) -> &'static [vk::PresentModeKHR] {
+        match self {
+            VsyncMode::Off => &[vk::PresentModeKHR::IMMEDIATE, vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::FIFO],
+            VsyncMode::Mailbox => &[vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::IMMEDIATE, vk::PresentModeKHR::FIFO],
+            VsyncMode::On => &[vk::PresentModeKHR::FIFO],
+            VsyncMode::Adaptive => &[vk::PresentModeKHR::FIFO_RELAXED, vk::PresentModeKHR::FIFO],
+        }
+    }
+}
+
+/// Bounds for the render resolution scale, relative to the swapchain extent.
+pub const MIN_RENDER_SCALE: f32 = 0.5;
+pub const MAX_RENDER_SCALE: f32 = 2.0;
+/// Frame time dynamic render scaling aims for unless told otherwise, in microseconds. About 60 frames per
+/// second.
+pub const DEFAULT_TARGET_FRAME_TIME: u128 = 16_700;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderScale {
+    Fixed(f32),
+    /// Adjusts the scale between `min` and `max` to keep the frame time, in microseconds, near the target.
+    Dynamic {
+        target_frame_time: u128,
+        min: f32,
+        max: f32,
+    },
+}
+
+impl RenderScale {
+    /// The largest scale this mode can render at. Render targets are allocated at this scale.
+    pub fn max_scale(&self) -> f32 {
+        match self {
+            RenderScale::Fixed(scale) => *scale,
+            RenderScale::Dynamic { min, max, .. } => max.max(*min),
+        }
+        .clamp(MIN_RENDER_SCALE, MAX_RENDER_SCALE)
+    }
+
+    pub fn min_scale(&self) -> f32 {
+        match self {
+            RenderScale::Fixed(scale) => *scale,
+            RenderScale::Dynamic { min, .. } => *min,
+        }
+        .clamp(MIN_RENDER_SCALE, self.max_scale())
+    }
+}
+
+/// Filter used when blitting the render target onto the swapchain image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpscaleFilter {
+    Nearest,
+    Bilinear,
+}
+
+impl UpscaleFilter {
+    pub fn to_vk(self) -> vk::Filter {
+        match self {
+            UpscaleFilter::Nearest => vk::Filter::NEAREST,
+            UpscaleFilter::Bilinear => vk::Filter::LINEAR,
+        }
+    }
+}
+
+/// Curve used to map HDR scene color to the displayable range. Values match `tonemap.comp`.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tonemapper {
+    Reinhard = 0,
+    Aces = 1,
+}
+
+/// What the voxel pass shows in place of shaded color, for inspecting octree traversal. Values match
+/// `voxel.comp`.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DebugView {
+    #[default]
+    Off = 0,
+    /// Octree cells the primary ray stepped through, as a heat map. Misses show too, where no instance
+    /// was hit.
+    StepCount = 1,
+    /// Octree level of the leaf that was hit. Shallower leaves are merged, uniform regions.
+    OctreeDepth = 2,
+    /// Edges of the leaf that was hit, over flat shading.
+    NodeBounds = 3,
+    Normals = 4,
+    InstanceId = 5,
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 6] = [
+        DebugView::Off,
+        DebugView::StepCount,
+        DebugView::OctreeDepth,
+        DebugView::NodeBounds,
+        DebugView::Normals,
+        DebugView::InstanceId,
+    ];
+
+    /// The view after this one, wrapping around to `Off`.
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+}
+
+/// Toggles and parameters of the post-processing passes. Fog and bloom work on scene color before
+/// tonemapping; grading and FXAA on display color after it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PostSettings {
+    pub fog: bool,
+    /// Extinction per voxel at `fog_height`.
+    pub fog_density: f32,
+    /// How quickly fog thins out above `fog_height`, per voxel.
+    pub fog_height_falloff: f32,
+    pub fog_height: f32,
+    /// Spreads the light of emissive voxels around them.
+    pub bloom: bool,
+    pub bloom_intensity: f32,
+    /// Applies the lookup table set with [`super::Renderer::set_color_grading_lut`].
+    pub color_grading: bool,
+    /// Blend between the ungraded and graded color.
+    pub color_grading_strength: f32,
+    pub fxaa: bool,
+}
+
+impl Default for PostSettings {
+    fn default() -> Self {
+        PostSettings {
+            fog: true,
+            fog_density: 0.01,
+            fog_height_falloff: 0.1,
+            fog_height: 0.0,
+            bloom: true,
+            bloom_intensity: 0.5,
+            color_grading: true,
+            color_grading_strength: 1.0,
+            fxaa: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderSettings {
+    pub vsync: VsyncMode,
+    /// Number of frames the CPU may record ahead of the GPU. Clamped to `1..=MAX_FRAMES_IN_FLIGHT`.
+    pub frames_in_flight: u32,
+    /// Caps the frame rate by sleeping before each frame. `None` disables the limiter.
+    pub frame_rate_limit: Option<u32>,
+    pub render_scale: RenderScale,
+    pub upscale_filter: UpscaleFilter,
+    pub tonemapper: Tonemapper,
+    pub exposure: f32,
+    /// Use an HDR10 swapchain when the surface supports one.
+    pub hdr_output: bool,
+    /// Luminance of scene value 1.0 in nits, when outputting HDR.
+    pub hdr_paper_white: f32,
+    /// Sample and radius are set through [`super::Environment`].
+    pub ambient_occlusion: bool,
+    /// Weight of the newest frame when blending it with reprojected history. 1.0 disables temporal
+    /// accumulation.
+    pub temporal_blend: f32,
+    /// Whether the voxel pass writes distance, normal, instance id and voxel coordinate per pixel, for
+    /// picking and post effects.
+    pub gbuffer: bool,
+    pub post: PostSettings,
+    /// Replaces shading with a debug view. Post-processing and temporal accumulation are skipped while
+    /// it is on.
+    pub debug_view: DebugView,
+    /// Most voxel instances that can be added. Only read when the renderer is created.
+    pub max_instance_count: u32,
+    /// Whether the Vulkan validation layer is loaded, if it's installed. Only read when the renderer is
+    /// created.
+    pub validation: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            vsync: VsyncMode::Mailbox,
+            frames_in_flight: 2,
+            frame_rate_limit: None,
+            render_scale: RenderScale::Fixed(1.0),
+            upscale_filter: UpscaleFilter::Bilinear,
+            tonemapper: Tonemapper::Aces,
+            exposure: 1.0,
+            hdr_output: true,
+            hdr_paper_white: 200.0,
+            ambient_occlusion: true,
+            temporal_blend: 0.1,
+            gbuffer: true,
+            post: PostSettings::default(),
+            debug_view: DebugView::Off,
+            max_instance_count: 1000,
+            validation: cfg!(debug_assertions),
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn frames_in_flight(&self) -> u32 {
+        self.frames_in_flight.clamp(1, MAX_FRAMES_IN_FLIGHT)
+    }
+
+    /// Target frame time of the limiter, in microseconds.
+    pub fn frame_time_limit(&self) -> Option<u128> {
+        self.frame_rate_limit
+            .filter(|limit| *limit > 0)
+            .map(|limit| 1_000_000 / limit as u128)
+    }
+}
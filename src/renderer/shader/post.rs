@@ -0,0 +1,219 @@
+use ash::vk;
+use super::ShaderStage;
+use crate::renderer::{
+    buffer::Buffer,
+    command_buffer::CommandBuffer,
+    image::Image,
+    pipeline::Pipeline,
+    vkcontext::VkContext,
+};
+
+/// A resource bound to a post-processing shader, in binding order.
+#[derive(Clone, Copy)]
+pub enum PostResource<'a> {
+    StorageImage(&'a Image),
+    UniformBuffer(&'a Buffer),
+    StorageBuffer(&'a Buffer),
+}
+
+impl PostResource<'_> {
+    fn descriptor_type(&self) -> vk::DescriptorType {
+        match self {
+            PostResource::StorageImage(_) => vk::DescriptorType::STORAGE_IMAGE,
+            PostResource::UniformBuffer(_) => vk::DescriptorType::UNIFORM_BUFFER,
+            PostResource::StorageBuffer(_) => vk::DescriptorType::STORAGE_BUFFER,
+        }
+    }
+}
+
+/// A compute shader with a single descriptor set layout, a number of sets of it and an optional push
+/// constant block. Every post-processing pass has this shape, so they share it instead of each having
+/// their own shader type.
+pub struct PostShader {
+    sets: Vec<vk::DescriptorSet>,
+    descriptor_types: Vec<vk::DescriptorType>,
+
+    set_layout: vk::DescriptorSetLayout,
+
+    descriptor_pool: vk::DescriptorPool,
+
+    pipeline: Pipeline,
+}
+
+impl PostShader {
+    /// `descriptor_types` lists the bindings in order. `push_constant_size` is in bytes, zero for none.
+    pub fn new<P: AsRef<std::path::Path>>(
+        vkcontext: &VkContext,
+        path: P,
+        descriptor_types: &[vk::DescriptorType],
+        push_constant_size: u32,
+        set_count: u32,
+    ) -> Self {
+        let stage = ShaderStage::new(vkcontext, path, vk::ShaderStageFlags::COMPUTE);
+
+        let set_layout = {
+            let bindings = descriptor_types.iter().enumerate()
+                .map(|(binding, descriptor_type)| {
+                    vk::DescriptorSetLayoutBinding::builder()
+                        .binding(binding as u32)
+                        .descriptor_type(*descriptor_type)
+                        .descriptor_count(1)
+                        .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                        .build()
+                })
+                .collect::<Vec<_>>();
+
+            let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+                .bindings(&bindings)
+                .build();
+
+            unsafe { vkcontext.device.create_descriptor_set_layout(&create_info, None).unwrap() }
+        };
+
+        let descriptor_pool = {
+            let sizes = descriptor_types.iter()
+                .map(|descriptor_type| {
+                    vk::DescriptorPoolSize {
+                        ty: *descriptor_type,
+                        descriptor_count: set_count,
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            let create_info = vk::DescriptorPoolCreateInfo::builder()
+                .max_sets(set_count)
+                .pool_sizes(&sizes)
+                .build();
+
+            unsafe { vkcontext.device.create_descriptor_pool(&create_info, None).unwrap() }
+        };
+
+        let sets = {
+            let set_layouts = vec![set_layout; set_count as usize];
+
+            let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(descriptor_pool)
+                .set_layouts(&set_layouts)
+                .build();
+
+            unsafe { vkcontext.device.allocate_descriptor_sets(&allocate_info).unwrap() }
+        };
+
+        let push_constant_ranges = [
+            vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::COMPUTE,
+                offset: 0,
+                size: push_constant_size,
+            },
+        ];
+
+        let pipeline = Pipeline::new_compute(
+            vkcontext,
+            &[set_layout],
+            if push_constant_size > 0 { &push_constant_ranges } else { &[] },
+            stage.shader_stage_create_info,
+        );
+
+        stage.destroy(vkcontext);
+
+        Self {
+            sets,
+            descriptor_types: descriptor_types.to_vec(),
+            set_layout,
+            descriptor_pool,
+            pipeline,
+        }
+    }
+
+    pub fn destroy(&mut self, vkcontext: &VkContext) {
+        unsafe {
+            self.pipeline.destroy(vkcontext);
+
+            vkcontext.device.destroy_descriptor_pool(self.descriptor_pool, None);
+
+            vkcontext.device.destroy_descriptor_set_layout(self.set_layout, None);
+        }
+    }
+}
+
+impl PostShader {
+    pub fn bind(&self, vkcontext: &VkContext, command_buffer: &CommandBuffer, set_index: usize) {
+        unsafe {
+            vkcontext.device.cmd_bind_pipeline(
+                command_buffer.handle,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline.handle,
+            );
+
+            vkcontext.device.cmd_bind_descriptor_sets(
+                command_buffer.handle,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline.layout,
+                0,
+                &self.sets[set_index..=set_index],
+                &[],
+            );
+        }
+    }
+
+    /// Binds set `set_index` and pushes `push_constants`, which must mirror the shader's push constant block.
+    pub fn bind_with<T: Copy>(
+        &self,
+        vkcontext: &VkContext,
+        command_buffer: &CommandBuffer,
+        set_index: usize,
+        push_constants: &T,
+    ) {
+        self.bind(vkcontext, command_buffer, set_index);
+        self.pipeline.push_constants(vkcontext, command_buffer, vk::ShaderStageFlags::COMPUTE, push_constants);
+    }
+
+    /// Points every binding of set `set_index` at `resources`, which must match the shader's bindings.
+    pub fn update_descriptors(&self, vkcontext: &VkContext, set_index: usize, resources: &[PostResource]) {
+        assert!(
+            resources.iter().map(PostResource::descriptor_type).eq(self.descriptor_types.iter().copied()),
+            "Post shader resources don't match its bindings.",
+        );
+
+        let image_infos = resources.iter()
+            .map(|resource| match resource {
+                PostResource::StorageImage(image) => {
+                    vk::DescriptorImageInfo::builder()
+                        .image_layout(vk::ImageLayout::GENERAL)
+                        .image_view(image.view)
+                        .build()
+                }
+                _ => vk::DescriptorImageInfo::default(),
+            })
+            .collect::<Vec<_>>();
+
+        let buffer_infos = resources.iter()
+            .map(|resource| match resource {
+                PostResource::UniformBuffer(buffer) | PostResource::StorageBuffer(buffer) => {
+                    vk::DescriptorBufferInfo::builder()
+                        .buffer(buffer.handle)
+                        .offset(0)
+                        .range(vk::WHOLE_SIZE)
+                        .build()
+                }
+                _ => vk::DescriptorBufferInfo::default(),
+            })
+            .collect::<Vec<_>>();
+
+        let write_ops = resources.iter().enumerate()
+            .map(|(binding, resource)| {
+                let builder = vk::WriteDescriptorSet::builder()
+                    .dst_set(self.sets[set_index])
+                    .dst_binding(binding as u32)
+                    .descriptor_type(resource.descriptor_type());
+
+                match resource {
+                    PostResource::StorageImage(_) => builder.image_info(&image_infos[binding..=binding]).build(),
+                    _ => builder.buffer_info(&buffer_infos[binding..=binding]).build(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        unsafe { vkcontext.device.update_descriptor_sets(&write_ops, &[]); }
+    }
+}
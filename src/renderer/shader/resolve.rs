@@ -0,0 +1,266 @@
+use ash::vk;
+use super::ShaderStage;
+use crate::renderer::{
+    buffer::Buffer,
+    command_buffer::CommandBuffer,
+    image::Image,
+    pipeline::Pipeline,
+    vkcontext::VkContext,
+};
+
+/// Blends the voxel pass output with last frame's reprojected result, rejecting history that belongs to
+/// a different surface.
+pub struct ResolveShader {
+    /// One per frame slot, for this frame's color, depth and id, and environment.
+    frame_sets: Vec<vk::DescriptorSet>,
+    /// One per frame parity. Set `i` reads history image `i` and writes the other one.
+    history_sets: Vec<vk::DescriptorSet>,
+
+    frame_set_layout: vk::DescriptorSetLayout,
+    history_set_layout: vk::DescriptorSetLayout,
+
+    descriptor_pool: vk::DescriptorPool,
+
+    pipeline: Pipeline,
+}
+
+impl ResolveShader {
+    pub fn new(vkcontext: &VkContext, frame_count: u32) -> Self {
+        let stage = ShaderStage::new(vkcontext, "shaders/resolve.spv", vk::ShaderStageFlags::COMPUTE);
+
+        let frame_set_layout = {
+            let bindings = [
+                // Color buffer binding.
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(0)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+                // Depth and surface id buffer binding.
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(1)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+                // Environment buffer binding.
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(2)
+                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+            ];
+
+            let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+                .bindings(&bindings)
+                .build();
+
+            unsafe { vkcontext.device.create_descriptor_set_layout(&create_info, None).unwrap() }
+        };
+
+        // History color and depth and id to read, then to write.
+        let history_set_layout = {
+            let bindings = (0..4)
+                .map(|binding| {
+                    vk::DescriptorSetLayoutBinding::builder()
+                        .binding(binding)
+                        .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                        .descriptor_count(1)
+                        .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                        .build()
+                })
+                .collect::<Vec<_>>();
+
+            let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+                .bindings(&bindings)
+                .build();
+
+            unsafe { vkcontext.device.create_descriptor_set_layout(&create_info, None).unwrap() }
+        };
+
+        let descriptor_pool = {
+            let sizes = [
+                vk::DescriptorPoolSize {
+                    ty: vk::DescriptorType::STORAGE_IMAGE,
+                    descriptor_count: 2 * frame_count + 2 * 4,
+                },
+                vk::DescriptorPoolSize {
+                    ty: vk::DescriptorType::UNIFORM_BUFFER,
+                    descriptor_count: frame_count,
+                },
+            ];
+
+            let create_info = vk::DescriptorPoolCreateInfo::builder()
+                .max_sets(frame_count + 2)
+                .pool_sizes(&sizes)
+                .build();
+
+            unsafe { vkcontext.device.create_descriptor_pool(&create_info, None).unwrap() }
+        };
+
+        let allocate_sets = |layout: vk::DescriptorSetLayout, count: usize| {
+            let set_layouts = vec![layout; count];
+
+            let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(descriptor_pool)
+                .set_layouts(&set_layouts)
+                .build();
+
+            unsafe { vkcontext.device.allocate_descriptor_sets(&allocate_info).unwrap() }
+        };
+
+        let frame_sets = allocate_sets(frame_set_layout, frame_count as usize);
+        let history_sets = allocate_sets(history_set_layout, 2);
+
+        let pipeline = Pipeline::new_compute(
+            vkcontext,
+            &[frame_set_layout, history_set_layout],
+            &[],
+            stage.shader_stage_create_info,
+        );
+
+        stage.destroy(vkcontext);
+
+        Self {
+            frame_sets,
+            history_sets,
+            frame_set_layout,
+            history_set_layout,
+            descriptor_pool,
+            pipeline,
+        }
+    }
+
+    pub fn destroy(&mut self, vkcontext: &VkContext) {
+        unsafe {
+            self.pipeline.destroy(vkcontext);
+
+            vkcontext.device.destroy_descriptor_pool(self.descriptor_pool, None);
+
+            vkcontext.device.destroy_descriptor_set_layout(self.frame_set_layout, None);
+            vkcontext.device.destroy_descriptor_set_layout(self.history_set_layout, None);
+        }
+    }
+}
+
+impl ResolveShader {
+    /// Binds the pipeline for the frame rendering into `frame_slot`. `history_index` is the history
+    /// image the previous frame wrote.
+    pub fn bind(&self, vkcontext: &VkContext, command_buffer: &CommandBuffer, frame_slot: u32, history_index: usize) {
+        unsafe {
+            vkcontext.device.cmd_bind_pipeline(
+                command_buffer.handle,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline.handle,
+            );
+
+            let frame_slot = frame_slot as usize;
+            let sets = [self.frame_sets[frame_slot], self.history_sets[history_index]];
+
+            vkcontext.device.cmd_bind_descriptor_sets(
+                command_buffer.handle,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline.layout,
+                0,
+                &sets,
+                &[],
+            );
+        }
+    }
+
+    pub fn update_frame_descriptors(
+        &self,
+        vkcontext: &VkContext,
+        color_buffers: &[Image],
+        depth_id_buffers: &[Image],
+        environment_buffers: &[Buffer],
+    ) {
+        let image_infos = color_buffers.iter().zip(depth_id_buffers.iter())
+            .map(|(color_buffer, depth_id_buffer)| {
+                [
+                    vk::DescriptorImageInfo::builder()
+                        .image_layout(vk::ImageLayout::GENERAL)
+                        .image_view(color_buffer.view)
+                        .build(),
+                    vk::DescriptorImageInfo::builder()
+                        .image_layout(vk::ImageLayout::GENERAL)
+                        .image_view(depth_id_buffer.view)
+                        .build(),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let buffer_infos = environment_buffers.iter()
+            .map(|buffer| {
+                vk::DescriptorBufferInfo::builder()
+                    .buffer(buffer.handle)
+                    .offset(0)
+                    .range(vk::WHOLE_SIZE)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        let write_ops = self.frame_sets.iter().zip(image_infos.iter()).zip(buffer_infos.iter())
+            .flat_map(|((set, image_infos), buffer_info)| {
+                [
+                    vk::WriteDescriptorSet::builder()
+                        .dst_set(*set)
+                        .dst_binding(0)
+                        .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                        .image_info(image_infos)
+                        .build(),
+                    vk::WriteDescriptorSet::builder()
+                        .dst_set(*set)
+                        .dst_binding(2)
+                        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                        .buffer_info(std::slice::from_ref(buffer_info))
+                        .build(),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        unsafe { vkcontext.device.update_descriptor_sets(&write_ops, &[]); }
+    }
+
+    pub fn update_history_descriptors(
+        &self,
+        vkcontext: &VkContext,
+        history_colors: &[Image; 2],
+        history_depth_ids: &[Image; 2],
+    ) {
+        let image_info = |image: &Image| {
+            vk::DescriptorImageInfo::builder()
+                .image_layout(vk::ImageLayout::GENERAL)
+                .image_view(image.view)
+                .build()
+        };
+
+        let image_infos = (0..2)
+            .map(|read| {
+                let write = read ^ 1;
+
+                [
+                    image_info(&history_colors[read]),
+                    image_info(&history_depth_ids[read]),
+                    image_info(&history_colors[write]),
+                    image_info(&history_depth_ids[write]),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let write_ops = self.history_sets.iter().zip(image_infos.iter())
+            .map(|(set, infos)| {
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(*set)
+                    .dst_binding(0)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .image_info(infos)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        unsafe { vkcontext.device.update_descriptor_sets(&write_ops, &[]); }
+    }
+}
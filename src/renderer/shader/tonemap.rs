@@ -0,0 +1,177 @@
+use ash::vk;
+use super::ShaderStage;
+use crate::renderer::{command_buffer::CommandBuffer, image::Image, pipeline::Pipeline, vkcontext::VkContext};
+
+/// Mirrors the `TonemapParameters` push constant block in `tonemap.comp`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct TonemapPushConstants {
+    pub extent: [u32; 2],
+    pub exposure: f32,
+    pub tonemapper: u32,
+    pub output_transfer: u32,
+    pub paper_white: f32,
+}
+
+/// Maps the HDR output of the voxel pass to the swapchain's color space.
+pub struct TonemapShader {
+    sets: Vec<vk::DescriptorSet>,
+
+    set_layout: vk::DescriptorSetLayout,
+
+    descriptor_pool: vk::DescriptorPool,
+
+    pipeline: Pipeline,
+}
+
+impl TonemapShader {
+    pub fn new(vkcontext: &VkContext, frame_count: u32) -> Self {
+        let stage = ShaderStage::new(vkcontext, "shaders/tonemap.spv", vk::ShaderStageFlags::COMPUTE);
+
+        let set_layout = {
+            let bindings = [
+                // HDR buffer binding.
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(0)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+                // Output buffer binding.
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(1)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+            ];
+
+            let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+                .bindings(&bindings)
+                .build();
+
+            unsafe { vkcontext.device.create_descriptor_set_layout(&create_info, None).unwrap() }
+        };
+
+        let descriptor_pool = {
+            let sizes = [
+                vk::DescriptorPoolSize {
+                    ty: vk::DescriptorType::STORAGE_IMAGE,
+                    descriptor_count: 2 * frame_count,
+                },
+            ];
+
+            let create_info = vk::DescriptorPoolCreateInfo::builder()
+                .max_sets(frame_count)
+                .pool_sizes(&sizes)
+                .build();
+
+            unsafe { vkcontext.device.create_descriptor_pool(&create_info, None).unwrap() }
+        };
+
+        let sets = {
+            let set_layouts = vec![set_layout; frame_count as usize];
+
+            let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(descriptor_pool)
+                .set_layouts(&set_layouts)
+                .build();
+
+            unsafe { vkcontext.device.allocate_descriptor_sets(&allocate_info).unwrap() }
+        };
+
+        let push_constant_ranges = [
+            vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::COMPUTE,
+                offset: 0,
+                size: std::mem::size_of::<TonemapPushConstants>() as u32,
+            },
+        ];
+
+        let pipeline = Pipeline::new_compute(
+            vkcontext,
+            &[set_layout],
+            &push_constant_ranges,
+            stage.shader_stage_create_info,
+        );
+
+        stage.destroy(vkcontext);
+
+        Self {
+            sets,
+            set_layout,
+            descriptor_pool,
+            pipeline,
+        }
+    }
+
+    pub fn destroy(&mut self, vkcontext: &VkContext) {
+        unsafe {
+            self.pipeline.destroy(vkcontext);
+
+            vkcontext.device.destroy_descriptor_pool(self.descriptor_pool, None);
+
+            vkcontext.device.destroy_descriptor_set_layout(self.set_layout, None);
+        }
+    }
+}
+
+impl TonemapShader {
+    pub fn bind(
+        &self,
+        vkcontext: &VkContext,
+        command_buffer: &CommandBuffer,
+        frame_slot: u32,
+        push_constants: &TonemapPushConstants,
+    ) {
+        unsafe {
+            vkcontext.device.cmd_bind_pipeline(
+                command_buffer.handle,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline.handle,
+            );
+
+            let frame_slot = frame_slot as usize;
+            vkcontext.device.cmd_bind_descriptor_sets(
+                command_buffer.handle,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline.layout,
+                0,
+                &self.sets[frame_slot..=frame_slot],
+                &[],
+            );
+        }
+
+        self.pipeline.push_constants(vkcontext, command_buffer, vk::ShaderStageFlags::COMPUTE, push_constants);
+    }
+
+    pub fn update_descriptors(&self, vkcontext: &VkContext, hdr_buffers: &[Image], output_buffers: &[Image]) {
+        let image_infos = hdr_buffers.iter().zip(output_buffers.iter())
+            .map(|(hdr_buffer, output_buffer)| {
+                [
+                    vk::DescriptorImageInfo::builder()
+                        .image_layout(vk::ImageLayout::GENERAL)
+                        .image_view(hdr_buffer.view)
+                        .build(),
+                    vk::DescriptorImageInfo::builder()
+                        .image_layout(vk::ImageLayout::GENERAL)
+                        .image_view(output_buffer.view)
+                        .build(),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let write_ops = self.sets.iter().zip(image_infos.iter())
+            .map(|(set, infos)| {
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(*set)
+                    .dst_binding(0)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .image_info(infos)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        unsafe { vkcontext.device.update_descriptor_sets(&write_ops, &[]); }
+    }
+}
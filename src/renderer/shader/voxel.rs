@@ -0,0 +1,909 @@
+use std::ops::Range;
+use ash::vk;
+use glam::Vec3;
+use super::ShaderStage;
+use crate::renderer::{
+    buffer::Buffer,
+    command_buffer::CommandBuffer,
+    environment::Environment,
+    gbuffer::{GBuffer, GBufferAttachment},
+    image::Image,
+    light::{LightBufferHeader, PointLight, MAX_POINT_LIGHTS},
+    pipeline::Pipeline,
+    vkcontext::VkContext,
+};
+use crate::container::FreeList;
+use crate::material::{Material, MAX_MATERIALS};
+use crate::voxel::{VoxelOctree, VoxelOctreeNode};
+
+/// Binding of `emission_buffer` in set 0 of `voxel.comp`, after the G-buffer.
+const EMISSION_BUFFER_BINDING: u32 = 11;
+
+/// Most bytes one `vkCmdUpdateBuffer` writes.
+const MAX_UPDATE_SIZE: usize = 65536;
+
+/// Mirrors `VoxelOctreeNode` in `voxel.comp`. std140 pads every array element to 16 bytes.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GpuVoxelOctreeNode {
+    branches: [[u32; 4]; 8],
+    masks: u16,
+    _padding: [u16; 7],
+}
+
+impl From<&VoxelOctreeNode> for GpuVoxelOctreeNode {
+    fn from(node: &VoxelOctreeNode) -> Self {
+        GpuVoxelOctreeNode {
+            branches: node.branches.map(|branch| [branch, 0, 0, 0]),
+            masks: node.masks(),
+            _padding: [0; 7],
+        }
+    }
+}
+
+/// Mirrors `Material` in `voxel.comp`, laid out std430.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GpuMaterial {
+    albedo: [f32; 3],
+    alpha: f32,
+    /// Premultiplied by the emission strength.
+    emission: [f32; 3],
+    roughness: f32,
+    metallic: f32,
+    ior: f32,
+    _padding: [f32; 2],
+}
+
+impl From<&Material> for GpuMaterial {
+    fn from(material: &Material) -> Self {
+        GpuMaterial {
+            albedo: material.albedo,
+            alpha: material.alpha,
+            emission: material.emission.map(|channel| channel * material.emission_strength),
+            roughness: material.roughness,
+            metallic: material.metallic,
+            ior: material.ior,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+/// Mirrors the `InstanceParameters` push constant block in `voxel.comp`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct InstancePushConstants {
+    position: [f32; 3],
+    octree_depth: u32,
+    instance_id: u32,
+}
+
+pub struct VoxelShader {
+    max_instance_count: u32,
+    instances: FreeList<VoxelShaderInstance>,
+    /// Octree changes queued for the next frame, see [`VoxelShader::queue_instance_update`].
+    octree_writes: Vec<OctreeWrite>,
+
+    environment_buffers: Vec<Buffer>,
+    light_buffers: Vec<Buffer>,
+    /// Shared by all frames. Only written while the GPU is idle.
+    material_buffer: Buffer,
+
+    global_sets: Vec<vk::DescriptorSet>,
+
+    global_set_layout: vk::DescriptorSetLayout,
+    instance_set_layout: vk::DescriptorSetLayout,
+
+    global_descriptor_pool: vk::DescriptorPool,
+    instance_descriptor_pool: vk::DescriptorPool,
+
+    pipeline: Pipeline,
+}
+
+impl VoxelShader {
+    pub fn new(vkcontext: &VkContext, frame_count: u32, max_instance_count: u32) -> Self {
+        let stage = ShaderStage::new(vkcontext, "shaders/voxel.spv", vk::ShaderStageFlags::COMPUTE);
+
+        // Global set layout.
+        let global_set_layout = {
+            let mut bindings = vec![
+                // Color buffer binding.
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(0)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+                // Environment buffer binding.
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(1)
+                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+                // Ambient occlusion history bindings, ping-ponged between frames.
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(2)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(3)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+                // Point light buffer binding.
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(4)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+                // Material buffer binding.
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(5)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+                // Depth and surface id buffer binding.
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(6)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+            ];
+
+            // G-buffer bindings.
+            bindings.extend(GBufferAttachment::ALL.map(|attachment| {
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(attachment.binding())
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build()
+            }));
+
+            // Emission buffer binding.
+            bindings.push(
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(EMISSION_BUFFER_BINDING)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+            );
+
+            let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+                .bindings(&bindings)
+                .build();
+
+            unsafe { vkcontext.device.create_descriptor_set_layout(&create_info, None).unwrap() }
+        };
+
+        // Instance set layout.
+        let instance_set_layout = {
+            let bindings = [
+                // Octree Nodes.
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(0)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+                // Voxels.
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(1)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+            ];
+
+            let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+                .bindings(&bindings)
+                .build();
+
+            unsafe { vkcontext.device.create_descriptor_set_layout(&create_info, None).unwrap() }
+        };
+
+        let global_descriptor_pool = {
+            let sizes = [
+                vk::DescriptorPoolSize {
+                    ty: vk::DescriptorType::STORAGE_IMAGE,
+                    descriptor_count: (5 + GBufferAttachment::COUNT as u32) * frame_count,
+                },
+                vk::DescriptorPoolSize {
+                    ty: vk::DescriptorType::UNIFORM_BUFFER,
+                    descriptor_count: frame_count,
+                },
+                vk::DescriptorPoolSize {
+                    ty: vk::DescriptorType::STORAGE_BUFFER,
+                    descriptor_count: 2 * frame_count,
+                },
+            ];
+
+            let create_info = vk::DescriptorPoolCreateInfo::builder()
+                .max_sets(frame_count)
+                .pool_sizes(&sizes)
+                .build();
+
+            unsafe { vkcontext.device.create_descriptor_pool(&create_info, None).unwrap() }
+        };
+
+        let instance_descriptor_pool = {
+            let sizes = [
+                vk::DescriptorPoolSize {
+                    ty: vk::DescriptorType::STORAGE_BUFFER,
+                    descriptor_count: 2 * frame_count * max_instance_count,
+                },
+            ];
+
+            let create_info = vk::DescriptorPoolCreateInfo::builder()
+                .flags(vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET)
+                .max_sets(frame_count * max_instance_count)
+                .pool_sizes(&sizes)
+                .build();
+
+            unsafe { vkcontext.device.create_descriptor_pool(&create_info, None).unwrap() }
+        };
+
+        let global_sets = {
+            let global_set_layouts = vec![global_set_layout; frame_count as usize];
+
+            let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(global_descriptor_pool)
+                .set_layouts(&global_set_layouts)
+                .build();
+
+            unsafe { vkcontext.device.allocate_descriptor_sets(&allocate_info).unwrap() }
+        };
+
+        let environment_buffers = global_sets.iter()
+            .map(|_| {
+                Buffer::new(
+                    vkcontext,
+                    std::mem::size_of::<Environment>() as vk::DeviceSize,
+                    vk::BufferUsageFlags::UNIFORM_BUFFER,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let light_buffers = global_sets.iter()
+            .map(|_| {
+                let size = std::mem::size_of::<LightBufferHeader>() + MAX_POINT_LIGHTS * std::mem::size_of::<PointLight>();
+
+                Buffer::new(vkcontext, size as vk::DeviceSize, vk::BufferUsageFlags::STORAGE_BUFFER)
+            })
+            .collect::<Vec<_>>();
+
+        let material_buffer = Buffer::new(
+            vkcontext,
+            (MAX_MATERIALS * std::mem::size_of::<GpuMaterial>()) as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+        );
+
+        // Environment, light and material buffers never change, so their descriptors are written once.
+        {
+            let buffer_info = |buffer: &Buffer| {
+                vk::DescriptorBufferInfo::builder()
+                    .buffer(buffer.handle)
+                    .offset(0)
+                    .range(vk::WHOLE_SIZE)
+                    .build()
+            };
+
+            let environment_infos = environment_buffers.iter().map(buffer_info).collect::<Vec<_>>();
+            let light_infos = light_buffers.iter().map(buffer_info).collect::<Vec<_>>();
+            let material_info = [buffer_info(&material_buffer)];
+
+            let write_ops = global_sets.iter().enumerate()
+                .flat_map(|(i, set)| {
+                    [
+                        vk::WriteDescriptorSet::builder()
+                            .dst_set(*set)
+                            .dst_binding(1)
+                            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                            .buffer_info(&environment_infos[i..=i])
+                            .build(),
+                        vk::WriteDescriptorSet::builder()
+                            .dst_set(*set)
+                            .dst_binding(4)
+                            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                            .buffer_info(&light_infos[i..=i])
+                            .build(),
+                        vk::WriteDescriptorSet::builder()
+                            .dst_set(*set)
+                            .dst_binding(5)
+                            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                            .buffer_info(&material_info)
+                            .build(),
+                    ]
+                })
+                .collect::<Vec<_>>();
+
+            unsafe { vkcontext.device.update_descriptor_sets(&write_ops, &[]); }
+        }
+
+        let push_constant_ranges = [
+            vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::COMPUTE,
+                offset: 0,
+                size: std::mem::size_of::<InstancePushConstants>() as u32,
+            },
+        ];
+
+        let pipeline = Pipeline::new_compute(
+            vkcontext,
+            &[global_set_layout, instance_set_layout],
+            &push_constant_ranges,
+            stage.shader_stage_create_info
+        );
+
+        stage.destroy(vkcontext);
+
+        Self {
+            max_instance_count,
+            instances: FreeList::<VoxelShaderInstance>::with_capacity(3),
+            octree_writes: Vec::new(),
+            environment_buffers,
+            light_buffers,
+            material_buffer,
+            global_sets,
+            global_set_layout,
+            instance_set_layout,
+            global_descriptor_pool,
+            instance_descriptor_pool,
+            pipeline,
+        }
+    }
+
+    pub fn destroy(&mut self, vkcontext: &VkContext) {
+        for (_, instance) in self.instances.iter() {
+            instance.destroy_buffers(vkcontext);
+        }
+
+        for buffer in self.environment_buffers.iter().chain(self.light_buffers.iter()) {
+            buffer.destroy(vkcontext);
+        }
+
+        self.material_buffer.destroy(vkcontext);
+
+        unsafe {
+            self.pipeline.destroy(vkcontext);
+
+            vkcontext.device.destroy_descriptor_pool(self.global_descriptor_pool, None);
+            vkcontext.device.destroy_descriptor_pool(self.instance_descriptor_pool, None);
+
+            vkcontext.device.destroy_descriptor_set_layout(self.global_set_layout, None);
+            vkcontext.device.destroy_descriptor_set_layout(self.instance_set_layout, None);
+        }
+    }
+}
+
+impl VoxelShader {
+    /// Uploads `octree` as a new instance placed at `position`. Returns the instance id.
+    pub fn allocate_instance(&mut self, vkcontext: &VkContext, octree: &VoxelOctree, position: Vec3) -> u32 {
+        assert!(
+            self.instances.iter().count() < self.max_instance_count as usize,
+            "Exceeded the maximum of {} voxel instances.",
+            self.max_instance_count,
+        );
+
+        let descriptor_sets = {
+            let set_layouts = vec![self.instance_set_layout; self.global_sets.len()];
+
+            let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(self.instance_descriptor_pool)
+                .set_layouts(&set_layouts)
+                .build();
+
+            unsafe { vkcontext.device.allocate_descriptor_sets(&allocate_info).unwrap() }
+        };
+
+        let (node_buffer, voxel_buffer) = Self::create_octree_buffers(vkcontext, octree);
+
+        let instance = VoxelShaderInstance {
+            descriptor_sets,
+            node_buffer,
+            voxel_buffer,
+            position,
+            octree_depth: octree.depth(),
+            id: 0,
+        };
+
+        instance.update_descriptors(vkcontext);
+
+        let index = self.instances.push_first(instance);
+        self.instances.get_mut(index).unwrap().id = index as u32;
+
+        index as u32
+    }
+
+    /// Queues the parts of an instance's octree that differ from `previous`, the octree it was last given,
+    /// to be written at the start of the next frame. Frames already in flight keep reading the old octree.
+    /// Returns false if `octree` outgrew the instance's buffers, which [`VoxelShader::update_instance`] then
+    /// has to replace.
+    pub fn queue_instance_update(&mut self, id: u32, previous: &VoxelOctree, octree: &VoxelOctree) -> bool {
+        let Some(instance) = self.instances.get(id as usize) else {
+            log::warn!("Tried to update nonexistent voxel instance {}.", id);
+            return true;
+        };
+
+        let node_size = octree.nodes().len() * std::mem::size_of::<GpuVoxelOctreeNode>();
+
+        if octree.depth() != instance.octree_depth
+            || node_size as vk::DeviceSize > instance.node_buffer.size
+            || std::mem::size_of_val(octree.voxels()) as vk::DeviceSize > instance.voxel_buffer.size
+        {
+            return false;
+        }
+
+        let nodes = changed_range(previous.nodes(), octree.nodes());
+
+        if !nodes.is_empty() {
+            let data = octree.nodes()[nodes.clone()].iter().map(GpuVoxelOctreeNode::from).collect::<Vec<_>>();
+            self.octree_writes.push(OctreeWrite::new(id, &instance.node_buffer, nodes.start, &data));
+        }
+
+        let voxels = changed_range(previous.voxels(), octree.voxels());
+
+        if !voxels.is_empty() {
+            let data = octree.voxels()[voxels.clone()].iter().map(|voxel| voxel.material).collect::<Vec<_>>();
+            self.octree_writes.push(OctreeWrite::new(id, &instance.voxel_buffer, voxels.start, &data));
+        }
+
+        true
+    }
+
+    /// The octree writes queued since the last call, to be recorded with [`record_octree_writes`].
+    pub fn take_octree_writes(&mut self) -> Vec<OctreeWrite> {
+        std::mem::take(&mut self.octree_writes)
+    }
+
+    /// Replaces the octree of an instance, with room to grow. The caller has to make sure the GPU is no
+    /// longer using it.
+    pub fn update_instance(&mut self, vkcontext: &VkContext, id: u32, octree: &VoxelOctree) {
+        let Some(instance) = self.instances.get_mut(id as usize) else {
+            log::warn!("Tried to update nonexistent voxel instance {}.", id);
+            return;
+        };
+
+        // Queued writes would land in the destroyed buffers, and the new ones hold the whole octree anyway.
+        self.octree_writes.retain(|write| write.instance_id != id);
+
+        instance.destroy_buffers(vkcontext);
+
+        (instance.node_buffer, instance.voxel_buffer) = Self::create_octree_buffers(vkcontext, octree);
+        instance.octree_depth = octree.depth();
+
+        instance.update_descriptors(vkcontext);
+    }
+
+    pub fn set_instance_position(&mut self, id: u32, position: Vec3) {
+        if let Some(instance) = self.instances.get_mut(id as usize) {
+            instance.position = position;
+        }
+    }
+
+    /// Frees an instance. The caller has to make sure the GPU is no longer using it.
+    pub fn free_instance(&mut self, vkcontext: &VkContext, id: u32) {
+        let Some(instance) = self.instances.remove(id as usize) else {
+            return;
+        };
+
+        self.octree_writes.retain(|write| write.instance_id != id);
+        instance.destroy_buffers(vkcontext);
+
+        unsafe {
+            vkcontext.device
+                .free_descriptor_sets(self.instance_descriptor_pool, &instance.descriptor_sets)
+                .unwrap();
+        }
+    }
+
+    pub fn update_environment(&self, frame_slot: u32, environment: &Environment) {
+        self.environment_buffers[frame_slot as usize].write(0, std::slice::from_ref(environment));
+    }
+
+    /// Uploads the material table. The GPU must not be using it.
+    pub fn update_materials(&self, materials: &[Material]) {
+        let materials = materials.iter().take(MAX_MATERIALS).map(GpuMaterial::from).collect::<Vec<_>>();
+
+        self.material_buffer.write(0, &materials);
+    }
+
+    /// Uploads the point lights. Anything past [`MAX_POINT_LIGHTS`] is dropped.
+    pub fn update_lights(&self, frame_slot: u32, lights: &[PointLight]) {
+        let lights = &lights[..lights.len().min(MAX_POINT_LIGHTS)];
+        let buffer = &self.light_buffers[frame_slot as usize];
+
+        let header = LightBufferHeader {
+            light_count: lights.len() as u32,
+            _padding: [0; 3],
+        };
+
+        buffer.write(0, std::slice::from_ref(&header));
+        buffer.write(std::mem::size_of::<LightBufferHeader>() as vk::DeviceSize, lights);
+    }
+
+    /// Dispatches the bound pipeline once per instance over `extent` pixels.
+    pub fn dispatch_instances(
+        &self,
+        vkcontext: &VkContext,
+        command_buffer: &CommandBuffer,
+        frame_slot: u32,
+        extent: vk::Extent2D,
+    ) {
+        for (_, instance) in self.instances.iter() {
+            let frame_slot = frame_slot as usize;
+
+            unsafe {
+                vkcontext.device.cmd_bind_descriptor_sets(
+                    command_buffer.handle,
+                    vk::PipelineBindPoint::COMPUTE,
+                    self.pipeline.layout,
+                    1,
+                    &instance.descriptor_sets[frame_slot..=frame_slot],
+                    &[],
+                );
+            }
+
+            let push_constants = InstancePushConstants {
+                position: instance.position.to_array(),
+                octree_depth: instance.octree_depth,
+                instance_id: instance.id,
+            };
+
+            self.pipeline.push_constants(vkcontext, command_buffer, vk::ShaderStageFlags::COMPUTE, &push_constants);
+
+            // Each instance depth tests against the hits of the ones before it.
+            let barrier = vk::MemoryBarrier::builder()
+                .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE)
+                .build();
+
+            unsafe {
+                vkcontext.device.cmd_dispatch(command_buffer.handle, extent.width.div_ceil(8), extent.height.div_ceil(8), 1);
+
+                vkcontext.device.cmd_pipeline_barrier(
+                    command_buffer.handle,
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::DependencyFlags::empty(),
+                    &[barrier],
+                    &[],
+                    &[],
+                );
+            }
+        }
+    }
+
+    pub fn bind(&self, vkcontext: &VkContext, command_buffer: &CommandBuffer, frame_slot: u32) {
+        unsafe {
+            vkcontext.device.cmd_bind_pipeline(
+                command_buffer.handle,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline.handle,
+            );
+        }
+
+        unsafe {
+            let null = [];
+            let frame_slot = frame_slot as usize;
+            vkcontext.device.cmd_bind_descriptor_sets(
+                command_buffer.handle, 
+                vk::PipelineBindPoint::COMPUTE, 
+                self.pipeline.layout, 
+                0, 
+                &self.global_sets[frame_slot..=frame_slot],
+                &null
+            );
+        }
+    }
+
+    /// Points every global set at the same pair of ambient occlusion history images.
+    pub fn update_ao_history_descriptors(&self, vkcontext: &VkContext, ao_history: &[Image; 2]) {
+        let image_infos = ao_history.iter()
+            .map(|image| {
+                vk::DescriptorImageInfo::builder()
+                    .image_layout(vk::ImageLayout::GENERAL)
+                    .image_view(image.view)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        let write_ops = self.global_sets.iter()
+            .flat_map(|set| {
+                image_infos.iter().enumerate().map(|(i, image_info)| {
+                    vk::WriteDescriptorSet::builder()
+                        .dst_set(*set)
+                        .dst_binding(2 + i as u32)
+                        .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                        .image_info(std::slice::from_ref(image_info))
+                        .build()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        unsafe { vkcontext.device.update_descriptor_sets(&write_ops, &[]); }
+    }
+
+    /// Environment buffers by frame slot, for passes that share the voxel pass's camera.
+    pub fn environment_buffers(&self) -> &[Buffer] {
+        &self.environment_buffers
+    }
+
+    pub fn update_gbuffer_descriptors(&self, vkcontext: &VkContext, gbuffers: &[GBuffer]) {
+        let image_infos = gbuffers.iter()
+            .map(|gbuffer| {
+                GBufferAttachment::ALL.map(|attachment| {
+                    vk::DescriptorImageInfo::builder()
+                        .image_layout(vk::ImageLayout::GENERAL)
+                        .image_view(gbuffer.image(attachment).view)
+                        .build()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let write_ops = self.global_sets.iter().zip(image_infos.iter())
+            .flat_map(|(set, infos)| {
+                GBufferAttachment::ALL.iter().zip(infos.iter()).map(|(attachment, info)| {
+                    vk::WriteDescriptorSet::builder()
+                        .dst_set(*set)
+                        .dst_binding(attachment.binding())
+                        .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                        .image_info(std::slice::from_ref(info))
+                        .build()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        unsafe { vkcontext.device.update_descriptor_sets(&write_ops, &[]); }
+    }
+
+    pub fn update_depth_id_buffer_descriptors(&self, vkcontext: &VkContext, depth_id_buffers: &[Image]) {
+        let image_infos = depth_id_buffers.iter()
+            .map(|depth_id_buffer| {
+                vk::DescriptorImageInfo::builder()
+                    .image_layout(vk::ImageLayout::GENERAL)
+                    .image_view(depth_id_buffer.view)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        let write_ops = self.global_sets.iter().zip(image_infos.iter())
+            .map(|(set, image_info)| {
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(*set)
+                    .dst_binding(6)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .image_info(std::slice::from_ref(image_info))
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        unsafe { vkcontext.device.update_descriptor_sets(&write_ops, &[]); }
+    }
+
+    pub fn update_emission_buffer_descriptors(&self, vkcontext: &VkContext, emission_buffers: &[Image]) {
+        let image_infos = emission_buffers.iter()
+            .map(|emission_buffer| {
+                vk::DescriptorImageInfo::builder()
+                    .image_layout(vk::ImageLayout::GENERAL)
+                    .image_view(emission_buffer.view)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        let write_ops = self.global_sets.iter().zip(image_infos.iter())
+            .map(|(set, image_info)| {
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(*set)
+                    .dst_binding(EMISSION_BUFFER_BINDING)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .image_info(std::slice::from_ref(image_info))
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        unsafe { vkcontext.device.update_descriptor_sets(&write_ops, &[]); }
+    }
+
+    pub fn update_color_buffer_descriptors(&self, vkcontext: &VkContext, color_buffers: &[Image]) {
+        let image_infos = color_buffers.iter()
+            .map(|color_buffer| {
+                vk::DescriptorImageInfo::builder()
+                    .image_layout(vk::ImageLayout::GENERAL)
+                    .image_view(color_buffer.view)
+                    .sampler(vk::Sampler::null())
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        
+        let write_ops = self.global_sets.iter().zip(image_infos.iter())
+            .map(|(set, image_info)| {
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(*set)
+                    .dst_binding(0)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .image_info(std::slice::from_ref(image_info))
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        
+        unsafe { vkcontext.device.update_descriptor_sets(&write_ops, &[]); }
+    }
+}
+
+impl VoxelShader {
+    /// Buffers holding `octree`, with half as much room again for edits to grow it into.
+    fn create_octree_buffers(vkcontext: &VkContext, octree: &VoxelOctree) -> (Buffer, Buffer) {
+        let nodes = octree.nodes().iter().map(GpuVoxelOctreeNode::from).collect::<Vec<_>>();
+        let voxels = octree.voxels().iter().map(|voxel| voxel.material).collect::<Vec<_>>();
+
+        let create_buffer = |size: usize| {
+            let usage = vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST;
+
+            Buffer::new(vkcontext, (size + size / 2) as vk::DeviceSize, usage)
+        };
+
+        let node_buffer = create_buffer(std::mem::size_of_val(nodes.as_slice()));
+        node_buffer.write(0, &nodes);
+
+        let voxel_buffer = create_buffer(std::mem::size_of_val(voxels.as_slice()));
+        voxel_buffer.write(0, &voxels);
+
+        (node_buffer, voxel_buffer)
+    }
+}
+
+pub struct VoxelShaderInstance {
+    id: u32,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+
+    node_buffer: Buffer,
+    voxel_buffer: Buffer,
+
+    position: Vec3,
+    octree_depth: u32,
+}
+
+impl VoxelShaderInstance {
+    fn update_descriptors(&self, vkcontext: &VkContext) {
+        let buffer_infos = [
+            vk::DescriptorBufferInfo::builder()
+                .buffer(self.node_buffer.handle)
+                .offset(0)
+                .range(vk::WHOLE_SIZE)
+                .build(),
+            vk::DescriptorBufferInfo::builder()
+                .buffer(self.voxel_buffer.handle)
+                .offset(0)
+                .range(vk::WHOLE_SIZE)
+                .build(),
+        ];
+
+        let write_ops = self.descriptor_sets.iter()
+            .map(|set| {
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(*set)
+                    .dst_binding(0)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .buffer_info(&buffer_infos)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        unsafe { vkcontext.device.update_descriptor_sets(&write_ops, &[]); }
+    }
+
+    fn destroy_buffers(&self, vkcontext: &VkContext) {
+        self.node_buffer.destroy(vkcontext);
+        self.voxel_buffer.destroy(vkcontext);
+    }
+}
+
+/// Part of an instance's octree buffer to overwrite, recorded into a frame's command buffer so frames
+/// already in flight don't see it.
+pub struct OctreeWrite {
+    instance_id: u32,
+    buffer: vk::Buffer,
+    offset: vk::DeviceSize,
+    data: Vec<u8>,
+}
+
+impl OctreeWrite {
+    /// Writes `elements` over the ones from index `first` on.
+    fn new<T: Copy>(instance_id: u32, buffer: &Buffer, first: usize, elements: &[T]) -> Self {
+        let data = unsafe {
+            std::slice::from_raw_parts(elements.as_ptr() as *const u8, std::mem::size_of_val(elements))
+        };
+
+        Self {
+            instance_id,
+            buffer: buffer.handle,
+            offset: (first * std::mem::size_of::<T>()) as vk::DeviceSize,
+            data: data.to_vec(),
+        }
+    }
+}
+
+/// Records `writes` ahead of the frame's passes. Earlier frames may still be reading the buffers, so the
+/// writes wait for every voxel pass before them, and this frame's waits for the writes.
+pub fn record_octree_writes(vkcontext: &VkContext, command_buffer: &CommandBuffer, writes: &[OctreeWrite]) {
+    if writes.is_empty() {
+        return;
+    }
+
+    let device = &vkcontext.device;
+
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer.handle,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[],
+        );
+
+        for write in writes {
+            for (i, chunk) in write.data.chunks(MAX_UPDATE_SIZE).enumerate() {
+                let offset = write.offset + (i * MAX_UPDATE_SIZE) as vk::DeviceSize;
+                device.cmd_update_buffer(command_buffer.handle, write.buffer, offset, chunk);
+            }
+        }
+
+        let barrier = vk::MemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ)
+            .build();
+
+        device.cmd_pipeline_barrier(
+            command_buffer.handle,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::DependencyFlags::empty(),
+            &[barrier],
+            &[],
+            &[],
+        );
+    }
+}
+
+/// Indices from the first to the last element of `current` that differ from `previous`, counting elements
+/// `previous` doesn't have. Empty if there's nothing to write.
+fn changed_range<T: PartialEq>(previous: &[T], current: &[T]) -> Range<usize> {
+    let common = previous.len().min(current.len());
+    let differs = |i: &usize| previous[*i] != current[*i];
+
+    let start = (0..common).find(differs).unwrap_or(common);
+    let end = if current.len() > common {
+        current.len()
+    } else {
+        (start..common).rfind(differs).map_or(start, |i| i + 1)
+    };
+
+    start..end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_range_spans_the_differences() {
+        assert!(changed_range(&[1, 2, 3, 4], &[1, 2, 3, 4]).is_empty());
+        assert_eq!(changed_range(&[1, 2, 3, 4], &[1, 5, 3, 4]), 1..2);
+        assert_eq!(changed_range(&[1, 2, 3, 4], &[0, 2, 3, 5]), 0..4);
+        assert_eq!(changed_range(&[1, 2], &[1, 2, 3]), 2..3);
+        assert_eq!(changed_range(&[1, 2], &[1, 0, 3]), 1..3);
+        assert!(changed_range(&[1, 2, 3], &[1, 2]).is_empty());
+        assert_eq!(changed_range::<u32>(&[], &[]), 0..0);
+    }
+}
@@ -1,5 +1,6 @@
 use ash::{extensions::khr::Surface, vk};
 use super::vkcontext::{VkContext, QueueFamilyIndices};
+use super::settings::VsyncMode;
 use super::utility::create_image_view;
 
 pub struct Swapchain {
@@ -17,6 +18,8 @@ impl Swapchain {
     pub fn new(
         vkcontext: &VkContext,
         queue_family_indices: QueueFamilyIndices,
+        vsync: VsyncMode,
+        prefer_hdr: bool,
     ) -> Self {
         let details = SwapchainSupportDetails::query(
             vkcontext.physical_device,
@@ -24,7 +27,7 @@ impl Swapchain {
             vkcontext.surface_khr
         );
 
-        let properties = details.get_ideal_swapchain_properties();
+        let properties = details.get_ideal_swapchain_properties(vsync, prefer_hdr);
 
         let format = properties.format;
         let present_mode = properties.present_mode;
@@ -60,7 +63,7 @@ impl Swapchain {
                 .image_color_space(format.color_space)
                 .image_extent(extent)
                 .image_array_layers(1)
-                .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::STORAGE);
+                .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_DST);
 
             builder = if graphics != present {
                 builder
@@ -182,6 +185,33 @@ pub struct SwapchainProperties {
     pub extent: vk::Extent2D,
 }
 
+/// How linear color has to be encoded before it is written to the swapchain. Values match `tonemap.comp`.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputTransfer {
+    /// The swapchain format is `_SRGB`, so encoding happens on write.
+    Linear = 0,
+    /// The swapchain format is `_UNORM` in an sRGB color space, so the shader has to encode.
+    Srgb = 1,
+    /// HDR10, Rec. 2020 primaries with the ST 2084 curve.
+    Pq = 2,
+}
+
+impl SwapchainProperties {
+    pub fn output_transfer(&self) -> OutputTransfer {
+        if self.format.color_space == vk::ColorSpaceKHR::HDR10_ST2084_EXT {
+            return OutputTransfer::Pq;
+        }
+
+        match self.format.format {
+            vk::Format::B8G8R8A8_SRGB
+            | vk::Format::R8G8B8A8_SRGB
+            | vk::Format::A8B8G8R8_SRGB_PACK32 => OutputTransfer::Linear,
+            _ => OutputTransfer::Srgb,
+        }
+    }
+}
+
 pub struct SwapchainSupportDetails {
     pub capabilities: vk::SurfaceCapabilitiesKHR,
     pub formats: Vec<vk::SurfaceFormatKHR>,
@@ -215,9 +245,9 @@ impl SwapchainSupportDetails {
         }
     }
 
-    pub fn get_ideal_swapchain_properties(&self) -> SwapchainProperties {
-        let format = Self::choose_swapchain_surface_format(&self.formats);
-        let present_mode = Self::choose_swapchain_surface_present_mode(&self.present_modes);
+    pub fn get_ideal_swapchain_properties(&self, vsync: VsyncMode, prefer_hdr: bool) -> SwapchainProperties {
+        let format = Self::choose_swapchain_surface_format(&self.formats, prefer_hdr);
+        let present_mode = Self::choose_swapchain_surface_present_mode(&self.present_modes, vsync);
         let extent = Self::choose_swapchain_extent(self.capabilities);
 
         SwapchainProperties {
@@ -227,31 +257,53 @@ impl SwapchainSupportDetails {
         }
     }
 
-    fn choose_swapchain_surface_format(available_formats: &[vk::SurfaceFormatKHR]) -> vk::SurfaceFormatKHR {
+    fn choose_swapchain_surface_format(
+        available_formats: &[vk::SurfaceFormatKHR],
+        prefer_hdr: bool,
+    ) -> vk::SurfaceFormatKHR {
         if available_formats.len() == 1 && available_formats[0].format == vk::Format::UNDEFINED {
             return vk::SurfaceFormatKHR {
-                format: vk::Format::B8G8R8A8_UNORM,
+                format: vk::Format::B8G8R8A8_SRGB,
                 color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
             };
         }
 
-        *available_formats
-            .iter()
-            .find(|format| {
-                format.format == vk::Format::B8G8R8A8_UNORM
-                    && format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
-            })
-            .unwrap_or(&available_formats[0])
-    }
+        let find = |format: vk::Format, color_space: vk::ColorSpaceKHR| {
+            available_formats
+                .iter()
+                .find(|available| available.format == format && available.color_space == color_space)
+                .copied()
+        };
 
-    fn choose_swapchain_surface_present_mode(available_present_modes: &[vk::PresentModeKHR]) -> vk::PresentModeKHR {
-        if available_present_modes.contains(&vk::PresentModeKHR::MAILBOX) {
-            vk::PresentModeKHR::MAILBOX
-        } else if available_present_modes.contains(&vk::PresentModeKHR::FIFO) {
-            vk::PresentModeKHR::FIFO
+        let hdr = if prefer_hdr {
+            find(vk::Format::A2B10G10R10_UNORM_PACK32, vk::ColorSpaceKHR::HDR10_ST2084_EXT)
+                .or_else(|| find(vk::Format::A2R10G10B10_UNORM_PACK32, vk::ColorSpaceKHR::HDR10_ST2084_EXT))
         } else {
-            vk::PresentModeKHR::IMMEDIATE
+            None
+        };
+
+        hdr
+            .or_else(|| find(vk::Format::B8G8R8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR))
+            .or_else(|| find(vk::Format::R8G8B8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR))
+            .or_else(|| find(vk::Format::B8G8R8A8_UNORM, vk::ColorSpaceKHR::SRGB_NONLINEAR))
+            .unwrap_or(available_formats[0])
+    }
+
+    fn choose_swapchain_surface_present_mode(
+        available_present_modes: &[vk::PresentModeKHR],
+        vsync: VsyncMode,
+    ) -> vk::PresentModeKHR {
+        let present_mode = vsync.preferred_present_modes()
+            .iter()
+            .find(|mode| available_present_modes.contains(mode))
+            .copied()
+            .unwrap_or(vk::PresentModeKHR::FIFO);
+
+        if present_mode != vsync.preferred_present_modes()[0] {
+            log::warn!("Preferred present mode for {:?} not supported, falling back to {:?}.", vsync, present_mode);
         }
+
+        present_mode
     }
 
     fn choose_swapchain_extent(capabilities: vk::SurfaceCapabilitiesKHR) -> vk::Extent2D {
@@ -0,0 +1,394 @@
+mod atlas;
+mod layout;
+
+use ab_glyph::FontVec;
+use ash::vk;
+use glam::Vec2;
+use super::buffer::Buffer;
+use super::command_buffer::CommandBuffer;
+use super::image::Image;
+use super::pipeline::{BlendMode, GraphicsPipelineInfo, Pipeline};
+use super::shader::ShaderStage;
+use super::swapchain::OutputTransfer;
+use super::utility::upload_image_pixels;
+use super::vkcontext::VkContext;
+use crate::utility;
+
+use atlas::SdfAtlas;
+use layout::layout_text;
+
+/// Font baked at startup, relative to the assets directory.
+const FONT_PATH: &str = "fonts/Hack-Regular.ttf";
+
+fn load_font() -> FontVec {
+    FontVec::try_from_vec(utility::fs::load(FONT_PATH).into_inner())
+        .unwrap_or_else(|error| panic!("Failed to parse font {}, {}.", FONT_PATH, error))
+}
+
+/// Mirrors the `TextParameters` push constant block in `text.vert` and `text.frag`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct TextPushConstants {
+    screen_size: [f32; 2],
+    output_transfer: u32,
+    paper_white: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct TextVertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: [u8; 4],
+}
+
+/// How a string is drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextStyle {
+    /// Font size in pixels.
+    pub size: f32,
+    /// sRGB encoded, with straight alpha.
+    pub color: [u8; 4],
+    /// Width in pixels to wrap lines at.
+    pub max_width: Option<f32>,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle {
+            size: 16.0,
+            color: [255, 255, 255, 255],
+            max_width: None,
+        }
+    }
+}
+
+/// Geometry of one frame in flight's text. The buffers only grow.
+struct TextFrame {
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    index_count: u32,
+}
+
+/// Draws text queued during a frame over it, from a signed distance field atlas so it stays sharp at
+/// any size.
+pub struct TextRenderer {
+    atlas: SdfAtlas,
+    atlas_image: Image,
+
+    /// Glyph quads queued since the last [`TextRenderer::prepare`].
+    vertices: Vec<TextVertex>,
+    indices: Vec<u32>,
+    frames: Vec<TextFrame>,
+
+    sampler: vk::Sampler,
+
+    set_layout: vk::DescriptorSetLayout,
+
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+
+    pipeline: Pipeline,
+}
+
+impl TextRenderer {
+    /// Loads and bakes the font. `color_format` is the format of the swapchain images it draws into.
+    pub fn new(vkcontext: &VkContext, command_pool: vk::CommandPool, color_format: vk::Format) -> Self {
+        let atlas = SdfAtlas::new(load_font());
+        let atlas_extent = vk::Extent2D { width: atlas.width, height: atlas.height };
+
+        let atlas_image = Image::new(
+            vkcontext,
+            atlas_extent,
+            vk::Format::R8_UNORM,
+            vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST,
+        );
+
+        upload_image_pixels(
+            vkcontext,
+            command_pool,
+            &atlas_image,
+            vk::Offset2D { x: 0, y: 0 },
+            atlas_extent,
+            &atlas.pixels,
+            false,
+        );
+
+        let sampler = {
+            let create_info = vk::SamplerCreateInfo::builder()
+                .mag_filter(vk::Filter::LINEAR)
+                .min_filter(vk::Filter::LINEAR)
+                .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+                .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .max_lod(0.0)
+                .build();
+
+            unsafe { vkcontext.device.create_sampler(&create_info, None).unwrap() }
+        };
+
+        let set_layout = {
+            let bindings = [
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(0)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+            ];
+
+            let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+                .bindings(&bindings)
+                .build();
+
+            unsafe { vkcontext.device.create_descriptor_set_layout(&create_info, None).unwrap() }
+        };
+
+        let descriptor_pool = {
+            let sizes = [
+                vk::DescriptorPoolSize {
+                    ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    descriptor_count: 1,
+                },
+            ];
+
+            let create_info = vk::DescriptorPoolCreateInfo::builder()
+                .max_sets(1)
+                .pool_sizes(&sizes)
+                .build();
+
+            unsafe { vkcontext.device.create_descriptor_pool(&create_info, None).unwrap() }
+        };
+
+        let descriptor_set = {
+            let set_layouts = [set_layout];
+
+            let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(descriptor_pool)
+                .set_layouts(&set_layouts)
+                .build();
+
+            unsafe { vkcontext.device.allocate_descriptor_sets(&allocate_info).unwrap()[0] }
+        };
+
+        let image_info = [
+            vk::DescriptorImageInfo::builder()
+                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .image_view(atlas_image.view)
+                .sampler(sampler)
+                .build(),
+        ];
+
+        let write_ops = [
+            vk::WriteDescriptorSet::builder()
+                .dst_set(descriptor_set)
+                .dst_binding(0)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .image_info(&image_info)
+                .build(),
+        ];
+
+        unsafe { vkcontext.device.update_descriptor_sets(&write_ops, &[]) };
+
+        Self {
+            atlas,
+            atlas_image,
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            frames: Vec::new(),
+            sampler,
+            set_layout,
+            descriptor_pool,
+            descriptor_set,
+            pipeline: Self::create_pipeline(vkcontext, set_layout, color_format),
+        }
+    }
+
+    pub fn destroy(&mut self, vkcontext: &VkContext) {
+        self.atlas_image.destroy(vkcontext);
+
+        for frame in self.frames.drain(..) {
+            frame.vertex_buffer.destroy(vkcontext);
+            frame.index_buffer.destroy(vkcontext);
+        }
+
+        unsafe {
+            self.pipeline.destroy(vkcontext);
+
+            vkcontext.device.destroy_sampler(self.sampler, None);
+
+            vkcontext.device.destroy_descriptor_pool(self.descriptor_pool, None);
+
+            vkcontext.device.destroy_descriptor_set_layout(self.set_layout, None);
+        }
+    }
+
+    /// Rebuilds the pipeline for a new swapchain format. The GPU must be idle.
+    pub fn set_color_format(&mut self, vkcontext: &VkContext, color_format: vk::Format) {
+        self.pipeline.destroy(vkcontext);
+        self.pipeline = Self::create_pipeline(vkcontext, self.set_layout, color_format);
+    }
+
+    fn create_pipeline(
+        vkcontext: &VkContext,
+        set_layout: vk::DescriptorSetLayout,
+        color_format: vk::Format,
+    ) -> Pipeline {
+        let vertex_stage = ShaderStage::new(vkcontext, "shaders/text.vert.spv", vk::ShaderStageFlags::VERTEX);
+        let fragment_stage = ShaderStage::new(vkcontext, "shaders/text.frag.spv", vk::ShaderStageFlags::FRAGMENT);
+
+        let vertex_bindings = [
+            vk::VertexInputBindingDescription {
+                binding: 0,
+                stride: std::mem::size_of::<TextVertex>() as u32,
+                input_rate: vk::VertexInputRate::VERTEX,
+            },
+        ];
+
+        // Position and UV in pixels and normalized coordinates, then straight sRGBA.
+        let vertex_attributes = [
+            vk::VertexInputAttributeDescription { location: 0, binding: 0, format: vk::Format::R32G32_SFLOAT, offset: 0 },
+            vk::VertexInputAttributeDescription { location: 1, binding: 0, format: vk::Format::R32G32_SFLOAT, offset: 8 },
+            vk::VertexInputAttributeDescription { location: 2, binding: 0, format: vk::Format::R8G8B8A8_UNORM, offset: 16 },
+        ];
+
+        let push_constant_ranges = [
+            vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                offset: 0,
+                size: std::mem::size_of::<TextPushConstants>() as u32,
+            },
+        ];
+
+        let pipeline = Pipeline::new_graphics(
+            vkcontext,
+            &[set_layout],
+            &push_constant_ranges,
+            &GraphicsPipelineInfo {
+                stages: &[vertex_stage.shader_stage_create_info, fragment_stage.shader_stage_create_info],
+                vertex_bindings: &vertex_bindings,
+                vertex_attributes: &vertex_attributes,
+                topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+                blend_mode: BlendMode::PremultipliedAlpha,
+                color_format,
+            },
+        );
+
+        vertex_stage.destroy(vkcontext);
+        fragment_stage.destroy(vkcontext);
+
+        pipeline
+    }
+}
+
+impl TextRenderer {
+    /// Lays out `text` and queues its glyphs for the next prepared frame. `position` is in pixels, and
+    /// `anchor` is the point of the text's box placed there, from (0, 0) at the top left to (1, 1) at the
+    /// bottom right.
+    pub fn queue(&mut self, text: &str, position: Vec2, anchor: Vec2, style: &TextStyle) {
+        let layout = layout_text(&self.atlas, text, style.size, style.max_width);
+        let origin = layout.origin(position, anchor);
+
+        for quad in layout.quads {
+            let first_vertex = self.vertices.len() as u32;
+            let min = origin + quad.min;
+            let max = origin + quad.max;
+
+            let corners = [
+                ([min.x, min.y], [quad.uv_min.x, quad.uv_min.y]),
+                ([max.x, min.y], [quad.uv_max.x, quad.uv_min.y]),
+                ([max.x, max.y], [quad.uv_max.x, quad.uv_max.y]),
+                ([min.x, max.y], [quad.uv_min.x, quad.uv_max.y]),
+            ];
+
+            self.vertices.extend(corners.map(|(position, uv)| TextVertex { position, uv, color: style.color }));
+            self.indices.extend([0, 1, 2, 0, 2, 3].map(|i| first_vertex + i));
+        }
+    }
+
+    /// Moves the queued glyphs into the buffers of frame slot `frame_slot`.
+    pub fn prepare(&mut self, vkcontext: &VkContext, frame_slot: usize) {
+        while self.frames.len() <= frame_slot {
+            self.frames.push(TextFrame {
+                vertex_buffer: Buffer::new(vkcontext, 0, vk::BufferUsageFlags::VERTEX_BUFFER),
+                index_buffer: Buffer::new(vkcontext, 0, vk::BufferUsageFlags::INDEX_BUFFER),
+                index_count: 0,
+            });
+        }
+
+        let frame = &mut self.frames[frame_slot];
+        let vertex_bytes = std::mem::size_of_val(self.vertices.as_slice()) as vk::DeviceSize;
+        let index_bytes = std::mem::size_of_val(self.indices.as_slice()) as vk::DeviceSize;
+
+        if vertex_bytes > frame.vertex_buffer.size {
+            frame.vertex_buffer.destroy(vkcontext);
+            frame.vertex_buffer = Buffer::new(vkcontext, vertex_bytes.next_power_of_two(), vk::BufferUsageFlags::VERTEX_BUFFER);
+        }
+
+        if index_bytes > frame.index_buffer.size {
+            frame.index_buffer.destroy(vkcontext);
+            frame.index_buffer = Buffer::new(vkcontext, index_bytes.next_power_of_two(), vk::BufferUsageFlags::INDEX_BUFFER);
+        }
+
+        frame.vertex_buffer.write(0, &self.vertices);
+        frame.index_buffer.write(0, &self.indices);
+        frame.index_count = self.indices.len() as u32;
+
+        self.vertices.clear();
+        self.indices.clear();
+    }
+
+    /// Draws what [`TextRenderer::prepare`] moved into `frame_slot`'s buffers. Dynamic rendering into an
+    /// image of `extent` must have begun.
+    pub fn record(
+        &self,
+        vkcontext: &VkContext,
+        command_buffer: &CommandBuffer,
+        frame_slot: usize,
+        extent: vk::Extent2D,
+        output_transfer: OutputTransfer,
+        paper_white: f32,
+    ) {
+        let Some(frame) = self.frames.get(frame_slot).filter(|frame| frame.index_count > 0) else {
+            return;
+        };
+
+        let device = &vkcontext.device;
+
+        let push_constants = TextPushConstants {
+            screen_size: [extent.width as f32, extent.height as f32],
+            output_transfer: output_transfer as u32,
+            paper_white,
+        };
+
+        let scissor = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        };
+
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer.handle, vk::PipelineBindPoint::GRAPHICS, self.pipeline.handle);
+            device.cmd_bind_descriptor_sets(
+                command_buffer.handle,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline.layout,
+                0,
+                &[self.descriptor_set],
+                &[],
+            );
+            device.cmd_bind_vertex_buffers(command_buffer.handle, 0, &[frame.vertex_buffer.handle], &[0]);
+            device.cmd_bind_index_buffer(command_buffer.handle, frame.index_buffer.handle, 0, vk::IndexType::UINT32);
+            device.cmd_set_scissor(command_buffer.handle, 0, &[scissor]);
+        }
+
+        self.pipeline.push_constants(
+            vkcontext,
+            command_buffer,
+            vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+            &push_constants,
+        );
+
+        unsafe { device.cmd_draw_indexed(command_buffer.handle, frame.index_count, 1, 0, 0, 0) };
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use ab_glyph::{point, Font, FontVec, GlyphId, PxScale, ScaleFont};
+use glam::Vec2;
+
+/// Pixel size glyphs are baked at. Larger text is drawn by scaling the distance field up.
+pub const BAKE_SIZE: f32 = 48.0;
+/// How far, in baked pixels, the distance field extends past a glyph's outline. Glyphs are padded by this
+/// much in the atlas.
+pub const SDF_SPREAD: u32 = 6;
+
+const ATLAS_WIDTH: u32 = 512;
+
+/// Characters baked into the atlas: printable ASCII, Latin-1 for accented Western European text, and a few
+/// symbols. The atlas is baked once, so anything else, like other scripts or emoji, is drawn as
+/// [`FALLBACK_CHARACTER`].
+const CHARACTERS: &str = concat!(
+    " !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~",
+    "¡¢£¤¥¦§¨©ª«¬®¯°±²³´µ¶·¸¹º»¼½¾¿ÀÁÂÃÄÅÆÇÈÉÊËÌÍÎÏÐÑÒÓÔÕÖ×ØÙÚÛÜÝÞßàáâãäåæçèéêëìíîïðñòóôõö÷øùúûüýþÿ",
+    "…→←↑↓",
+);
+const FALLBACK_CHARACTER: char = '?';
+
+/// Where a glyph's distance field is in the atlas and where it goes relative to the pen position, on the
+/// baseline. Offset and size are in baked pixels and include the padding.
+#[derive(Clone, Copy, Debug)]
+pub struct AtlasGlyph {
+    pub id: GlyphId,
+    pub offset: Vec2,
+    pub size: Vec2,
+    pub uv_min: Vec2,
+    pub uv_max: Vec2,
+}
+
+/// A font with a signed distance field of each of its glyphs, packed into one single channel image.
+/// Values are 0.5 on the outline and rise towards the inside of the glyph.
+pub struct SdfAtlas {
+    pub font: FontVec,
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+    glyphs: HashMap<char, AtlasGlyph>,
+}
+
+impl SdfAtlas {
+    pub fn new(font: FontVec) -> Self {
+        let scaled_font = font.as_scaled(PxScale::from(BAKE_SIZE));
+
+        // Bake every glyph on its own first, so they can be packed tallest first.
+        let mut fields = CHARACTERS.chars()
+            .filter_map(|character| {
+                let id = scaled_font.glyph_id(character);
+
+                if id.0 == 0 && character != FALLBACK_CHARACTER {
+                    log::warn!("Font has no glyph for {:?}.", character);
+                    return None;
+                }
+
+                Some((character, id, bake_glyph(&font, id)))
+            })
+            .collect::<Vec<_>>();
+
+        fields.sort_by_key(|(_, _, field)| std::cmp::Reverse(field.height));
+
+        // Shelf packing: glyphs are placed left to right, in rows as tall as their first glyph.
+        let mut placements = Vec::with_capacity(fields.len());
+        let (mut x, mut y, mut row_height) = (0, 0, 0);
+
+        for (_, _, field) in &fields {
+            if x + field.width > ATLAS_WIDTH {
+                x = 0;
+                y += row_height;
+                row_height = 0;
+            }
+
+            placements.push((x, y));
+            x += field.width;
+            row_height = row_height.max(field.height);
+        }
+
+        let width = ATLAS_WIDTH;
+        let height = (y + row_height).next_power_of_two();
+        let mut pixels = vec![0; (width * height) as usize];
+        let mut glyphs = HashMap::with_capacity(fields.len());
+
+        for ((character, id, field), (x, y)) in fields.into_iter().zip(placements) {
+            for row in 0..field.height {
+                let source = (row * field.width) as usize..((row + 1) * field.width) as usize;
+                let target = ((y + row) * width + x) as usize;
+
+                pixels[target..target + field.width as usize].copy_from_slice(&field.pixels[source]);
+            }
+
+            let atlas_size = Vec2::new(width as f32, height as f32);
+            let size = Vec2::new(field.width as f32, field.height as f32);
+            let uv_min = Vec2::new(x as f32, y as f32) / atlas_size;
+
+            glyphs.insert(character, AtlasGlyph {
+                id,
+                offset: field.offset,
+                size,
+                uv_min,
+                uv_max: uv_min + size / atlas_size,
+            });
+        }
+
+        Self {
+            font,
+            width,
+            height,
+            pixels,
+            glyphs,
+        }
+    }
+
+    /// The baked glyph for `character`, or the fallback glyph if it wasn't baked. `None` only if the font
+    /// has no fallback glyph either.
+    pub fn glyph(&self, character: char) -> Option<&AtlasGlyph> {
+        self.glyphs.get(&character).or_else(|| self.glyphs.get(&FALLBACK_CHARACTER))
+    }
+}
+
+struct GlyphField {
+    width: u32,
+    height: u32,
+    offset: Vec2,
+    pixels: Vec<u8>,
+}
+
+/// Rasterizes a glyph at [`BAKE_SIZE`] and turns its coverage into a padded distance field. Glyphs without
+/// an outline, like spaces, get an empty field.
+fn bake_glyph(font: &FontVec, id: GlyphId) -> GlyphField {
+    let padding = SDF_SPREAD;
+
+    let Some(outline) = font.outline_glyph(id.with_scale_and_position(BAKE_SIZE, point(0.0, 0.0))) else {
+        return GlyphField {
+            width: 0,
+            height: 0,
+            offset: Vec2::ZERO,
+            pixels: Vec::new(),
+        };
+    };
+
+    let bounds = outline.px_bounds();
+    let width = bounds.width() as u32 + 2 * padding;
+    let height = bounds.height() as u32 + 2 * padding;
+
+    let mut is_inside = vec![false; (width * height) as usize];
+
+    outline.draw(|x, y, coverage| {
+        is_inside[((y + padding) * width + x + padding) as usize] = coverage >= 0.5;
+    });
+
+    let outside_distances = squared_distances(width as usize, height as usize, |i| is_inside[i]);
+    let inside_distances = squared_distances(width as usize, height as usize, |i| !is_inside[i]);
+
+    let pixels = outside_distances.iter().zip(&inside_distances)
+        .map(|(outside, inside)| {
+            let distance = outside.sqrt() - inside.sqrt();
+            let value = 0.5 - distance / (2.0 * SDF_SPREAD as f32);
+
+            (value.clamp(0.0, 1.0) * 255.0).round() as u8
+        })
+        .collect();
+
+    GlyphField {
+        width,
+        height,
+        offset: Vec2::new(bounds.min.x, bounds.min.y) - padding as f32,
+        pixels,
+    }
+}
+
+/// Stands in for infinity, which the transform below can't subtract.
+const FAR: f32 = 1e20;
+
+/// Squared distance from each pixel to the nearest pixel for which `is_seed` holds, with the separable
+/// transform by Felzenszwalb and Huttenlocher: columns first, then rows.
+fn squared_distances(width: usize, height: usize, is_seed: impl Fn(usize) -> bool) -> Vec<f32> {
+    let mut distances = (0..width * height).map(|i| if is_seed(i) { 0.0 } else { FAR }).collect::<Vec<_>>();
+
+    let length = width.max(height);
+    let mut line = vec![0.0; length];
+    let mut transformed = vec![0.0; length];
+    let mut parabolas = vec![0; length];
+    let mut boundaries = vec![0.0; length + 1];
+
+    for x in 0..width {
+        for y in 0..height {
+            line[y] = distances[y * width + x];
+        }
+
+        transform_line(&line[..height], &mut transformed, &mut parabolas, &mut boundaries);
+
+        for y in 0..height {
+            distances[y * width + x] = transformed[y];
+        }
+    }
+
+    for y in 0..height {
+        line[..width].copy_from_slice(&distances[y * width..(y + 1) * width]);
+
+        transform_line(&line[..width], &mut transformed, &mut parabolas, &mut boundaries);
+
+        distances[y * width..(y + 1) * width].copy_from_slice(&transformed[..width]);
+    }
+
+    distances
+}
+
+/// One dimensional squared distance transform of `f`, as the lower envelope of parabolas rooted at each
+/// sample.
+fn transform_line(f: &[f32], transformed: &mut [f32], parabolas: &mut [usize], boundaries: &mut [f32]) {
+    let intersection = |q: usize, p: usize| {
+        ((f[q] + (q * q) as f32) - (f[p] + (p * p) as f32)) / (2 * q - 2 * p) as f32
+    };
+
+    let mut k = 0;
+    parabolas[0] = 0;
+    boundaries[0] = f32::NEG_INFINITY;
+    boundaries[1] = f32::INFINITY;
+
+    for q in 1..f.len() {
+        let mut s = intersection(q, parabolas[k]);
+
+        while s <= boundaries[k] {
+            k -= 1;
+            s = intersection(q, parabolas[k]);
+        }
+
+        k += 1;
+        parabolas[k] = q;
+        boundaries[k] = s;
+        boundaries[k + 1] = f32::INFINITY;
+    }
+
+    k = 0;
+
+    for (q, value) in transformed.iter_mut().enumerate().take(f.len()) {
+        while boundaries[k + 1] < q as f32 {
+            k += 1;
+        }
+
+        let offset = q as f32 - parabolas[k] as f32;
+        *value = offset * offset + f[parabolas[k]];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::load_font;
+
+    #[test]
+    fn unbaked_characters_fall_back() {
+        let atlas = SdfAtlas::new(load_font());
+        let fallback = atlas.glyph(FALLBACK_CHARACTER).unwrap().id;
+
+        for character in ['A', 'é', 'ß', '°', '→'] {
+            assert_ne!(atlas.glyph(character).unwrap().id, fallback, "{:?} wasn't baked.", character);
+        }
+
+        for character in ['漢', 'Ж', '😀', '\t'] {
+            assert_eq!(atlas.glyph(character).unwrap().id, fallback, "{:?} was baked.", character);
+        }
+    }
+
+    #[test]
+    fn glyphs_are_packed_without_overlapping() {
+        let atlas = SdfAtlas::new(load_font());
+        let glyphs = atlas.glyphs.values().filter(|glyph| glyph.size.x > 0.0).collect::<Vec<_>>();
+
+        for (i, a) in glyphs.iter().enumerate() {
+            assert!(a.uv_min.cmpge(Vec2::ZERO).all() && a.uv_max.cmple(Vec2::ONE).all());
+
+            for b in &glyphs[i + 1..] {
+                let overlaps = a.uv_min.cmplt(b.uv_max).all() && b.uv_min.cmplt(a.uv_max).all();
+                assert!(!overlaps, "{:?} overlaps {:?}.", a.id, b.id);
+            }
+        }
+    }
+}
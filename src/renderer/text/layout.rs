@@ -0,0 +1,192 @@
+use ab_glyph::{Font, FontVec, GlyphId, PxScale, PxScaleFont, ScaleFont};
+use glam::Vec2;
+use super::atlas::{SdfAtlas, BAKE_SIZE};
+
+/// A glyph's rectangle relative to the top left of its text, in pixels, and where it is in the atlas.
+#[derive(Clone, Copy, Debug)]
+pub struct GlyphQuad {
+    pub min: Vec2,
+    pub max: Vec2,
+    pub uv_min: Vec2,
+    pub uv_max: Vec2,
+}
+
+/// Positioned glyphs of a string and the size of the box they fill.
+pub struct TextLayout {
+    pub quads: Vec<GlyphQuad>,
+    pub size: Vec2,
+}
+
+impl TextLayout {
+    /// Where the top left of the box goes for `anchor`, the point of the box from (0, 0) at the top left to
+    /// (1, 1) at the bottom right, to be at `position`. Rounded to whole pixels so glyphs stay sharp.
+    pub fn origin(&self, position: Vec2, anchor: Vec2) -> Vec2 {
+        (position - anchor * self.size).round()
+    }
+}
+
+/// Lays out `text` at `size` pixels, with kerning. Lines break at newlines and, if `max_width` is set,
+/// between words that would run past it. Words wider than `max_width` overflow rather than being split.
+///
+/// The bundled font is monospaced and has no kerning pairs, so kerning only changes anything with a
+/// proportional font.
+pub fn layout_text(atlas: &SdfAtlas, text: &str, size: f32, max_width: Option<f32>) -> TextLayout {
+    let font = atlas.font.as_scaled(PxScale::from(size));
+    let scale = size / BAKE_SIZE;
+    let line_height = font.height() + font.line_gap();
+    let max_width = max_width.unwrap_or(f32::INFINITY);
+
+    let space = atlas.glyph(' ').map(|glyph| glyph.id);
+    let space_advance = space.map_or(0.0, |id| font.h_advance(id));
+
+    let mut quads = Vec::new();
+    let mut width = 0.0f32;
+    let mut baseline = font.ascent();
+
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            baseline += line_height;
+        }
+
+        let mut pen = 0.0;
+        let mut previous: Option<GlyphId> = None;
+
+        for (j, word) in line.split(' ').enumerate() {
+            if j > 0 {
+                let space_width = space_advance + kerning(&font, previous, space);
+                let word_width = measure_word(atlas, &font, word);
+
+                if pen > 0.0 && pen + space_width + word_width > max_width {
+                    baseline += line_height;
+                    pen = 0.0;
+                    previous = None;
+                } else {
+                    pen += space_width;
+                    previous = space;
+                }
+            }
+
+            for character in word.chars() {
+                let Some(glyph) = atlas.glyph(character) else {
+                    continue;
+                };
+
+                pen += kerning(&font, previous, Some(glyph.id));
+
+                if glyph.size.x > 0.0 && glyph.size.y > 0.0 {
+                    let min = Vec2::new(pen, baseline) + glyph.offset * scale;
+
+                    quads.push(GlyphQuad {
+                        min,
+                        max: min + glyph.size * scale,
+                        uv_min: glyph.uv_min,
+                        uv_max: glyph.uv_max,
+                    });
+                }
+
+                pen += font.h_advance(glyph.id);
+                previous = Some(glyph.id);
+            }
+
+            width = width.max(pen);
+        }
+    }
+
+    TextLayout {
+        quads,
+        size: Vec2::new(width, baseline - font.descent()),
+    }
+}
+
+fn measure_word(atlas: &SdfAtlas, font: &PxScaleFont<&FontVec>, word: &str) -> f32 {
+    let mut width = 0.0;
+    let mut previous = None;
+
+    for glyph in word.chars().filter_map(|character| atlas.glyph(character)) {
+        width += kerning(font, previous, Some(glyph.id)) + font.h_advance(glyph.id);
+        previous = Some(glyph.id);
+    }
+
+    width
+}
+
+fn kerning(font: &PxScaleFont<&FontVec>, previous: Option<GlyphId>, next: Option<GlyphId>) -> f32 {
+    match (previous, next) {
+        (Some(previous), Some(next)) => font.kern(previous, next),
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::load_font;
+
+    const SIZE: f32 = 16.0;
+
+    fn advance(atlas: &SdfAtlas, character: char) -> f32 {
+        atlas.font.as_scaled(PxScale::from(SIZE)).h_advance(atlas.glyph(character).unwrap().id)
+    }
+
+    fn line_height(atlas: &SdfAtlas) -> f32 {
+        let font = atlas.font.as_scaled(PxScale::from(SIZE));
+
+        font.height() + font.line_gap()
+    }
+
+    #[test]
+    fn lines_wrap_between_words_that_run_past_the_width() {
+        let atlas = SdfAtlas::new(load_font());
+        let advance = advance(&atlas, 'a');
+        let single = layout_text(&atlas, "aaa bbb aaa", SIZE, None);
+        let wrapped = layout_text(&atlas, "aaa bbb aaa", SIZE, Some(7.5 * advance));
+
+        assert_eq!(single.quads.len(), 9);
+        assert_eq!(wrapped.quads.len(), 9);
+        assert!((single.size.x - 11.0 * advance).abs() < 1e-3);
+        assert!((wrapped.size.x - 7.0 * advance).abs() < 1e-3);
+        assert!((wrapped.size.y - single.size.y - line_height(&atlas)).abs() < 1e-3);
+
+        // The third word starts the second line, where the first did on the first.
+        let (first, third) = (wrapped.quads[0], wrapped.quads[6]);
+        assert!((third.min.x - first.min.x).abs() < 1e-3);
+        assert!((third.min.y - first.min.y - line_height(&atlas)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn newlines_break_lines_and_long_words_overflow() {
+        let atlas = SdfAtlas::new(load_font());
+        let advance = advance(&atlas, 'a');
+        let single = layout_text(&atlas, "aaaa", SIZE, None);
+
+        let broken = layout_text(&atlas, "aa\naa", SIZE, None);
+        assert!((broken.size.x - 2.0 * advance).abs() < 1e-3);
+        assert!((broken.size.y - single.size.y - line_height(&atlas)).abs() < 1e-3);
+
+        let overflowing = layout_text(&atlas, "aaaa", SIZE, Some(advance));
+        assert_eq!(overflowing.size, single.size);
+    }
+
+    #[test]
+    fn kerning_moves_the_pen() {
+        let atlas = SdfAtlas::new(load_font());
+        let font = atlas.font.as_scaled(PxScale::from(SIZE));
+        let (a, v) = (atlas.glyph('A').unwrap(), atlas.glyph('V').unwrap());
+        let layout = layout_text(&atlas, "AV", SIZE, None);
+
+        let pen = layout.quads[1].min.x - v.offset.x * SIZE / BAKE_SIZE;
+        assert!((pen - font.h_advance(a.id) - font.kern(a.id, v.id)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn anchors_place_the_box() {
+        let layout = TextLayout { quads: Vec::new(), size: Vec2::new(40.0, 20.0) };
+        let position = Vec2::new(100.0, 50.0);
+
+        assert_eq!(layout.origin(position, Vec2::ZERO), position);
+        assert_eq!(layout.origin(position, Vec2::splat(0.5)), Vec2::new(80.0, 40.0));
+        assert_eq!(layout.origin(position, Vec2::ONE), Vec2::new(60.0, 30.0));
+        assert_eq!(layout.origin(Vec2::new(100.0, 50.0), Vec2::new(0.5, 0.25)), Vec2::new(80.0, 45.0));
+        assert_eq!(layout.origin(Vec2::new(100.3, 50.6), Vec2::ZERO), Vec2::new(100.0, 51.0));
+    }
+}
@@ -0,0 +1,456 @@
+use std::collections::HashMap;
+use ash::vk;
+use egui::epaint::{ClippedPrimitive, ImageData, Primitive, Vertex};
+use egui::{TextureFilter, TextureId, TexturesDelta};
+use super::buffer::Buffer;
+use super::command_buffer::CommandBuffer;
+use super::image::Image;
+use super::pipeline::{BlendMode, GraphicsPipelineInfo, Pipeline};
+use super::shader::ShaderStage;
+use super::swapchain::OutputTransfer;
+use super::utility::upload_image_pixels;
+use super::vkcontext::VkContext;
+
+/// Textures the UI can have at once. egui only makes one for its font atlas unless asked for more.
+const MAX_UI_TEXTURES: u32 = 64;
+
+/// Mirrors the `UiParameters` push constant block in `ui.vert` and `ui.frag`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct UiPushConstants {
+    screen_size: [f32; 2],
+    output_transfer: u32,
+    paper_white: f32,
+}
+
+/// A mesh, or part of one, drawn with one texture and clip rectangle.
+struct UiDraw {
+    texture: TextureId,
+    scissor: vk::Rect2D,
+    first_index: u32,
+    index_count: u32,
+    vertex_offset: i32,
+}
+
+/// Geometry of one frame in flight's UI. The buffers only grow.
+struct UiFrame {
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    draws: Vec<UiDraw>,
+    pixels_per_point: f32,
+}
+
+struct UiTexture {
+    image: Image,
+    set: vk::DescriptorSet,
+}
+
+/// Draws egui output over the frame with a graphics pipeline, into the swapchain image.
+pub struct UiRenderer {
+    textures: HashMap<TextureId, UiTexture>,
+    /// Textures egui is done with, freed before the next textures are set, once the GPU is idle.
+    freed_textures: Vec<TextureId>,
+    frames: Vec<UiFrame>,
+
+    linear_sampler: vk::Sampler,
+    nearest_sampler: vk::Sampler,
+
+    set_layout: vk::DescriptorSetLayout,
+
+    descriptor_pool: vk::DescriptorPool,
+
+    pipeline: Pipeline,
+}
+
+impl UiRenderer {
+    /// `color_format` is the format of the swapchain images it draws into.
+    pub fn new(vkcontext: &VkContext, color_format: vk::Format) -> Self {
+        let set_layout = {
+            let bindings = [
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(0)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+            ];
+
+            let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+                .bindings(&bindings)
+                .build();
+
+            unsafe { vkcontext.device.create_descriptor_set_layout(&create_info, None).unwrap() }
+        };
+
+        let descriptor_pool = {
+            let sizes = [
+                vk::DescriptorPoolSize {
+                    ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    descriptor_count: MAX_UI_TEXTURES,
+                },
+            ];
+
+            let create_info = vk::DescriptorPoolCreateInfo::builder()
+                .flags(vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET)
+                .max_sets(MAX_UI_TEXTURES)
+                .pool_sizes(&sizes)
+                .build();
+
+            unsafe { vkcontext.device.create_descriptor_pool(&create_info, None).unwrap() }
+        };
+
+        let create_sampler = |filter: vk::Filter| {
+            let create_info = vk::SamplerCreateInfo::builder()
+                .mag_filter(filter)
+                .min_filter(filter)
+                .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+                .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .max_lod(0.0)
+                .build();
+
+            unsafe { vkcontext.device.create_sampler(&create_info, None).unwrap() }
+        };
+
+        Self {
+            textures: HashMap::new(),
+            freed_textures: Vec::new(),
+            frames: Vec::new(),
+            linear_sampler: create_sampler(vk::Filter::LINEAR),
+            nearest_sampler: create_sampler(vk::Filter::NEAREST),
+            set_layout,
+            descriptor_pool,
+            pipeline: Self::create_pipeline(vkcontext, set_layout, color_format),
+        }
+    }
+
+    pub fn destroy(&mut self, vkcontext: &VkContext) {
+        for (_, texture) in self.textures.drain() {
+            texture.image.destroy(vkcontext);
+        }
+
+        for frame in self.frames.drain(..) {
+            frame.vertex_buffer.destroy(vkcontext);
+            frame.index_buffer.destroy(vkcontext);
+        }
+
+        unsafe {
+            self.pipeline.destroy(vkcontext);
+
+            vkcontext.device.destroy_sampler(self.linear_sampler, None);
+            vkcontext.device.destroy_sampler(self.nearest_sampler, None);
+
+            vkcontext.device.destroy_descriptor_pool(self.descriptor_pool, None);
+
+            vkcontext.device.destroy_descriptor_set_layout(self.set_layout, None);
+        }
+    }
+
+    /// Rebuilds the pipeline for a new swapchain format. The GPU must be idle.
+    pub fn set_color_format(&mut self, vkcontext: &VkContext, color_format: vk::Format) {
+        self.pipeline.destroy(vkcontext);
+        self.pipeline = Self::create_pipeline(vkcontext, self.set_layout, color_format);
+    }
+
+    fn create_pipeline(
+        vkcontext: &VkContext,
+        set_layout: vk::DescriptorSetLayout,
+        color_format: vk::Format,
+    ) -> Pipeline {
+        let vertex_stage = ShaderStage::new(vkcontext, "shaders/ui.vert.spv", vk::ShaderStageFlags::VERTEX);
+        let fragment_stage = ShaderStage::new(vkcontext, "shaders/ui.frag.spv", vk::ShaderStageFlags::FRAGMENT);
+
+        let vertex_bindings = [
+            vk::VertexInputBindingDescription {
+                binding: 0,
+                stride: std::mem::size_of::<Vertex>() as u32,
+                input_rate: vk::VertexInputRate::VERTEX,
+            },
+        ];
+
+        // Position and UV in points and normalized coordinates, then premultiplied sRGBA.
+        let vertex_attributes = [
+            vk::VertexInputAttributeDescription { location: 0, binding: 0, format: vk::Format::R32G32_SFLOAT, offset: 0 },
+            vk::VertexInputAttributeDescription { location: 1, binding: 0, format: vk::Format::R32G32_SFLOAT, offset: 8 },
+            vk::VertexInputAttributeDescription { location: 2, binding: 0, format: vk::Format::R8G8B8A8_UNORM, offset: 16 },
+        ];
+
+        let push_constant_ranges = [
+            vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                offset: 0,
+                size: std::mem::size_of::<UiPushConstants>() as u32,
+            },
+        ];
+
+        let pipeline = Pipeline::new_graphics(
+            vkcontext,
+            &[set_layout],
+            &push_constant_ranges,
+            &GraphicsPipelineInfo {
+                stages: &[vertex_stage.shader_stage_create_info, fragment_stage.shader_stage_create_info],
+                vertex_bindings: &vertex_bindings,
+                vertex_attributes: &vertex_attributes,
+                topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+                blend_mode: BlendMode::PremultipliedAlpha,
+                color_format,
+            },
+        );
+
+        vertex_stage.destroy(vkcontext);
+        fragment_stage.destroy(vkcontext);
+
+        pipeline
+    }
+}
+
+impl UiRenderer {
+    /// Creates and patches textures, and frees the ones the previous delta was done with. Waits for the GPU
+    /// to go idle before touching textures earlier frames may still be drawing with.
+    pub fn update_textures(
+        &mut self,
+        vkcontext: &VkContext,
+        command_pool: vk::CommandPool,
+        textures_delta: &TexturesDelta,
+    ) {
+        let is_patching = textures_delta.set.iter().any(|(id, delta)| delta.pos.is_some() || self.textures.contains_key(id));
+
+        if is_patching || !self.freed_textures.is_empty() {
+            vkcontext.wait_gpu_idle();
+        }
+
+        for id in self.freed_textures.drain(..) {
+            if let Some(texture) = self.textures.remove(&id) {
+                texture.image.destroy(vkcontext);
+                unsafe { vkcontext.device.free_descriptor_sets(self.descriptor_pool, &[texture.set]).unwrap() };
+            }
+        }
+
+        for (id, delta) in &textures_delta.set {
+            let size = delta.image.size();
+            let pixels = match &delta.image {
+                ImageData::Color(image) => image.pixels.clone(),
+                ImageData::Font(image) => image.srgba_pixels(None).collect(),
+            };
+
+            // A whole image replaces the texture, which may change its size.
+            if delta.pos.is_none() {
+                if let Some(texture) = self.textures.remove(id) {
+                    texture.image.destroy(vkcontext);
+                    unsafe { vkcontext.device.free_descriptor_sets(self.descriptor_pool, &[texture.set]).unwrap() };
+                }
+
+                let sampler = match delta.options.magnification {
+                    TextureFilter::Linear => self.linear_sampler,
+                    TextureFilter::Nearest => self.nearest_sampler,
+                };
+
+                let texture = self.create_texture(vkcontext, size, sampler);
+                self.textures.insert(*id, texture);
+            }
+
+            let Some(texture) = self.textures.get(id) else {
+                log::warn!("UI texture {:?} patched before it was created.", id);
+                continue;
+            };
+
+            let [x, y] = delta.pos.unwrap_or([0, 0]);
+
+            upload_image_pixels(
+                vkcontext,
+                command_pool,
+                &texture.image,
+                vk::Offset2D { x: x as i32, y: y as i32 },
+                vk::Extent2D { width: size[0] as u32, height: size[1] as u32 },
+                &pixels,
+                delta.pos.is_some(),
+            );
+        }
+
+        self.freed_textures.extend(textures_delta.free.iter().copied());
+    }
+
+    fn create_texture(&self, vkcontext: &VkContext, size: [usize; 2], sampler: vk::Sampler) -> UiTexture {
+        let image = Image::new(
+            vkcontext,
+            vk::Extent2D { width: size[0] as u32, height: size[1] as u32 },
+            vk::Format::R8G8B8A8_UNORM,
+            vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST,
+        );
+
+        let set = {
+            let set_layouts = [self.set_layout];
+
+            let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(self.descriptor_pool)
+                .set_layouts(&set_layouts)
+                .build();
+
+            unsafe { vkcontext.device.allocate_descriptor_sets(&allocate_info).expect("Too many UI textures.")[0] }
+        };
+
+        let image_info = [
+            vk::DescriptorImageInfo::builder()
+                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .image_view(image.view)
+                .sampler(sampler)
+                .build(),
+        ];
+
+        let write_ops = [
+            vk::WriteDescriptorSet::builder()
+                .dst_set(set)
+                .dst_binding(0)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .image_info(&image_info)
+                .build(),
+        ];
+
+        unsafe { vkcontext.device.update_descriptor_sets(&write_ops, &[]) };
+
+        UiTexture { image, set }
+    }
+
+    /// Copies the meshes of `primitives` into the buffers of frame slot `frame_slot`, clipped to
+    /// `extent` in pixels.
+    pub fn prepare(
+        &mut self,
+        vkcontext: &VkContext,
+        frame_slot: usize,
+        primitives: &[ClippedPrimitive],
+        pixels_per_point: f32,
+        extent: vk::Extent2D,
+    ) {
+        while self.frames.len() <= frame_slot {
+            self.frames.push(UiFrame {
+                vertex_buffer: Buffer::new(vkcontext, 0, vk::BufferUsageFlags::VERTEX_BUFFER),
+                index_buffer: Buffer::new(vkcontext, 0, vk::BufferUsageFlags::INDEX_BUFFER),
+                draws: Vec::new(),
+                pixels_per_point,
+            });
+        }
+
+        let meshes = primitives.iter()
+            .filter_map(|primitive| match &primitive.primitive {
+                Primitive::Mesh(mesh) => Some((primitive.clip_rect, mesh)),
+                // Paint callbacks are for other backends' custom rendering.
+                Primitive::Callback(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        let vertex_bytes = meshes.iter().map(|(_, mesh)| std::mem::size_of_val(mesh.vertices.as_slice())).sum::<usize>();
+        let index_bytes = meshes.iter().map(|(_, mesh)| std::mem::size_of_val(mesh.indices.as_slice())).sum::<usize>();
+
+        let frame = &mut self.frames[frame_slot];
+
+        if vertex_bytes as vk::DeviceSize > frame.vertex_buffer.size {
+            frame.vertex_buffer.destroy(vkcontext);
+            frame.vertex_buffer = Buffer::new(
+                vkcontext,
+                vertex_bytes.next_power_of_two() as vk::DeviceSize,
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+            );
+        }
+
+        if index_bytes as vk::DeviceSize > frame.index_buffer.size {
+            frame.index_buffer.destroy(vkcontext);
+            frame.index_buffer = Buffer::new(
+                vkcontext,
+                index_bytes.next_power_of_two() as vk::DeviceSize,
+                vk::BufferUsageFlags::INDEX_BUFFER,
+            );
+        }
+
+        frame.draws.clear();
+        frame.pixels_per_point = pixels_per_point;
+
+        let mut vertex_count = 0;
+        let mut index_count = 0;
+
+        for (clip_rect, mesh) in meshes {
+            frame.vertex_buffer.write((vertex_count * std::mem::size_of::<Vertex>()) as vk::DeviceSize, &mesh.vertices);
+            frame.index_buffer.write((index_count * std::mem::size_of::<u32>()) as vk::DeviceSize, &mesh.indices);
+
+            let min_x = (clip_rect.min.x * pixels_per_point).round().clamp(0.0, extent.width as f32) as u32;
+            let min_y = (clip_rect.min.y * pixels_per_point).round().clamp(0.0, extent.height as f32) as u32;
+            let max_x = (clip_rect.max.x * pixels_per_point).round().clamp(0.0, extent.width as f32) as u32;
+            let max_y = (clip_rect.max.y * pixels_per_point).round().clamp(0.0, extent.height as f32) as u32;
+
+            if max_x > min_x && max_y > min_y {
+                frame.draws.push(UiDraw {
+                    texture: mesh.texture_id,
+                    scissor: vk::Rect2D {
+                        offset: vk::Offset2D { x: min_x as i32, y: min_y as i32 },
+                        extent: vk::Extent2D { width: max_x - min_x, height: max_y - min_y },
+                    },
+                    first_index: index_count as u32,
+                    index_count: mesh.indices.len() as u32,
+                    vertex_offset: vertex_count as i32,
+                });
+            }
+
+            vertex_count += mesh.vertices.len();
+            index_count += mesh.indices.len();
+        }
+    }
+
+    /// Draws what [`UiRenderer::prepare`] copied for `frame_slot`. Dynamic rendering into an image of
+    /// `extent` must have begun.
+    pub fn record(
+        &self,
+        vkcontext: &VkContext,
+        command_buffer: &CommandBuffer,
+        frame_slot: usize,
+        extent: vk::Extent2D,
+        output_transfer: OutputTransfer,
+        paper_white: f32,
+    ) {
+        let Some(frame) = self.frames.get(frame_slot).filter(|frame| !frame.draws.is_empty()) else {
+            return;
+        };
+
+        let device = &vkcontext.device;
+
+        let push_constants = UiPushConstants {
+            screen_size: [
+                extent.width as f32 / frame.pixels_per_point,
+                extent.height as f32 / frame.pixels_per_point,
+            ],
+            output_transfer: output_transfer as u32,
+            paper_white,
+        };
+
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer.handle, vk::PipelineBindPoint::GRAPHICS, self.pipeline.handle);
+            device.cmd_bind_vertex_buffers(command_buffer.handle, 0, &[frame.vertex_buffer.handle], &[0]);
+            device.cmd_bind_index_buffer(command_buffer.handle, frame.index_buffer.handle, 0, vk::IndexType::UINT32);
+        }
+
+        self.pipeline.push_constants(
+            vkcontext,
+            command_buffer,
+            vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+            &push_constants,
+        );
+
+        for draw in &frame.draws {
+            let Some(texture) = self.textures.get(&draw.texture) else {
+                continue;
+            };
+
+            unsafe {
+                device.cmd_bind_descriptor_sets(
+                    command_buffer.handle,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.pipeline.layout,
+                    0,
+                    &[texture.set],
+                    &[],
+                );
+                device.cmd_set_scissor(command_buffer.handle, 0, &[draw.scissor]);
+                device.cmd_draw_indexed(command_buffer.handle, draw.index_count, 1, draw.first_index, draw.vertex_offset, 0);
+            }
+        }
+    }
+}
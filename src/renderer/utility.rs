@@ -1,4 +1,9 @@
 use ash::{vk, Device};
+use super::buffer::Buffer;
+use super::command_buffer::CommandBuffer;
+use super::image::Image;
+use super::vkcontext::VkContext;
+use super::COLOR_SUBRESOURCE_LAYERS;
 
 pub fn create_image_view(
     device: &Device,
@@ -22,3 +27,124 @@ pub fn create_image_view(
 
     unsafe { device.create_image_view(&create_info, None).unwrap() }
 }
+
+pub fn find_memory_type(
+    vkcontext: &VkContext,
+    type_bits: u32,
+    properties: vk::MemoryPropertyFlags,
+) -> u32 {
+    let memory_properties = unsafe {
+        vkcontext.instance.get_physical_device_memory_properties(vkcontext.physical_device)
+    };
+
+    (0..memory_properties.memory_type_count)
+        .find(|i| {
+            type_bits & (1 << i) != 0
+                && memory_properties.memory_types[*i as usize].property_flags.contains(properties)
+        })
+        .expect("Failed to find a suitable memory type.")
+}
+
+/// Barrier over the single mip level and layer of a color image.
+pub fn color_image_barrier(
+    image: vk::Image,
+    old_layout: vk::ImageLayout,
+    new_layout: vk::ImageLayout,
+    src_access_mask: vk::AccessFlags,
+    dst_access_mask: vk::AccessFlags,
+) -> vk::ImageMemoryBarrier {
+    vk::ImageMemoryBarrier::builder()
+        .old_layout(old_layout)
+        .new_layout(new_layout)
+        .src_access_mask(src_access_mask)
+        .dst_access_mask(dst_access_mask)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        })
+        .build()
+}
+
+/// Copies `pixels` into a region of `image` through a staging buffer and waits for the copy. The image is
+/// left ready for sampling.
+pub fn upload_image_pixels<T: Copy>(
+    vkcontext: &VkContext,
+    command_pool: vk::CommandPool,
+    image: &Image,
+    offset: vk::Offset2D,
+    extent: vk::Extent2D,
+    pixels: &[T],
+    is_patch: bool,
+) {
+    let staging_buffer = Buffer::with_data(vkcontext, pixels, vk::BufferUsageFlags::TRANSFER_SRC);
+    let mut command_buffer = CommandBuffer::new(vkcontext, command_pool, true);
+
+    command_buffer.begin(vkcontext, true, false, false);
+
+    // A patch keeps the rest of the texture, a whole image doesn't have to.
+    let old_layout = if is_patch { vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL } else { vk::ImageLayout::UNDEFINED };
+
+    let region = vk::BufferImageCopy {
+        buffer_offset: 0,
+        buffer_row_length: 0,
+        buffer_image_height: 0,
+        image_subresource: COLOR_SUBRESOURCE_LAYERS,
+        image_offset: vk::Offset3D { x: offset.x, y: offset.y, z: 0 },
+        image_extent: vk::Extent3D { width: extent.width, height: extent.height, depth: 1 },
+    };
+
+    unsafe {
+        vkcontext.device.cmd_pipeline_barrier(
+            command_buffer.handle,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[color_image_barrier(
+                image.handle,
+                old_layout,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::AccessFlags::empty(),
+                vk::AccessFlags::TRANSFER_WRITE,
+            )],
+        );
+
+        vkcontext.device.cmd_copy_buffer_to_image(
+            command_buffer.handle,
+            staging_buffer.handle,
+            image.handle,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &[region],
+        );
+
+        vkcontext.device.cmd_pipeline_barrier(
+            command_buffer.handle,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[color_image_barrier(
+                image.handle,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::AccessFlags::SHADER_READ,
+            )],
+        );
+    }
+
+    command_buffer.end_and_submit_single_use(vkcontext, vkcontext.graphics_queue);
+
+    unsafe { vkcontext.device.queue_wait_idle(vkcontext.graphics_queue).unwrap() };
+
+    command_buffer.destroy(vkcontext, command_pool);
+    staging_buffer.destroy(vkcontext);
+}
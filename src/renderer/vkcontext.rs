@@ -2,6 +2,8 @@ use ash::{
     extensions::{ext::DebugUtils, khr::{Surface, Swapchain}},
     vk, Device, Entry, Instance,
 };
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::ffi::{CStr, CString};
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
 use winit::window::Window;
@@ -19,12 +21,27 @@ pub struct VkContext {
     pub instance: Instance,
     pub loaders: ExtensionLoaders,
     pub entry: Entry,
+
+    /// Size of every live allocation, and whether it's device local.
+    allocations: RefCell<HashMap<vk::DeviceMemory, (vk::DeviceSize, bool)>>,
+}
+
+/// Device memory allocated through [`VkContext::allocate_memory`] and not yet freed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub allocation_count: usize,
+    pub device_local_bytes: vk::DeviceSize,
+    pub host_visible_bytes: vk::DeviceSize,
 }
 
 impl VkContext {
-    pub fn new(window: &Window) -> Self {
+    /// With `enable_validation`, the Khronos validation layer is loaded if it's installed and its messages
+    /// are logged. With `preferred_device`, the first suitable GPU whose name contains it is picked,
+    /// ignoring case.
+    pub fn new(window: &Window, enable_validation: bool, preferred_device: Option<&str>) -> Self {
         let entry = unsafe { Entry::load().expect("Failed to load ash entry.") };
-        let instance = Self::create_instance(&entry, window);
+        let enable_validation = enable_validation && check_validation_layer_support(&entry);
+        let instance = Self::create_instance(&entry, window, enable_validation);
 
         let surface_loader = Surface::new(&entry, &instance);
 
@@ -39,10 +56,10 @@ impl VkContext {
             .unwrap()
         };
 
-        let debug_report_callback = setup_debug_messenger(&entry, &instance);
+        let debug_report_callback = setup_debug_messenger(&entry, &instance, enable_validation);
 
         let (physical_device, queue_family_indices) =
-            Self::pick_physical_device(&instance, &surface_loader, surface_khr);
+            Self::pick_physical_device(&instance, &surface_loader, surface_khr, preferred_device);
 
         let (device, graphics_queue, present_queue) = 
             Self::create_logical_device_with_graphics_queue(&instance, physical_device, queue_family_indices);
@@ -63,6 +80,7 @@ impl VkContext {
                 swapchain: swapchain_loader,
             },
             entry,
+            allocations: RefCell::new(HashMap::new()),
         }
     }
 
@@ -82,10 +100,50 @@ impl VkContext {
     pub fn wait_gpu_idle(&self) {
         unsafe { self.device.device_wait_idle().unwrap(); }
     }
+
+    /// Allocates device memory and keeps track of it for [`VkContext::memory_usage`].
+    pub fn allocate_memory(&self, allocate_info: &vk::MemoryAllocateInfo) -> vk::DeviceMemory {
+        let memory = unsafe { self.device.allocate_memory(allocate_info, None).unwrap() };
+
+        let memory_properties = unsafe { self.instance.get_physical_device_memory_properties(self.physical_device) };
+        let is_device_local = memory_properties.memory_types[allocate_info.memory_type_index as usize]
+            .property_flags
+            .contains(vk::MemoryPropertyFlags::DEVICE_LOCAL);
+
+        self.allocations.borrow_mut().insert(memory, (allocate_info.allocation_size, is_device_local));
+
+        memory
+    }
+
+    /// Frees memory from [`VkContext::allocate_memory`]. Null memory is ignored.
+    pub fn free_memory(&self, memory: vk::DeviceMemory) {
+        if memory == vk::DeviceMemory::null() {
+            return;
+        }
+
+        self.allocations.borrow_mut().remove(&memory);
+
+        unsafe { self.device.free_memory(memory, None) };
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.allocations.borrow().values()
+            .fold(MemoryUsage::default(), |mut usage, (size, is_device_local)| {
+                usage.allocation_count += 1;
+
+                if *is_device_local {
+                    usage.device_local_bytes += size;
+                } else {
+                    usage.host_visible_bytes += size;
+                }
+
+                usage
+            })
+    }
 }
 
 impl VkContext {
-    fn create_instance(entry: &Entry, window: &Window) -> Instance {
+    fn create_instance(entry: &Entry, window: &Window, enable_validation: bool) -> Instance {
         let app_name = CString::new("Industria").unwrap();
         let engine_name = CString::new("No Engine").unwrap();
         let app_info = vk::ApplicationInfo::builder()
@@ -101,10 +159,15 @@ impl VkContext {
 
         let mut extension_names = extension_names.iter().map(|ext| *ext).collect::<Vec<_>>();
 
-        if ENABLE_VALIDATION_LAYERS {
+        if enable_validation {
             extension_names.push(DebugUtils::name().as_ptr());
         }
 
+        // Needed for HDR color spaces to be reported by the surface.
+        if Self::is_instance_extension_supported(entry, vk::ExtSwapchainColorspaceFn::name()) {
+            extension_names.push(vk::ExtSwapchainColorspaceFn::name().as_ptr());
+        }
+
         let (_layer_names, layer_names_ptr) = get_layer_names_and_pointers();
 
         let mut instance_create_info = vk::InstanceCreateInfo::builder()
@@ -112,30 +175,50 @@ impl VkContext {
             .enabled_extension_names(&extension_names)
             .flags(vk::InstanceCreateFlags::default());
 
-        if ENABLE_VALIDATION_LAYERS {
-            check_validation_layer_support(&entry);
+        if enable_validation {
             instance_create_info = instance_create_info.enabled_layer_names(&layer_names_ptr);
         }
 
         unsafe { entry.create_instance(&instance_create_info, None).unwrap() }
     }
 
+    fn is_instance_extension_supported(entry: &Entry, name: &CStr) -> bool {
+        entry
+            .enumerate_instance_extension_properties(None)
+            .unwrap()
+            .iter()
+            .any(|ext| unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) } == name)
+    }
+
     fn pick_physical_device(
         instance: &Instance,
         surface_loader: &Surface,
         surface_khr: vk::SurfaceKHR,
+        preferred_device: Option<&str>,
     ) -> (vk::PhysicalDevice, QueueFamilyIndices) {
         let devices = unsafe { instance.enumerate_physical_devices().unwrap() };
-        let device = devices
+        let devices: Vec<_> = devices
             .into_iter()
-            .find(|device| Self::is_device_suitable(instance, surface_loader, surface_khr, *device))
-            .expect("No suitable physical devices found.");
+            .filter(|device| Self::is_device_suitable(instance, surface_loader, surface_khr, *device))
+            .map(|device| (device, Self::device_name(instance, device)))
+            .collect();
 
-        let props = unsafe { instance.get_physical_device_properties(device) };
-        
-        log::debug!("Selected physical device: {:?}", unsafe {
-            CStr::from_ptr(props.device_name.as_ptr())
-        });
+        let (device, name) = match preferred_device {
+            Some(preferred) => devices
+                .iter()
+                .find(|(_, name)| name.to_lowercase().contains(&preferred.to_lowercase()))
+                .or_else(|| {
+                    let names: Vec<_> = devices.iter().map(|(_, name)| name.as_str()).collect();
+                    log::warn!("No suitable physical device matches {:?}, found {:?}.", preferred, names);
+
+                    devices.first()
+                }),
+            None => devices.first(),
+        }
+        .cloned()
+        .expect("No suitable physical devices found.");
+
+        log::info!("Selected physical device: {}", name);
 
         let (graphics, present) = Self::find_queue_families(instance, surface_loader, surface_khr, device);
 
@@ -147,6 +230,12 @@ impl VkContext {
         (device, queue_families_indices)
     }
 
+    fn device_name(instance: &Instance, device: vk::PhysicalDevice) -> String {
+        let props = unsafe { instance.get_physical_device_properties(device) };
+
+        unsafe { CStr::from_ptr(props.device_name.as_ptr()) }.to_string_lossy().into_owned()
+    }
+
     fn is_device_suitable(
         instance: &Instance,
         surface_loader: &Surface,
@@ -163,11 +252,19 @@ impl VkContext {
 
         let features = unsafe { instance.get_physical_device_features(device) };
 
+        let mut vk13_features = vk::PhysicalDeviceVulkan13Features::default();
+        let mut features2 = vk::PhysicalDeviceFeatures2::builder()
+            .push_next(&mut vk13_features)
+            .build();
+
+        unsafe { instance.get_physical_device_features2(device, &mut features2) };
+
         graphics.is_some()
             && present.is_some()
             && extension_support
             && is_swapchain_suitable
             && features.sampler_anisotropy == vk::TRUE
+            && vk13_features.dynamic_rendering == vk::TRUE
     }
 
     fn check_device_extension_support(instance: &Instance, device: vk::PhysicalDevice) -> bool {
@@ -272,11 +369,17 @@ impl VkContext {
             .uniform_and_storage_buffer16_bit_access(true)
             .build();
 
+        // Graphics pipelines render without render pass objects.
+        let mut vk13_device_features = vk::PhysicalDeviceVulkan13Features::builder()
+            .dynamic_rendering(true)
+            .build();
+
         let device_create_info = vk::DeviceCreateInfo::builder()
             .queue_create_infos(&queue_create_infos)
             .enabled_extension_names(&device_extensions_ptrs)
             .enabled_features(&device_features)
             .push_next(&mut vk11_device_features)
+            .push_next(&mut vk13_device_features)
             .build();
 
         let device = unsafe {
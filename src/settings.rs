@@ -0,0 +1,517 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use serde::{Deserialize, Serialize};
+use crate::camera::CameraMode;
+use crate::utility;
+use crate::renderer::{
+    PostSettings,
+    RenderScale,
+    RenderSettings,
+    Tonemapper,
+    UpscaleFilter,
+    VsyncMode,
+    DEFAULT_TARGET_FRAME_TIME,
+    MAX_FRAMES_IN_FLIGHT,
+    MAX_RENDER_SCALE,
+    MIN_RENDER_SCALE,
+};
+
+/// Directory the player's files are kept in, under the platform's config directory.
+const CONFIG_DIRECTORY: &str = "industra";
+pub const SETTINGS_FILE: &str = "settings.toml";
+
+/// Where the player's `file` is kept. Falls back to the working directory on platforms without a config
+/// directory.
+pub fn config_path(file: &str) -> PathBuf {
+    match dirs::config_dir() {
+        Some(directory) => directory.join(CONFIG_DIRECTORY).join(file),
+        None => PathBuf::from(file),
+    }
+}
+
+/// Writes `contents` to `path`, creating its directory if needed.
+pub fn write_config(path: &Path, contents: &str) -> std::io::Result<()> {
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+
+    std::fs::write(path, contents)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowMode {
+    Windowed,
+    /// A window covering the monitor. Switches quickly and keeps the desktop's video mode.
+    Borderless,
+    /// Takes the monitor over with a video mode of its own.
+    Exclusive,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderScaleMode {
+    /// Always renders at `render_scale`.
+    Fixed,
+    /// Renders at up to `render_scale`, lowering it while frames take longer than `target_frame_time`.
+    Dynamic,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    pub title: String,
+    pub mode: WindowMode,
+    /// Name of the monitor to go fullscreen on. Empty, or a monitor that isn't connected, uses the one the
+    /// window is on.
+    pub monitor: String,
+    /// Size of the window in logical pixels, which the monitor's scale factor turns into physical ones.
+    /// Kept up to date as the window is resized.
+    pub width: u32,
+    pub height: u32,
+    /// Desktop position of the window's top left corner in physical pixels, kept up to date as the window is
+    /// moved. Left to the platform when missing.
+    pub position: Option<[i32; 2]>,
+    /// Video mode for exclusive fullscreen in physical pixels. Zero uses the monitor's own size.
+    pub fullscreen_width: u32,
+    pub fullscreen_height: u32,
+    /// Refresh rate for exclusive fullscreen in hertz. Zero uses the highest available.
+    pub refresh_rate: u32,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        WindowSettings {
+            title: String::from("Industria"),
+            mode: WindowMode::Windowed,
+            monitor: String::new(),
+            width: 800,
+            height: 600,
+            position: None,
+            fullscreen_width: 0,
+            fullscreen_height: 0,
+            refresh_rate: 0,
+        }
+    }
+}
+
+/// The parts of [`RenderSettings`] players choose. Debug views and effect parameters are left to the
+/// debug UI.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    pub vsync: VsyncMode,
+    pub frames_in_flight: u32,
+    /// 0 disables the limiter.
+    pub frame_rate_limit: u32,
+    pub render_scale_mode: RenderScaleMode,
+    pub render_scale: f32,
+    /// Frame time dynamic scaling aims for, in milliseconds.
+    pub target_frame_time: f32,
+    pub upscale_filter: UpscaleFilter,
+    pub tonemapper: Tonemapper,
+    pub exposure: f32,
+    pub hdr_output: bool,
+    pub hdr_paper_white: f32,
+    pub ambient_occlusion: bool,
+    pub temporal_blend: f32,
+    pub fog: bool,
+    pub bloom: bool,
+    pub color_grading: bool,
+    pub fxaa: bool,
+    /// Takes effect after a restart.
+    pub max_instances: u32,
+    /// Loads the Vulkan validation layer. Missing follows the build, on in debug builds only, so the file
+    /// debug and release builds share doesn't pin either. Takes effect after a restart.
+    pub validation: Option<bool>,
+    /// Part of the name of the GPU to render with, ignoring case. Empty picks the first one that works.
+    /// Takes effect after a restart.
+    pub gpu: String,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        let render = RenderSettings::default();
+
+        let (render_scale_mode, target_frame_time) = match render.render_scale {
+            RenderScale::Fixed(_) => (RenderScaleMode::Fixed, DEFAULT_TARGET_FRAME_TIME),
+            RenderScale::Dynamic { target_frame_time, .. } => (RenderScaleMode::Dynamic, target_frame_time),
+        };
+
+        GraphicsSettings {
+            vsync: render.vsync,
+            frames_in_flight: render.frames_in_flight,
+            frame_rate_limit: render.frame_rate_limit.unwrap_or(0),
+            render_scale_mode,
+            render_scale: render.render_scale.max_scale(),
+            target_frame_time: target_frame_time as f32 / 1000.0,
+            upscale_filter: render.upscale_filter,
+            tonemapper: render.tonemapper,
+            exposure: render.exposure,
+            hdr_output: render.hdr_output,
+            hdr_paper_white: render.hdr_paper_white,
+            ambient_occlusion: render.ambient_occlusion,
+            temporal_blend: render.temporal_blend,
+            fog: render.post.fog,
+            bloom: render.post.bloom,
+            color_grading: render.post.color_grading,
+            fxaa: render.post.fxaa,
+            max_instances: render.max_instance_count,
+            validation: None,
+            gpu: String::new(),
+        }
+    }
+}
+
+impl GraphicsSettings {
+    /// `settings` with these applied. Everything the player doesn't choose is kept.
+    pub fn apply_to(&self, settings: RenderSettings) -> RenderSettings {
+        RenderSettings {
+            vsync: self.vsync,
+            frames_in_flight: self.frames_in_flight,
+            frame_rate_limit: Some(self.frame_rate_limit).filter(|limit| *limit > 0),
+            render_scale: match self.render_scale_mode {
+                RenderScaleMode::Fixed => RenderScale::Fixed(self.render_scale),
+                RenderScaleMode::Dynamic => RenderScale::Dynamic {
+                    target_frame_time: (self.target_frame_time * 1000.0) as u128,
+                    min: MIN_RENDER_SCALE,
+                    max: self.render_scale,
+                },
+            },
+            upscale_filter: self.upscale_filter,
+            tonemapper: self.tonemapper,
+            exposure: self.exposure,
+            hdr_output: self.hdr_output,
+            hdr_paper_white: self.hdr_paper_white,
+            ambient_occlusion: self.ambient_occlusion,
+            temporal_blend: self.temporal_blend,
+            post: PostSettings {
+                fog: self.fog,
+                bloom: self.bloom,
+                color_grading: self.color_grading,
+                fxaa: self.fxaa,
+                ..settings.post
+            },
+            max_instance_count: self.max_instances,
+            validation: self.validation.unwrap_or(settings.validation),
+            ..settings
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputSettings {
+    /// Multiplies mouse and stick look speed.
+    pub look_sensitivity: f32,
+    pub invert_look_y: bool,
+    /// Whether moving the cursor to the edge of the window pans the orbit camera.
+    pub edge_panning: bool,
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        InputSettings {
+            look_sensitivity: 1.0,
+            invert_look_y: false,
+            edge_panning: true,
+        }
+    }
+}
+
+/// Volumes from 0.0 to 1.0. There's no audio yet; these are kept for when there is.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub effects_volume: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            master_volume: 1.0,
+            music_volume: 0.7,
+            effects_volume: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameplaySettings {
+    /// Length of a full day/night cycle, in seconds.
+    pub day_length: f32,
+    /// Camera the game starts with.
+    pub camera_mode: CameraMode,
+}
+
+impl Default for GameplaySettings {
+    fn default() -> Self {
+        GameplaySettings {
+            day_length: 120.0,
+            camera_mode: CameraMode::Orbit,
+        }
+    }
+}
+
+/// A settings file that couldn't be read or parsed.
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Io(error) => write!(f, "{}", error),
+            SettingsError::Parse(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Everything the player can configure, besides bindings. Missing settings take their defaults.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub window: WindowSettings,
+    pub graphics: GraphicsSettings,
+    pub input: InputSettings,
+    pub audio: AudioSettings,
+    pub gameplay: GameplaySettings,
+}
+
+impl Settings {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SettingsError> {
+        let source = std::fs::read_to_string(path).map_err(SettingsError::Io)?;
+
+        Self::from_toml(&source).map_err(SettingsError::Parse)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        write_config(path.as_ref(), &self.to_toml())
+    }
+
+    /// Unknown settings are ignored with a warning, likely typos or leftovers from older versions, and
+    /// values out of range are clamped into it.
+    pub fn from_toml(source: &str) -> Result<Self, toml::de::Error> {
+        let (mut settings, unknown) = Self::parse(source)?;
+
+        for path in unknown {
+            log::warn!("Ignoring unknown setting {}.", path);
+        }
+
+        settings.validate();
+
+        Ok(settings)
+    }
+
+    /// The settings in `source` and the paths of the ones that aren't known.
+    fn parse(source: &str) -> Result<(Self, Vec<String>), toml::de::Error> {
+        let mut unknown = Vec::new();
+        let settings = serde_ignored::deserialize(toml::Deserializer::new(source), |path| {
+            unknown.push(path.to_string());
+        })?;
+
+        Ok((settings, unknown))
+    }
+
+    pub fn to_toml(&self) -> String {
+        let mut value = toml::Value::try_from(self).expect("Failed to serialize settings.");
+        utility::shorten_floats(&mut value);
+
+        toml::to_string(&value).expect("Failed to serialize settings.")
+    }
+
+    /// Clamps every value into its valid range, with a warning for each one that wasn't.
+    pub fn validate(&mut self) {
+        clamp_setting("window.width", &mut self.window.width, 320..=16384);
+        clamp_setting("window.height", &mut self.window.height, 240..=16384);
+        clamp_setting("window.fullscreen_width", &mut self.window.fullscreen_width, 0..=16384);
+        clamp_setting("window.fullscreen_height", &mut self.window.fullscreen_height, 0..=16384);
+        clamp_setting("window.refresh_rate", &mut self.window.refresh_rate, 0..=1000);
+
+        let graphics = &mut self.graphics;
+        clamp_setting("graphics.frames_in_flight", &mut graphics.frames_in_flight, 1..=MAX_FRAMES_IN_FLIGHT);
+        clamp_setting("graphics.frame_rate_limit", &mut graphics.frame_rate_limit, 0..=1000);
+        clamp_setting("graphics.render_scale", &mut graphics.render_scale, MIN_RENDER_SCALE..=MAX_RENDER_SCALE);
+        clamp_setting("graphics.target_frame_time", &mut graphics.target_frame_time, 1.0..=100.0);
+        clamp_setting("graphics.exposure", &mut graphics.exposure, 0.01..=16.0);
+        clamp_setting("graphics.hdr_paper_white", &mut graphics.hdr_paper_white, 80.0..=1000.0);
+        clamp_setting("graphics.temporal_blend", &mut graphics.temporal_blend, 0.01..=1.0);
+        clamp_setting("graphics.max_instances", &mut graphics.max_instances, 1..=65536);
+
+        clamp_setting("input.look_sensitivity", &mut self.input.look_sensitivity, 0.05..=10.0);
+
+        clamp_setting("audio.master_volume", &mut self.audio.master_volume, 0.0..=1.0);
+        clamp_setting("audio.music_volume", &mut self.audio.music_volume, 0.0..=1.0);
+        clamp_setting("audio.effects_volume", &mut self.audio.effects_volume, 0.0..=1.0);
+
+        clamp_setting("gameplay.day_length", &mut self.gameplay.day_length, 10.0..=3600.0);
+    }
+}
+
+/// Clamps `value` into `range`, warning with `name` if it was outside.
+pub fn clamp_setting<T: PartialOrd + Copy + fmt::Display>(name: &str, value: &mut T, range: RangeInclusive<T>) {
+    // Also catches NaN, which compares false with everything.
+    if !range.contains(value) {
+        let clamped = if *value > *range.end() { *range.end() } else { *range.start() };
+
+        log::warn!("Setting {} is {}, outside {}..={}. Using {}.", name, value, range.start(), range.end(), clamped);
+        *value = clamped;
+    }
+}
+
+/// The settings file, reloaded whenever it changes on disk so edits apply while the game runs.
+pub struct SettingsFile {
+    path: PathBuf,
+    settings: Settings,
+    modified: Option<SystemTime>,
+    /// Whether the file on disk loaded, so saving over it won't lose the player's edits.
+    is_loaded: bool,
+}
+
+impl SettingsFile {
+    /// Loads the settings at `path`. If there are none yet, the defaults are written there for the player
+    /// to edit; if they can't be loaded, the defaults are used until the file is fixed.
+    pub fn open(path: PathBuf) -> Self {
+        let (settings, is_loaded) = if path.exists() {
+            match Settings::load(&path) {
+                Ok(settings) => (settings, true),
+                Err(error) => {
+                    log::warn!("Failed to load settings, using the defaults, {}.", error);
+                    (Settings::default(), false)
+                }
+            }
+        } else {
+            let settings = Settings::default();
+
+            if let Err(error) = settings.save(&path) {
+                log::warn!("Failed to save default settings, {}.", error);
+            }
+
+            (settings, true)
+        };
+
+        let modified = modified_time(&path);
+
+        Self { path, settings, modified, is_loaded }
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Reloads the file if it changed since it was last read. Returns the settings from before the change,
+    /// so callers can compare them and apply what changed. A file that fails to load keeps the current
+    /// settings.
+    pub fn poll(&mut self) -> Option<Settings> {
+        let modified = modified_time(&self.path);
+
+        if modified == self.modified {
+            return None;
+        }
+
+        self.modified = modified;
+
+        let result = Settings::load(&self.path);
+        self.is_loaded = result.is_ok();
+
+        match result {
+            Ok(settings) if settings != self.settings => {
+                log::info!("Reloaded settings from {}.", self.path.display());
+                Some(std::mem::replace(&mut self.settings, settings))
+            }
+            Ok(_) => None,
+            Err(error) => {
+                log::warn!("Failed to reload settings, keeping the current ones, {}.", error);
+                None
+            }
+        }
+    }
+
+    /// Changes the settings and saves them, for what the game keeps track of itself, like the window's
+    /// position. The next poll doesn't report the change. A file that failed to load is left alone until
+    /// the player fixes it.
+    pub fn modify(&mut self, modify: impl FnOnce(&mut Settings)) {
+        modify(&mut self.settings);
+
+        if !self.is_loaded {
+            return;
+        }
+
+        if let Err(error) = self.settings.save(&self.path) {
+            log::warn!("Failed to save settings, {}.", error);
+        }
+
+        self.modified = modified_time(&self.path);
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_round_trip() {
+        let settings = Settings::default();
+
+        assert_eq!(Settings::from_toml(&settings.to_toml()).unwrap(), settings);
+        assert_eq!(Settings::from_toml("").unwrap(), settings);
+    }
+
+    #[test]
+    fn validation_follows_the_build_unless_set() {
+        let render = RenderSettings::default();
+        assert_eq!(render.validation, cfg!(debug_assertions));
+
+        let mut settings = Settings::default();
+        assert!(!settings.to_toml().contains("validation"));
+        assert_eq!(settings.graphics.apply_to(render).validation, cfg!(debug_assertions));
+
+        settings.graphics.validation = Some(!cfg!(debug_assertions));
+        assert_eq!(settings.graphics.apply_to(render).validation, !cfg!(debug_assertions));
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped() {
+        let settings = Settings::from_toml(
+            "[window]\nwidth = 10\n[graphics]\nframes_in_flight = 99\nexposure = nan\n[audio]\nmusic_volume = 1.5\n",
+        ).unwrap();
+
+        assert_eq!(settings.window.width, 320);
+        assert_eq!(settings.graphics.frames_in_flight, MAX_FRAMES_IN_FLIGHT);
+        assert_eq!(settings.graphics.exposure, 0.01);
+        assert_eq!(settings.audio.music_volume, 1.0);
+    }
+
+    #[test]
+    fn clamp_setting_leaves_values_in_range() {
+        let mut value = 0.5;
+        clamp_setting("value", &mut value, 0.0..=1.0);
+        assert_eq!(value, 0.5);
+
+        let mut value = -2;
+        clamp_setting("value", &mut value, 0..=10);
+        assert_eq!(value, 0);
+    }
+
+    #[test]
+    fn unknown_settings_are_reported() {
+        let (settings, unknown) = Settings::parse(
+            "speed = 2\n[graphics]\nvsycn = \"off\"\nfxaa = false\n[input]\nlook_sensitivity = 2.0\n",
+        ).unwrap();
+
+        assert_eq!(unknown, ["speed", "graphics.vsycn"]);
+        assert!(!settings.graphics.fxaa);
+        assert_eq!(settings.input.look_sensitivity, 2.0);
+    }
+}
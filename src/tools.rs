@@ -0,0 +1,212 @@
+use std::fmt;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use glam::{UVec3, Vec2, Vec3};
+use crate::camera::Camera;
+use crate::material::MaterialRegistry;
+use crate::utility::Clock;
+use crate::voxel::{self, VoxError, VoxelOctree};
+
+/// Direction the thumbnail's light comes from.
+const THUMBNAIL_SUN: Vec3 = Vec3::new(0.36, 0.8, 0.48);
+/// Light that reaches surfaces facing away from the sun, or in its shadow.
+const THUMBNAIL_AMBIENT: f32 = 0.3;
+
+/// A tool that couldn't finish.
+#[derive(Debug)]
+pub enum ToolError {
+    Vox(VoxError),
+    Io(std::io::Error),
+    Png(png::EncodingError),
+}
+
+impl fmt::Display for ToolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ToolError::Vox(error) => write!(f, "{}", error),
+            ToolError::Io(error) => write!(f, "{}", error),
+            ToolError::Png(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Renders `world` from above at one of its corners, framing all of it, into a `size` by `size` PNG.
+///
+/// This approximates the game's look rather than reproducing it. Every ray is cast with
+/// [`VoxelOctree::raycast`], so the picture shows what picking would hit, lit by a fixed sun with hard
+/// shadows and a flat ambient term. None of `voxel.comp`'s shading is shared: there's no sky, ambient
+/// occlusion, reflection, refraction, point lights, fog or tonemapping, and glass and water are opaque.
+pub fn render_thumbnail(world: &VoxelOctree, materials: &MaterialRegistry, output: &Path, size: u32) -> Result<(), ToolError> {
+    let extent = world.size() as f32;
+    let center = Vec3::splat(extent * 0.5);
+    let forward = Vec3::new(1.0, -0.8, 1.0).normalize();
+
+    let mut camera = Camera {
+        rotation: Vec3::new(forward.y.asin(), forward.x.atan2(forward.z), 0.0),
+        ..Default::default()
+    };
+
+    // Far enough for the bounding sphere to fit the view.
+    let radius = extent * 3f32.sqrt() * 0.5;
+    camera.position = center - forward * radius / (camera.fov * 0.5).sin();
+
+    let sun = THUMBNAIL_SUN.normalize();
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+
+    for y in 0..size {
+        for x in 0..size {
+            let ndc = (Vec2::new(x as f32, y as f32) + 0.5) / size as f32 * 2.0 - 1.0;
+            let direction = camera.ray_direction(ndc, 1.0);
+
+            let Some(hit) = world.raycast(camera.position, direction, f32::MAX) else {
+                pixels.extend_from_slice(&[0; 4]);
+                continue;
+            };
+
+            let material = world.get(hit.voxel).and_then(|voxel| materials.get(voxel.material));
+            let Some(material) = material else {
+                pixels.extend_from_slice(&[0; 4]);
+                continue;
+            };
+
+            let normal = hit.normal.as_vec3();
+            let point = camera.position + direction * hit.distance + normal * 1e-3;
+
+            let is_lit = world.raycast(point, sun, f32::MAX).is_none();
+            let diffuse = if is_lit { normal.dot(sun).max(0.0) } else { 0.0 };
+            let light = THUMBNAIL_AMBIENT + (1.0 - THUMBNAIL_AMBIENT) * diffuse;
+
+            let color = Vec3::from(material.albedo) * light
+                + Vec3::from(material.emission) * material.emission_strength;
+
+            pixels.extend(color.to_array().map(linear_to_srgb));
+            pixels.push(255);
+        }
+    }
+
+    let file = File::create(output).map_err(ToolError::Io)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), size, size);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(ToolError::Png)?;
+    writer.write_image_data(&pixels).map_err(ToolError::Png)?;
+
+    log::info!("Wrote a {}x{} thumbnail to {}.", size, size, output.display());
+
+    Ok(())
+}
+
+/// Writes the palette colors the model at `input` uses as a material file at `output`.
+pub fn convert_vox(input: &Path, output: &Path) -> Result<(), ToolError> {
+    let mut materials = MaterialRegistry::new();
+    let octree = voxel::load_vox(input, &mut materials).map_err(ToolError::Vox)?;
+
+    std::fs::write(output, materials.to_toml()).map_err(ToolError::Io)?;
+
+    log::info!(
+        "Wrote {} materials of a {}³ model to {}.",
+        materials.materials().len() - 1,
+        octree.size(),
+        output.display(),
+    );
+
+    Ok(())
+}
+
+/// Times looking up every cell of `world`, building a copy of it, and casting `ray_count` rays through it
+/// between random points around it. Prints the results.
+pub fn bench(world: &VoxelOctree, ray_count: u32, seed: u64) {
+    let size = world.size();
+
+    let clock = Clock::new();
+    let mut voxels = Vec::new();
+
+    for x in 0..size {
+        for y in 0..size {
+            for z in 0..size {
+                let position = UVec3::new(x, y, z);
+
+                if let Some(voxel) = world.get(position) {
+                    voxels.push((position, *voxel));
+                }
+            }
+        }
+    }
+
+    let lookup_time = clock.elapsed();
+    let clock = Clock::new();
+    let mut copy = VoxelOctree::new(world.depth());
+
+    for (position, voxel) in &voxels {
+        copy.set(*position, Some(*voxel));
+    }
+
+    let build_time = clock.elapsed();
+
+    // Rays run between points on a sphere around the world, so most of them cross it.
+    let mut random = SplitMix64(seed);
+    let center = Vec3::splat(size as f32 * 0.5);
+    let radius = size as f32;
+    let rays: Vec<_> = (0..ray_count)
+        .map(|_| {
+            let from = center + random.unit_vector() * radius;
+            let to = center + random.unit_vector() * radius;
+
+            (from, (to - from).normalize_or(Vec3::Y))
+        })
+        .collect();
+
+    let clock = Clock::new();
+    let hit_count = rays.iter().filter(|(origin, direction)| world.raycast(*origin, *direction, f32::MAX).is_some()).count();
+    let raycast_time = clock.elapsed().max(1);
+
+    let cell_count = (size as u128).pow(3);
+
+    println!("World: {}³ cells, {} voxels, {} nodes.", size, voxels.len(), world.nodes().len());
+    println!("Lookups: {:.2} ms, {} ns per cell.", lookup_time as f64 / 1000.0, lookup_time * 1000 / cell_count);
+    println!("Build: {:.2} ms, {} nodes.", build_time as f64 / 1000.0, copy.nodes().len());
+    println!(
+        "Raycasts: {:.2} ms, {:.2} million rays per second, {:.1}% hit.",
+        raycast_time as f64 / 1000.0,
+        ray_count as f64 / raycast_time as f64,
+        hit_count as f64 / ray_count.max(1) as f64 * 100.0,
+    );
+}
+
+/// Small and fast, and the same on every platform, so a seed always gives the same rays.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+
+        z ^ (z >> 31)
+    }
+
+    /// From 0.0 up to but not including 1.0.
+    fn unit(&mut self) -> f32 {
+        (self.next() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniformly distributed over the sphere.
+    fn unit_vector(&mut self) -> Vec3 {
+        let y = self.unit() * 2.0 - 1.0;
+        let (sin, cos) = (self.unit() * std::f32::consts::TAU).sin_cos();
+        let ring = (1.0 - y * y).sqrt();
+
+        Vec3::new(ring * cos, y, ring * sin)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let encoded = if value <= 0.0031308 { value * 12.92 } else { 1.055 * value.powf(1.0 / 2.4) - 0.055 };
+
+    (encoded * 255.0 + 0.5) as u8
+}
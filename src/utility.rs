@@ -19,6 +19,17 @@ impl Clock {
     }
 }
 
+/// Writes floats in TOML the way they were written as `f32`, so 0.1 doesn't come out as
+/// 0.10000000149011612.
+pub fn shorten_floats(value: &mut toml::Value) {
+    match value {
+        toml::Value::Float(float) => *float = (*float as f32).to_string().parse().unwrap_or(*float),
+        toml::Value::Table(table) => table.iter_mut().for_each(|(_, value)| shorten_floats(value)),
+        toml::Value::Array(array) => array.iter_mut().for_each(shorten_floats),
+        _ => {}
+    }
+}
+
 pub mod fs {
     use std::{io::Cursor, path::Path};
 
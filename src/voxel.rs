@@ -0,0 +1,422 @@
+mod vox;
+
+use glam::{BVec3, IVec3, UVec3, Vec3, Vec3Swizzles};
+
+pub use vox::{load_vox, VoxError};
+
+/// Matches `MAX_STEPS` in `voxel.comp`.
+const RAYCAST_MAX_STEPS: u32 = 512;
+/// Matches `STEP_EPSILON` in `voxel.comp`.
+const RAYCAST_STEP_EPSILON: f32 = 1e-3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Voxel {
+    /// Id in the [`crate::material::MaterialRegistry`] the renderer was given.
+    pub material: u32,
+}
+
+impl Voxel {
+    pub fn new(material: u32) -> Self {
+        Voxel { material }
+    }
+}
+
+/// A node of a [`VoxelOctree`]. Child `i` covers the octant with `x = i & 1`, `y = (i >> 1) & 1` and
+/// `z = (i >> 2) & 1`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VoxelOctreeNode {
+    /// Index of the child node, or of the voxel if the child is a leaf.
+    pub branches: [u32; 8],
+    /// Which children exist.
+    pub child_mask: u8,
+    /// Which existing children are voxels rather than nodes.
+    pub leaf_mask: u8,
+}
+
+impl VoxelOctreeNode {
+    /// Masks in the layout `voxel.comp` expects: child mask in the low byte, leaf mask in the high byte.
+    pub fn masks(&self) -> u16 {
+        self.child_mask as u16 | (self.leaf_mask as u16) << 8
+    }
+}
+
+/// Result of [`VoxelOctree::raycast`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VoxelRaycastHit {
+    pub voxel: UVec3,
+    /// Normal of the face the ray entered the voxel through.
+    pub normal: IVec3,
+    /// Distance along the ray to that face.
+    pub distance: f32,
+}
+
+/// A sparse voxel octree covering `size()` voxels along each axis. The root is node 0.
+#[derive(Clone)]
+pub struct VoxelOctree {
+    depth: u32,
+    nodes: Vec<VoxelOctreeNode>,
+    voxels: Vec<Voxel>,
+    /// Voxel slots cleared since they were filled, reused before new ones are added.
+    free_voxels: Vec<u32>,
+}
+
+impl VoxelOctree {
+    pub fn new(depth: u32) -> Self {
+        assert!((1..=16).contains(&depth), "Octree depth must be between 1 and 16.");
+
+        VoxelOctree {
+            depth,
+            nodes: vec![VoxelOctreeNode::default()],
+            voxels: Vec::new(),
+            free_voxels: Vec::new(),
+        }
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn size(&self) -> u32 {
+        1 << self.depth
+    }
+
+    pub fn nodes(&self) -> &[VoxelOctreeNode] {
+        &self.nodes
+    }
+
+    pub fn voxels(&self) -> &[Voxel] {
+        &self.voxels
+    }
+}
+
+impl VoxelOctree {
+    /// Sets or clears the voxel at `position`. Cleared voxel slots are reused by later voxels, and emptied
+    /// nodes are kept for when the voxels around them are set again, so clearing doesn't shrink the tree.
+    pub fn set(&mut self, position: UVec3, voxel: Option<Voxel>) {
+        assert!(position.max_element() < self.size(), "Voxel position {} outside of octree.", position);
+
+        let mut node_index = 0;
+
+        for level in (0..self.depth).rev() {
+            let child = Self::child_index(position, level);
+            let bit = 1u8 << child;
+            let is_last_level = level == 0;
+
+            let node = self.nodes[node_index];
+
+            if node.child_mask & bit == 0 {
+                let Some(voxel) = voxel else {
+                    return;
+                };
+
+                let branch = if is_last_level {
+                    self.add_voxel(voxel)
+                } else {
+                    self.nodes.push(VoxelOctreeNode::default());
+                    self.nodes.len() as u32 - 1
+                };
+
+                let node = &mut self.nodes[node_index];
+                node.child_mask |= bit;
+                node.branches[child] = branch;
+
+                if is_last_level {
+                    node.leaf_mask |= bit;
+                    return;
+                }
+            } else if is_last_level {
+                let node = &mut self.nodes[node_index];
+
+                match voxel {
+                    Some(voxel) => self.voxels[node.branches[child] as usize] = voxel,
+                    // The slot is freed rather than compacted, so other indices stay valid.
+                    None => {
+                        node.child_mask &= !bit;
+                        node.leaf_mask &= !bit;
+                        self.free_voxels.push(node.branches[child]);
+                    }
+                }
+
+                return;
+            }
+
+            node_index = self.nodes[node_index].branches[child] as usize;
+        }
+    }
+
+    pub fn get(&self, position: UVec3) -> Option<&Voxel> {
+        if position.max_element() >= self.size() {
+            return None;
+        }
+
+        let mut node_index = 0;
+
+        for level in (0..self.depth).rev() {
+            let child = Self::child_index(position, level);
+            let node = &self.nodes[node_index];
+
+            if node.child_mask & (1 << child) == 0 {
+                return None;
+            }
+
+            if node.leaf_mask & (1 << child) != 0 {
+                return Some(&self.voxels[node.branches[child] as usize]);
+            }
+
+            node_index = node.branches[child] as usize;
+        }
+
+        None
+    }
+
+    /// Casts a ray in octree space. Steps through the octree exactly like `trace` in `voxel.comp`, so both
+    /// report the same hit.
+    pub fn raycast(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<VoxelRaycastHit> {
+        let size = self.size() as f32;
+
+        let inverse_direction = direction.abs().max(Vec3::splat(1e-8)).recip() * sign(direction + 1e-12);
+
+        let t0 = -origin * inverse_direction;
+        let t1 = (Vec3::splat(size) - origin) * inverse_direction;
+        let t_near = t0.min(t1);
+        let t_far = t0.max(t1);
+
+        let mut t = t_near.max_element().max(0.0);
+        let t_exit = t_far.min_element().min(max_distance);
+
+        if t > t_exit {
+            return None;
+        }
+
+        // Entry face of the root. From inside the octree, fall back to facing the ray.
+        let mut normal = -sign(direction) * mask(t_near.cmpeq(Vec3::splat(t)));
+
+        if t == 0.0 {
+            let axis = direction.abs();
+            normal = -sign(direction) * mask(axis.cmpge(axis.yzx().max(axis.zxy())));
+        }
+
+        for _ in 0..RAYCAST_MAX_STEPS {
+            if t > t_exit {
+                break;
+            }
+
+            let position = (origin + direction * t).clamp(Vec3::ZERO, Vec3::splat(size - RAYCAST_STEP_EPSILON));
+
+            let mut node_index = 0;
+            let mut cell_min = Vec3::ZERO;
+            let mut cell_size = size;
+
+            for _ in 0..self.depth {
+                cell_size *= 0.5;
+
+                let octant = position.cmpge(cell_min + cell_size);
+                let child = octant.bitmask() as usize;
+                cell_min += mask(octant) * cell_size;
+
+                let node = &self.nodes[node_index];
+
+                if node.child_mask & (1 << child) == 0 {
+                    break;
+                }
+
+                if node.leaf_mask & (1 << child) != 0 {
+                    return Some(VoxelRaycastHit {
+                        voxel: cell_min.as_uvec3(),
+                        normal: normal.as_ivec3(),
+                        distance: t,
+                    });
+                }
+
+                node_index = node.branches[child] as usize;
+            }
+
+            // Step out of the empty cell, remembering which face the next one is entered through. Axes the ray
+            // doesn't move along have a positive inverse direction, so they take the far face too.
+            let cell_max = cell_min + cell_size;
+            let t_cell = (Vec3::select(direction.cmpge(Vec3::ZERO), cell_max, cell_min) - origin) * inverse_direction;
+            let t_next = t_cell.min_element();
+
+            normal = -sign(direction) * mask(t_cell.cmpeq(Vec3::splat(t_next)));
+            t = t_next.max(t) + RAYCAST_STEP_EPSILON;
+        }
+
+        None
+    }
+
+    /// Puts `voxel` in a free slot, or a new one if there are none, and returns its index.
+    fn add_voxel(&mut self, voxel: Voxel) -> u32 {
+        match self.free_voxels.pop() {
+            Some(index) => {
+                self.voxels[index as usize] = voxel;
+                index
+            }
+            None => {
+                self.voxels.push(voxel);
+                self.voxels.len() as u32 - 1
+            }
+        }
+    }
+
+    fn child_index(position: UVec3, level: u32) -> usize {
+        let bits = (position >> level) & 1;
+
+        (bits.x | bits.y << 1 | bits.z << 2) as usize
+    }
+}
+
+/// GLSL's `sign`, which unlike [`f32::signum`] is zero for zero.
+fn sign(v: Vec3) -> Vec3 {
+    Vec3::select(v.cmpeq(Vec3::ZERO), Vec3::ZERO, v.signum())
+}
+
+/// GLSL's `vec3(bvec3)`.
+fn mask(b: BVec3) -> Vec3 {
+    Vec3::select(b, Vec3::ONE, Vec3::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An 8³ octree with a floor at y = 0, a pillar at x = 5, z = 2 up to y = 4, and a lone voxel at the
+    /// top of the far corner.
+    fn test_octree() -> VoxelOctree {
+        let mut octree = VoxelOctree::new(3);
+
+        for x in 0..8 {
+            for z in 0..8 {
+                octree.set(UVec3::new(x, 0, z), Some(Voxel::new(1)));
+            }
+        }
+
+        for y in 1..=4 {
+            octree.set(UVec3::new(5, y, 2), Some(Voxel::new(2)));
+        }
+
+        octree.set(UVec3::new(6, 7, 6), Some(Voxel::new(3)));
+
+        octree
+    }
+
+    /// Steps through every cell along the ray, the way `voxel.comp` would if the octree were a dense grid.
+    /// The ray must start outside the octree.
+    fn reference_raycast(octree: &VoxelOctree, origin: Vec3, direction: Vec3) -> Option<VoxelRaycastHit> {
+        let size = octree.size() as f32;
+        let t0 = -origin / direction;
+        let t1 = (Vec3::splat(size) - origin) / direction;
+        let t_near = t0.min(t1);
+        let t_exit = t0.max(t1).min_element();
+
+        let mut t = t_near.max_element();
+
+        if t > t_exit || t < 0.0 {
+            return None;
+        }
+
+        let step = sign(direction).as_ivec3();
+        let mut axis = (0..3).max_by(|a, b| t_near[*a].total_cmp(&t_near[*b])).unwrap();
+        let mut cell = (origin + direction * t).floor().as_ivec3().clamp(IVec3::ZERO, IVec3::splat(size as i32 - 1));
+        let mut t_max = ((cell + step.max(IVec3::ZERO)).as_vec3() - origin) / direction;
+        let t_delta = direction.abs().recip();
+
+        while cell.cmpge(IVec3::ZERO).all() && cell.cmplt(IVec3::splat(size as i32)).all() {
+            if octree.get(cell.as_uvec3()).is_some() {
+                let mut normal = IVec3::ZERO;
+                normal[axis] = -step[axis];
+
+                return Some(VoxelRaycastHit { voxel: cell.as_uvec3(), normal, distance: t });
+            }
+
+            axis = (0..3).min_by(|a, b| t_max[*a].total_cmp(&t_max[*b])).unwrap();
+            t = t_max[axis];
+            cell[axis] += step[axis];
+            t_max[axis] += t_delta[axis];
+        }
+
+        None
+    }
+
+    #[test]
+    fn cleared_voxel_slots_are_reused() {
+        let mut octree = test_octree();
+        let voxel_count = octree.voxels().len();
+        let node_count = octree.nodes().len();
+
+        for i in 0..10 {
+            octree.set(UVec3::new(5, 3, 2), None);
+            octree.set(UVec3::new(1, 0, 1), None);
+            assert_eq!(octree.get(UVec3::new(5, 3, 2)), None);
+
+            octree.set(UVec3::new(1, 0, 1), Some(Voxel::new(10 + i)));
+            octree.set(UVec3::new(5, 3, 2), Some(Voxel::new(20 + i)));
+            assert_eq!(octree.get(UVec3::new(1, 0, 1)), Some(&Voxel::new(10 + i)));
+            assert_eq!(octree.get(UVec3::new(5, 3, 2)), Some(&Voxel::new(20 + i)));
+        }
+
+        assert_eq!(octree.voxels().len(), voxel_count);
+        assert_eq!(octree.nodes().len(), node_count);
+        assert_eq!(octree.get(UVec3::new(5, 4, 2)), Some(&Voxel::new(2)));
+        assert_eq!(octree.get(UVec3::new(6, 7, 6)), Some(&Voxel::new(3)));
+    }
+
+    #[test]
+    fn raycast_hits_known_faces() {
+        let octree = test_octree();
+
+        let hit = octree.raycast(Vec3::new(-2.0, 1.5, 2.5), Vec3::X, f32::MAX).unwrap();
+        assert_eq!(hit.voxel, UVec3::new(5, 1, 2));
+        assert_eq!(hit.normal, IVec3::NEG_X);
+        assert!((hit.distance - 7.0).abs() < 2e-3);
+
+        let hit = octree.raycast(Vec3::new(5.5, 10.0, 2.5), Vec3::NEG_Y, f32::MAX).unwrap();
+        assert_eq!(hit.voxel, UVec3::new(5, 4, 2));
+        assert_eq!(hit.normal, IVec3::Y);
+        assert!((hit.distance - 5.0).abs() < 2e-3);
+
+        let hit = octree.raycast(Vec3::new(6.5, 7.5, -1.0), Vec3::Z, f32::MAX).unwrap();
+        assert_eq!(hit.voxel, UVec3::new(6, 7, 6));
+        assert_eq!(hit.normal, IVec3::NEG_Z);
+
+        assert_eq!(octree.raycast(Vec3::new(-1.0, 5.5, 0.5), Vec3::X, f32::MAX), None);
+        assert_eq!(octree.raycast(Vec3::new(-2.0, 1.5, 2.5), Vec3::X, 6.0), None);
+    }
+
+    #[test]
+    fn raycast_matches_grid_traversal() {
+        let octree = test_octree();
+        let center = Vec3::splat(4.0);
+
+        // A fixed sequence of rays from a sphere around the octree towards points inside it.
+        let mut state = 1u32;
+        let mut random = || {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            (state >> 8) as f32 / (1 << 24) as f32
+        };
+
+        for _ in 0..2000 {
+            let from = center + (Vec3::new(random(), random(), random()) * 2.0 - 1.0).normalize() * 12.0;
+            let to = Vec3::new(random(), random(), random()) * 8.0;
+            let direction = (to - from).normalize();
+
+            let hit = octree.raycast(from, direction, f32::MAX);
+            let expected = reference_raycast(&octree, from, direction);
+
+            match (hit, expected) {
+                (Some(hit), Some(expected)) => {
+                    // Within a step epsilon of an edge, either voxel along it may be hit.
+                    let point = from + direction * expected.distance;
+                    let edge_distance = (point - point.round()).abs() + expected.normal.abs().as_vec3();
+
+                    if edge_distance.min_element() > 1e-2 {
+                        assert_eq!(hit.voxel, expected.voxel, "ray from {} along {}", from, direction);
+                    }
+
+                    assert_eq!(hit.normal, expected.normal, "ray from {} along {}", from, direction);
+                    assert!((hit.distance - expected.distance).abs() < 1e-2, "ray from {} along {}", from, direction);
+                }
+                _ => assert_eq!(hit, expected, "ray from {} along {}", from, direction),
+            }
+        }
+    }
+}
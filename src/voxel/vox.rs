@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use glam::UVec3;
+use crate::material::{Material, MaterialRegistry};
+use super::{Voxel, VoxelOctree};
+
+/// Oldest version of the format the loader knows.
+const VOX_VERSION: u32 = 150;
+/// Emission strength of a fully emissive palette color.
+const EMISSION_STRENGTH: f32 = 4.0;
+
+/// A `.vox` file that couldn't be read or isn't one.
+#[derive(Debug)]
+pub enum VoxError {
+    Io(std::io::Error),
+    Invalid(&'static str),
+}
+
+impl fmt::Display for VoxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VoxError::Io(error) => write!(f, "{}", error),
+            VoxError::Invalid(reason) => write!(f, "invalid .vox file, {}", reason),
+        }
+    }
+}
+
+/// Loads the first model of a MagicaVoxel file into an octree just big enough for it. MagicaVoxel is Z up,
+/// so its Y and Z are swapped.
+///
+/// Each palette color the model uses becomes a material named after the file and the color's index, like
+/// `castle_12`. Materials already in `materials` under that name are kept, so colors can be tuned in a
+/// material file.
+pub fn load_vox<P: AsRef<Path>>(path: P, materials: &mut MaterialRegistry) -> Result<VoxelOctree, VoxError> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).map_err(VoxError::Io)?;
+    let prefix = path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+
+    let file = VoxFile::parse(&bytes)?;
+
+    let size = UVec3::new(file.size.x, file.size.z, file.size.y);
+    let depth = u32::BITS - (size.max_element().max(2) - 1).leading_zeros();
+    let mut octree = VoxelOctree::new(depth);
+
+    let mut ids = HashMap::new();
+
+    for &[x, y, z, index] in &file.voxels {
+        let position = UVec3::new(x as u32, z as u32, y as u32);
+
+        if position.cmpge(size).any() {
+            return Err(VoxError::Invalid("voxel outside of the model"));
+        }
+
+        let id = *ids.entry(index).or_insert_with(|| {
+            let name = format!("{}_{}", prefix, index);
+
+            // A full registry leaves the voxels in the default material.
+            materials.id(&name).or_else(|| materials.register(file.material(name, index))).unwrap_or(0)
+        });
+
+        octree.set(position, Some(Voxel::new(id)));
+    }
+
+    Ok(octree)
+}
+
+/// The parts of a `.vox` file the octree is built from.
+struct VoxFile {
+    size: UVec3,
+    /// X, Y, Z and palette index.
+    voxels: Vec<[u8; 4]>,
+    /// Straight sRGBA, by palette index. Index 0 is never used.
+    palette: [[u8; 4]; 256],
+    /// Properties of each `MATL` chunk, by palette index.
+    properties: HashMap<u8, HashMap<String, String>>,
+}
+
+impl VoxFile {
+    fn parse(bytes: &[u8]) -> Result<Self, VoxError> {
+        let mut reader = Reader { bytes, offset: 0 };
+
+        if reader.take(4)? != b"VOX " {
+            return Err(VoxError::Invalid("missing VOX header"));
+        }
+
+        if reader.u32()? < VOX_VERSION {
+            log::warn!("Loading a .vox file older than version {}.", VOX_VERSION);
+        }
+
+        let (id, _) = reader.chunk()?;
+
+        if id != b"MAIN" {
+            return Err(VoxError::Invalid("missing MAIN chunk"));
+        }
+
+        let mut size = None;
+        let mut voxels = None;
+        let mut model_count = 0;
+        let mut palette = default_palette();
+        let mut properties = HashMap::new();
+
+        // The rest are the children of MAIN. Scene graph chunks are skipped, along with any unknown ones.
+        while !reader.is_empty() {
+            let (id, mut content) = reader.chunk()?;
+
+            match id {
+                b"SIZE" => {
+                    model_count += 1;
+
+                    if size.is_none() {
+                        size = Some(UVec3::new(content.u32()?, content.u32()?, content.u32()?));
+                    }
+                }
+                b"XYZI" if voxels.is_none() => {
+                    let count = content.u32()? as usize;
+                    let data = content.take(count.checked_mul(4).ok_or(VoxError::Invalid("too many voxels"))?)?;
+
+                    voxels = Some(data.chunks_exact(4).map(|voxel| [voxel[0], voxel[1], voxel[2], voxel[3]]).collect());
+                }
+                // Color i of the chunk is palette index i + 1.
+                b"RGBA" => {
+                    for color in &mut palette[1..] {
+                        *color = content.take(4)?.try_into().unwrap();
+                    }
+                }
+                b"MATL" => {
+                    let index = content.u32()?;
+                    let dictionary = content.dictionary()?;
+
+                    if let Ok(index) = u8::try_from(index) {
+                        properties.insert(index, dictionary);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if model_count > 1 {
+            log::warn!("Loading only the first of {} models in the .vox file.", model_count);
+        }
+
+        Ok(VoxFile {
+            size: size.ok_or(VoxError::Invalid("missing SIZE chunk"))?,
+            voxels: voxels.ok_or(VoxError::Invalid("missing XYZI chunk"))?,
+            palette,
+            properties,
+        })
+    }
+
+    /// The palette color at `index` as a material, with what its `MATL` chunk says about the surface.
+    fn material(&self, name: String, index: u8) -> Material {
+        let [r, g, b, _] = self.palette[index as usize];
+        let albedo = [r, g, b].map(srgb_to_linear);
+
+        let empty = HashMap::new();
+        let properties = self.properties.get(&index).unwrap_or(&empty);
+        let property = |key: &str| properties.get(key).and_then(|value| value.parse::<f32>().ok());
+
+        let mut material = Material { name, albedo, ..Default::default() };
+
+        match properties.get("_type").map(String::as_str) {
+            Some("_metal") => {
+                material.metallic = property("_metal").unwrap_or(1.0);
+                material.roughness = property("_rough").unwrap_or(0.1);
+            }
+            Some("_glass") => {
+                material.alpha = 1.0 - property("_trans").unwrap_or(0.5);
+                material.roughness = property("_rough").unwrap_or(0.1);
+                // Older versions store the index of refraction less one.
+                material.ior = property("_ri").or(property("_ior").map(|ior| ior + 1.0)).unwrap_or(1.5);
+            }
+            Some("_emit") => {
+                material.emission = albedo;
+                // Full emission is about as bright as the lamp in the default materials.
+                material.emission_strength = property("_emit").unwrap_or(1.0) * EMISSION_STRENGTH;
+            }
+            _ => {}
+        }
+
+        material
+    }
+}
+
+/// Reads little endian values from a `.vox` file, failing rather than running past its end.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.offset >= self.bytes.len()
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], VoxError> {
+        let end = self.offset.checked_add(count).filter(|end| *end <= self.bytes.len());
+        let end = end.ok_or(VoxError::Invalid("unexpected end of file"))?;
+
+        let bytes = &self.bytes[self.offset..end];
+        self.offset = end;
+
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, VoxError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, VoxError> {
+        let length = self.u32()? as usize;
+
+        Ok(String::from_utf8_lossy(self.take(length)?).into_owned())
+    }
+
+    fn dictionary(&mut self) -> Result<HashMap<String, String>, VoxError> {
+        let count = self.u32()?;
+
+        (0..count).map(|_| Ok((self.string()?, self.string()?))).collect()
+    }
+
+    /// The id and a reader over the content of the next chunk. Its children follow the content and are
+    /// read as the chunks after it.
+    fn chunk(&mut self) -> Result<(&'a [u8], Reader<'a>), VoxError> {
+        let id = self.take(4)?;
+        let content_size = self.u32()? as usize;
+        let _children_size = self.u32()?;
+
+        let content = Reader { bytes: self.take(content_size)?, offset: 0 };
+
+        Ok((id, content))
+    }
+}
+
+/// MagicaVoxel's palette for files without an `RGBA` chunk: a 6×6×6 color cube without black, then ramps
+/// of red, green, blue and gray.
+fn default_palette() -> [[u8; 4]; 256] {
+    const CUBE: [u8; 6] = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    const RAMP: [u8; 10] = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+
+    let cube = CUBE.iter().flat_map(|&r| CUBE.iter().flat_map(move |&g| CUBE.iter().map(move |&b| [r, g, b, 0xff])));
+    let ramps = (0..4).flat_map(|channel| {
+        RAMP.iter().map(move |&value| match channel {
+            3 => [value, value, value, 0xff],
+            _ => {
+                let mut color = [0, 0, 0, 0xff];
+                color[channel] = value;
+                color
+            }
+        })
+    });
+
+    let mut palette = [[0; 4]; 256];
+
+    for (entry, color) in palette[1..].iter_mut().zip(cube.take(215).chain(ramps)) {
+        *entry = color;
+    }
+
+    palette
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}